@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use backend::api::common::Point;
+use backend::game::Game;
+use backend::game_objects::hex::Hex;
+use backend::game_objects::unit::Unit;
+use backend::settings::Settings;
+
+// Random square field with the unit of the given speed in the middle
+fn game(side: u32, speed: u32) -> (Game, Hex) {
+    let settings = Settings {
+        num_x: (side, side),
+        num_y: (side, side),
+        walls_percent: (15, 15),
+        terrain_percent: (15, 15),
+        num_units: (1, 1),
+        ..Settings::default()
+    };
+    let mut game = Game::random(2, 0, settings).unwrap();
+    let middle = game.field.get_hex_mut(side / 2, side / 2).unwrap();
+    middle.set_content(None);
+    middle.set_unit(Some(Unit::new(0, 5, [1, 2], speed)));
+    let middle = *middle;
+    (game, middle)
+}
+
+// Recursive flood fill the search used before, as the baseline
+fn flood_fill(game: &Game, hex: &Hex, value: u32, max_value: u32, map: &mut HashMap<Point, u32>) {
+    if value > max_value {
+        return;
+    }
+    match map.get(&hex.to_point()) {
+        Some(old) if *old <= value => return,
+        _ => {}
+    }
+    if (hex.unit.is_some() && value != 0) || hex.move_cost().is_none() {
+        return;
+    }
+
+    map.insert(hex.to_point(), value);
+    for neighbour in game.find_neighbours(&hex.to_point()) {
+        if let Some(cost) = neighbour.move_cost() {
+            flood_fill(game, &neighbour, value + cost, max_value, map);
+        }
+    }
+}
+
+fn reachable(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reachable");
+    for (side, speed) in &[(15, 8), (50, 12), (100, 16)] {
+        let (game, unit) = game(*side, *speed);
+        let name = format!("{}x{} speed {}", side, side, speed);
+
+        group.bench_with_input(BenchmarkId::new("dijkstra", &name), &unit, |b, unit| {
+            b.iter(|| game.find_paths(unit, *speed).points())
+        });
+        group.bench_with_input(BenchmarkId::new("flood fill", &name), &unit, |b, unit| {
+            b.iter(|| {
+                let mut map = HashMap::new();
+                flood_fill(&game, unit, 0, *speed, &mut map);
+                map
+            })
+        });
+    }
+    group.finish();
+}
+
+fn get_hex(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_hex");
+    for side in &[15, 100] {
+        let (game, _) = game(*side, 1);
+        let last = side - 1;
+
+        group.bench_with_input(BenchmarkId::new("index", side), side, |b, _| {
+            b.iter(|| game.field.get_hex(last, last))
+        });
+        group.bench_with_input(BenchmarkId::new("linear search", side), side, |b, _| {
+            b.iter(|| {
+                game.field
+                    .hexes
+                    .iter()
+                    .find(|hex| hex.x == last && hex.y == last)
+                    .copied()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, reachable, get_hex);
+criterion_main!(benches);
@@ -1,31 +1,108 @@
+use crate::api::response::Error;
+use crate::communicator;
+use crate::game_server::GameServer;
 use crate::websocket::Websocket;
 use actix::{Addr, Message};
+use serde_json::Value;
+use uuid::Uuid;
 
 #[derive(Debug, Message)]
 #[rtype(result = "()")]
 pub struct Request<T> {
     pub sender: Addr<Websocket>,
+    // User the sender belongs to
+    pub user_id: Uuid,
+    // Session of the sender, it survives reconnects
+    pub session: Uuid,
+    // Id client has set in the request, it is sent back with errors
+    pub request_id: Option<Value>,
     pub payload: T,
 }
 
 impl<T> Request<T> {
-    pub fn new(addr: Addr<Websocket>, payload: T) -> Request<T> {
+    pub fn new(client: Client, request_id: Option<Value>, payload: T) -> Request<T> {
         Request {
-            sender: addr,
+            sender: client.address,
+            user_id: client.user_id,
+            session: client.session,
+            request_id,
             payload,
         }
     }
+
+    pub fn client(&self) -> Client {
+        Client::new(self.sender.clone(), self.user_id, self.session)
+    }
+
+    // Send error about this request to its sender only
+    pub fn reply_error(&self, error: Error) {
+        let error = error.for_request(self.request_id.clone());
+        communicator::broadcast(&error, vec![self.sender.clone()]);
+    }
+}
+
+// Connected user, the same session can come back with another websocket
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub address: Addr<Websocket>,
+    pub user_id: Uuid,
+    pub session: Uuid,
 }
 
+impl Client {
+    pub fn new(address: Addr<Websocket>, user_id: Uuid, session: Uuid) -> Client {
+        Client {
+            address,
+            user_id,
+            session,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Client {
+    // Client of a new user whose websocket is never read, messages to it are dropped
+    pub fn test() -> Client {
+        let (sender, _) = actix::dev::channel::channel(16);
+        Client::new(Addr::new(sender), Uuid::new_v4(), Uuid::new_v4())
+    }
+}
+
+// Sent by websocket to lobby when it is connected
 #[derive(Debug, Message)]
 #[rtype(result = "()")]
-pub struct NewClient {
+pub struct Connect {
+    pub client: Client,
+}
+
+impl Connect {
+    pub fn new(client: Client) -> Connect {
+        Connect { client }
+    }
+}
+
+// Sent by websocket to lobby when connection is lost
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct Disconnect {
     pub address: Addr<Websocket>,
 }
 
+impl Disconnect {
+    pub fn new(address: Addr<Websocket>) -> Disconnect {
+        Disconnect { address }
+    }
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct NewClient {
+    pub client: Client,
+}
+
 impl NewClient {
-    pub fn new(address: Addr<Websocket>) -> NewClient {
-        NewClient { address }
+    pub fn new(client: Client) -> NewClient {
+        NewClient { client }
     }
 }
 
@@ -33,10 +110,50 @@ impl NewClient {
 #[rtype(result = "()")]
 pub struct LooseClient {
     pub address: Addr<Websocket>,
+    // Player keeps the seat for a while, it may reconnect
+    pub disconnected: bool,
 }
 
 impl LooseClient {
-    pub fn new(address: Addr<Websocket>) -> LooseClient {
-        LooseClient { address }
+    pub fn new(address: Addr<Websocket>, disconnected: bool) -> LooseClient {
+        LooseClient {
+            address,
+            disconnected,
+        }
+    }
+}
+
+// Sent by lobby to websocket when it enters or leaves a room
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct RoomChanged {
+    pub room: Option<Addr<GameServer>>,
+}
+
+impl RoomChanged {
+    pub fn new(room: Option<Addr<GameServer>>) -> RoomChanged {
+        RoomChanged { room }
+    }
+}
+
+// Sent by room to lobby when game in the room starts or ends
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct RoomState {
+    pub room_id: Uuid,
+    pub game_started: bool,
+}
+
+impl RoomState {
+    pub fn new(room_id: Uuid, game_started: bool) -> RoomState {
+        RoomState {
+            room_id,
+            game_started,
+        }
     }
 }
+
+// Sent by lobby to room when the last client leaves it
+#[derive(Debug, Message)]
+#[rtype(result = "()")]
+pub struct CloseRoom;
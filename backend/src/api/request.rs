@@ -1,12 +1,75 @@
 use actix::Message;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use uuid::Uuid;
 
 use super::common::Point;
-use crate::api::response::Error;
+use crate::api::response::{Error, ErrorCode};
+use crate::bot::Bot;
+use crate::clock::TimerSettings;
+use crate::settings::Settings;
+
+// Every command client can send, chosen by the cmd field
+#[derive(Deserialize, Debug)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    Click(Click),
+    SkipTurn(SkipTurn),
+    Surrender(Surrender),
+    OfferDraw(OfferDraw),
+    AcceptDraw(AcceptDraw),
+    Rematch(Rematch),
+    View(ChooseView),
+    StartGame(StartGame),
+    CreateRoom(CreateRoom),
+    JoinRoom(JoinRoom),
+    LeaveRoom(LeaveRoom),
+    ListRooms(ListRooms),
+    ListReplays,
+    WatchReplay(WatchReplay),
+    ReplayStep,
+    #[serde(other)]
+    Unknown,
+}
 
-pub const CMD_CLICK: &str = "click";
-pub const CMD_SKIP_TURN: &str = "skip_turn";
-pub const CMD_START_GAME: &str = "start_game";
+// Request with the id client may set to match errors with requests
+#[derive(Debug)]
+pub struct Envelope {
+    pub id: Option<Value>,
+    pub request: Request,
+}
+
+// Parse the client message, error is ready to be sent back
+pub fn parse(text: &str) -> Result<Envelope, Error> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|error| Error::with_code(ErrorCode::BadJson, error.to_string()))?;
+    let id = value.get("id").cloned();
+
+    let cmd = match value.get("cmd") {
+        Some(Value::String(cmd)) => cmd.clone(),
+        _ => {
+            return Err(
+                Error::with_code(ErrorCode::MissingCommand, "no cmd field".to_string())
+                    .for_request(id),
+            )
+        }
+    };
+
+    match serde_json::from_value(value) {
+        Ok(Request::Unknown) => Err(Error::with_code(
+            ErrorCode::UnknownCommand,
+            format!("unknown command {}", cmd),
+        )
+        .for_request(id)),
+        Ok(request) => Ok(Envelope { id, request }),
+        Err(error) => Err(Error::with_code(
+            ErrorCode::InvalidRequest,
+            format!("{}: {}", cmd, error),
+        )
+        .for_request(id)),
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Message)]
 #[rtype(result = "Option<Error>")]
@@ -14,16 +77,163 @@ pub struct Click {
     pub target: Point,
 }
 
-impl Click {
-    pub fn from_str(s: &str) -> Click {
-        serde_json::from_str(s).unwrap()
-    }
+#[derive(Deserialize, Debug, Message)]
+#[rtype(result = "Option<Error>")]
+pub struct SkipTurn {}
+
+#[derive(Deserialize, Debug, Message)]
+#[rtype(result = "Option<Error>")]
+pub struct Surrender {}
+
+#[derive(Deserialize, Debug, Message)]
+#[rtype(result = "Option<Error>")]
+pub struct OfferDraw {}
+
+#[derive(Deserialize, Debug, Message)]
+#[rtype(result = "Option<Error>")]
+pub struct AcceptDraw {}
+
+// Play again in the same room with the same players
+#[derive(Deserialize, Debug, Message)]
+#[rtype(result = "Option<Error>")]
+pub struct Rematch {}
+
+// Spectator chooses whose eyes to watch the fog of war game with, none to see everything
+#[derive(Deserialize, Debug, Message)]
+#[rtype(result = "Option<Error>")]
+pub struct ChooseView {
+    pub player: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Message)]
+#[rtype(result = "Option<Error>")]
+pub struct StartGame {
+    // Seed to generate the game with, random if not set
+    pub seed: Option<u32>,
+    // Name of the map preset, random map if not set
+    pub preset: Option<String>,
+    // Settings of the random map, default if not set
+    pub settings: Option<Settings>,
+    // Bots taking the last player seats
+    #[serde(default)]
+    pub bots: Vec<Bot>,
+    // Time limits, no limits if not set
+    pub timer: Option<TimerSettings>,
+    // Players see only what their units see
+    #[serde(default)]
+    pub fog: bool,
+    // Surviving melee defender strikes back once per round
+    #[serde(default)]
+    pub retaliation: bool,
 }
 
-#[derive(Debug, Message)]
+#[derive(Deserialize, Debug, Message)]
 #[rtype(result = "Option<Error>")]
-pub struct SkipTurn;
+pub struct CreateRoom {}
 
-#[derive(Serialize, Message)]
+// Join room by id, or any room waiting for players if id is not set
+#[derive(Serialize, Deserialize, Debug, Message)]
 #[rtype(result = "Option<Error>")]
-pub struct StartGame;
+pub struct JoinRoom {
+    pub room_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, Debug, Message)]
+#[rtype(result = "Option<Error>")]
+pub struct LeaveRoom {}
+
+#[derive(Deserialize, Debug, Message)]
+#[rtype(result = "Option<Error>")]
+pub struct ListRooms {}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WatchReplay {
+    pub replay_id: Uuid,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_json(text: &str) -> Value {
+        serde_json::to_value(parse(text).unwrap_err()).unwrap()
+    }
+
+    #[test]
+    fn parse_commands() {
+        let envelope = parse("{\"cmd\": \"click\", \"target\": {\"x\": 1, \"y\": 2}}").unwrap();
+        assert!(envelope.id.is_none());
+        match envelope.request {
+            Request::Click(click) => assert_eq!(click.target, Point { x: 1, y: 2 }),
+            request => unreachable!("wrong request {:?}", request),
+        }
+
+        assert!(matches!(
+            parse("{\"cmd\": \"skip_turn\", \"id\": 5}").unwrap(),
+            Envelope {
+                id: Some(Value::Number(_)),
+                request: Request::SkipTurn(_),
+            }
+        ));
+        assert!(matches!(
+            parse("{\"cmd\": \"join_room\"}").unwrap().request,
+            Request::JoinRoom(JoinRoom { room_id: None })
+        ));
+        assert!(matches!(
+            parse("{\"cmd\": \"replay_step\"}").unwrap().request,
+            Request::ReplayStep
+        ));
+    }
+
+    #[test]
+    fn parse_start_game_defaults() {
+        match parse("{\"cmd\": \"start_game\"}").unwrap().request {
+            Request::StartGame(start_game) => {
+                assert!(start_game.bots.is_empty());
+                assert_eq!(start_game.timer, None);
+            }
+            request => unreachable!("wrong request {:?}", request),
+        }
+        match parse("{\"cmd\": \"start_game\", \"timer\": {}}")
+            .unwrap()
+            .request
+        {
+            Request::StartGame(start_game) => {
+                assert_eq!(start_game.timer, Some(TimerSettings::default()));
+            }
+            request => unreachable!("wrong request {:?}", request),
+        }
+    }
+
+    #[test]
+    fn parse_bad_json() {
+        let error = error_json("{\"cmd\": ");
+        assert_eq!(error["code"], "bad_json");
+        assert!(error.get("request_id").is_none());
+    }
+
+    #[test]
+    fn parse_missing_command() {
+        let error = error_json("{\"id\": \"abc\"}");
+        assert_eq!(error["code"], "missing_command");
+        assert_eq!(error["request_id"], "abc");
+    }
+
+    #[test]
+    fn parse_unknown_command() {
+        let error = error_json("{\"cmd\": \"fly\", \"id\": 7}");
+        assert_eq!(error["code"], "unknown_command");
+        assert_eq!(error["request_id"], 7);
+    }
+
+    #[test]
+    fn parse_missing_field() {
+        let error = error_json("{\"cmd\": \"click\", \"id\": 8}");
+        assert_eq!(error["cmd"], "error");
+        assert_eq!(error["code"], "invalid_request");
+        assert_eq!(error["request_id"], 8);
+
+        let error = error_json("{\"cmd\": \"watch_replay\", \"replay_id\": \"not uuid\"}");
+        assert_eq!(error["code"], "invalid_request");
+    }
+}
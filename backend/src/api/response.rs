@@ -1,8 +1,12 @@
 use serde::Serialize;
+use std::time::Duration;
+use uuid::Uuid;
 
 use crate::api::common::Point;
-use crate::game::Game;
+use crate::game::{Combat, Game, GameError};
 use crate::game_objects::{grid::Grid, hex::Hex};
+use crate::settings::Settings;
+use crate::sight::View;
 
 const CMD_FIELD: &str = "field";
 const CMD_STATE: &str = "state";
@@ -16,18 +20,39 @@ const CMD_DIE: &str = "die";
 const CMD_UPDATE: &str = "update";
 const CMD_GAME_END: &str = "end";
 const CMD_CONNECTION_QUEUE: &str = "queue";
+const CMD_ROOMS: &str = "rooms";
+const CMD_ROOM: &str = "room";
+const CMD_REPLAYS: &str = "replays";
+const CMD_SESSION: &str = "session";
+const CMD_TURN: &str = "turn";
+const CMD_TIMER: &str = "timer";
+const CMD_DRAW_OFFER: &str = "draw_offer";
+const CMD_REMATCH_OFFER: &str = "rematch_offer";
+const CMD_VISION: &str = "vision";
 
 // End state constants
 const END_STATE_WIN: &str = "win";
 const END_STATE_LOSE: &str = "lose";
 const END_STATE_DISCONNECTED: &str = "disconnected";
+const END_STATE_FINISHED: &str = "finished";
+const END_STATE_SURRENDERED: &str = "surrendered";
+const END_STATE_DRAW: &str = "draw";
 
 #[derive(Serialize)]
 pub struct Field {
     cmd: String,
     num_x: u32,
     num_y: u32,
+    // Seed and settings would give the whole map away, so they are not sent in the fog
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u32>,
+    // Settings the random map was generated with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    settings: Option<Settings>,
     field: Grid,
+    // Hexes the client can see in the fog of war, none if it sees everything
+    #[serde(skip_serializing_if = "Option::is_none")]
+    visible: Option<Vec<Point>>,
 }
 
 impl Field {
@@ -36,7 +61,39 @@ impl Field {
             cmd: CMD_FIELD.to_string(),
             num_x: game.field.num_x,
             num_y: game.field.num_y,
+            seed: Some(game.seed),
+            settings: game.settings.clone(),
             field: game.field.clone(),
+            visible: None,
+        }
+    }
+
+    // Field in the fog of war, only units the player sees are on it
+    pub fn with_view(game: &Game, view: &View) -> Field {
+        Field {
+            seed: None,
+            settings: None,
+            field: view.field.clone(),
+            visible: Some(view.visible_points()),
+            ..Field::new(game)
+        }
+    }
+}
+
+// What has changed in the fog of war for the player
+#[derive(Serialize, Debug)]
+pub struct Vision {
+    cmd: String,
+    visible: Vec<Point>,
+    hexes: Vec<Hex>,
+}
+
+impl Vision {
+    pub fn new(view: &View, hexes: Vec<Hex>) -> Vision {
+        Vision {
+            cmd: CMD_VISION.to_string(),
+            visible: view.visible_points(),
+            hexes,
         }
     }
 }
@@ -46,14 +103,17 @@ pub struct Selecting {
     cmd: String,
     target: Point,
     highlight_hexes: Vec<Point>,
+    // Enemies the unit can attack from where it stands
+    attack_hexes: Vec<Point>,
 }
 
 impl Selecting {
-    pub fn new(target: Point, highlight_hexes: &[Point]) -> Selecting {
+    pub fn new(target: Point, highlight_hexes: &[Point], attack_hexes: &[Point]) -> Selecting {
         Selecting {
             cmd: CMD_SELECT.to_string(),
             target,
             highlight_hexes: highlight_hexes.to_vec(),
+            attack_hexes: attack_hexes.to_vec(),
         }
     }
 }
@@ -93,14 +153,19 @@ pub struct Attacking {
     cmd: String,
     from: Point,
     to: Point,
+    damage: u32,
+    // Damage the defender has dealt back, none if it hasn't struck back
+    retaliation: Option<u32>,
 }
 
 impl Attacking {
-    pub fn new(from: Point, to: Point) -> Attacking {
+    pub fn new(from: Point, to: Point, combat: &Combat) -> Attacking {
         Attacking {
             cmd: CMD_ATTACK.to_string(),
             from,
             to,
+            damage: combat.damage,
+            retaliation: combat.retaliation,
         }
     }
 }
@@ -120,10 +185,92 @@ impl State {
     }
 }
 
+// Kind of the error for clients to handle it without parsing the message
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    // Request is not a valid JSON
+    BadJson,
+    // Request has no cmd field
+    MissingCommand,
+    UnknownCommand,
+    // Fields of the command are missing or have wrong types
+    InvalidRequest,
+    // Command needs the running game
+    GameNotStarted,
+    // Command can be sent only by the current player
+    NotYourTurn,
+    // Command can be sent only by a player still playing the game
+    NotAPlayer,
+    // Nobody has offered draw to accept
+    NoDrawOffer,
+    // Rematch can be asked only by players of the last game
+    NotLastPlayer,
+    // Only spectators choose whose eyes to watch with
+    NotASpectator,
+    // There is no such player in the game
+    NoPlayer,
+    // Room with the given id doesn't exist
+    NoRoom,
+    // Command needs the client to be in a room
+    NotInRoom,
+    // Game can't start now, or the room is not full yet
+    GameAlreadyStarted,
+    NotEnoughPlayers,
+    // Saved game goes on only with the players it was saved with
+    WaitingForPlayers,
+    // Options of the new game are wrong, details tell what exactly
+    InvalidSettings,
+    InvalidPreset,
+    InvalidTimer,
+    TooManyBots,
+    // Replays
+    ReplayNotFound,
+    UnsupportedReplay,
+    // Nothing to play, replay is not chosen or has ended
+    NoReplay,
+    // Errors of the game, see GameError
+    NoHex,
+    NoUnit,
+    NoMoves,
+    WrongHex,
+    NoSelectedHex,
+    SelectEnemy,
+    AlreadyMoved,
+    Blocked,
+    FieldIsFull,
+    // Something went wrong on the server
+    Internal,
+}
+
+impl From<&GameError> for ErrorCode {
+    fn from(error: &GameError) -> Self {
+        match error {
+            GameError::NoHex => ErrorCode::NoHex,
+            GameError::NoUnit => ErrorCode::NoUnit,
+            GameError::NoMoves => ErrorCode::NoMoves,
+            GameError::WrongHex => ErrorCode::WrongHex,
+            GameError::NoSelectedHex => ErrorCode::NoSelectedHex,
+            GameError::SelectEnemy => ErrorCode::SelectEnemy,
+            GameError::AlreadyMoved => ErrorCode::AlreadyMoved,
+            GameError::Blocked => ErrorCode::Blocked,
+            GameError::FieldIsFull => ErrorCode::FieldIsFull,
+        }
+    }
+}
+
 #[derive(Serialize, Debug)]
 pub struct Error {
     cmd: String,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<ErrorCode>,
+    // Id client has set in the request caused the error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<serde_json::Value>,
+    // What exactly went wrong
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
 }
 
 impl Error {
@@ -131,8 +278,37 @@ impl Error {
         Error {
             cmd: CMD_ERROR.to_string(),
             message,
+            code: None,
+            request_id: None,
+            details: None,
+        }
+    }
+
+    pub fn with_code(code: ErrorCode, message: String) -> Error {
+        Error {
+            code: Some(code),
+            ..Error::new(message)
         }
     }
+
+    // Error of the game action, code is taken from GameError in the chain
+    pub fn from_report(report: &eyre::Report) -> Error {
+        match report.downcast_ref::<GameError>() {
+            Some(error) => Error::with_code(error.into(), error.to_string()),
+            None => Error::with_code(ErrorCode::Internal, "internal error".to_string()),
+        }
+    }
+
+    pub fn with_details<T: Serialize>(self, details: &T) -> Error {
+        Error {
+            details: serde_json::to_value(details).ok(),
+            ..self
+        }
+    }
+
+    pub fn for_request(self, request_id: Option<serde_json::Value>) -> Error {
+        Error { request_id, ..self }
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -185,6 +361,9 @@ pub enum EndState {
     Win,
     Lose,
     Disconnected,
+    Finished, // Replay has been played to the end
+    Surrendered,
+    Draw,
 }
 
 #[derive(Serialize, Debug)]
@@ -201,6 +380,9 @@ impl End {
                 EndState::Win => END_STATE_WIN.to_string(),
                 EndState::Lose => END_STATE_LOSE.to_string(),
                 EndState::Disconnected => END_STATE_DISCONNECTED.to_string(),
+                EndState::Finished => END_STATE_FINISHED.to_string(),
+                EndState::Surrendered => END_STATE_SURRENDERED.to_string(),
+                EndState::Draw => END_STATE_DRAW.to_string(),
             },
         }
     }
@@ -224,3 +406,180 @@ impl ConnectionQueue {
         }
     }
 }
+
+#[derive(Serialize, Debug)]
+pub struct RoomInfo {
+    id: Uuid,
+    players_number: u32,
+    game_started: bool,
+}
+
+impl RoomInfo {
+    pub fn new(id: Uuid, players_number: u32, game_started: bool) -> RoomInfo {
+        RoomInfo {
+            id,
+            players_number,
+            game_started,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Rooms {
+    cmd: String,
+    rooms: Vec<RoomInfo>,
+}
+
+impl Rooms {
+    pub fn new(rooms: Vec<RoomInfo>) -> Rooms {
+        Rooms {
+            cmd: CMD_ROOMS.to_string(),
+            rooms,
+        }
+    }
+}
+
+// Room the client is in now, none if the client has left the room
+#[derive(Serialize, Debug)]
+pub struct Room {
+    cmd: String,
+    room_id: Option<Uuid>,
+}
+
+impl Room {
+    pub fn new(room_id: Option<Uuid>) -> Room {
+        Room {
+            cmd: CMD_ROOM.to_string(),
+            room_id,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Replays {
+    cmd: String,
+    replays: Vec<Uuid>,
+}
+
+impl Replays {
+    pub fn new(replays: Vec<Uuid>) -> Replays {
+        Replays {
+            cmd: CMD_REPLAYS.to_string(),
+            replays,
+        }
+    }
+}
+
+// Session of the client, it is passed back to continue the game after reconnect
+#[derive(Serialize, Debug)]
+pub struct Session {
+    cmd: String,
+    session: Uuid,
+    // Client was returned to the game it had left
+    reconnected: bool,
+}
+
+impl Session {
+    pub fn new(session: Uuid, reconnected: bool) -> Session {
+        Session {
+            cmd: CMD_SESSION.to_string(),
+            session,
+            reconnected,
+        }
+    }
+}
+
+// Player whose turn it is now
+#[derive(Serialize, Debug)]
+pub struct Turn {
+    cmd: String,
+    player: u32,
+}
+
+impl Turn {
+    pub fn new(player: u32) -> Turn {
+        Turn {
+            cmd: CMD_TURN.to_string(),
+            player,
+        }
+    }
+}
+
+// Time the current player has left, in seconds
+#[derive(Serialize, Debug)]
+pub struct Timer {
+    cmd: String,
+    player: u32,
+    time_left: u64,
+    // None if match has no bank
+    bank_left: Option<u64>,
+}
+
+impl Timer {
+    pub fn new(player: u32, time_left: Duration, bank_left: Option<Duration>) -> Timer {
+        Timer {
+            cmd: CMD_TIMER.to_string(),
+            player,
+            time_left: time_left.as_secs(),
+            bank_left: bank_left.map(|bank| bank.as_secs()),
+        }
+    }
+}
+
+// Player offers the others to end the game in a draw or to play again
+#[derive(Serialize, Debug)]
+pub struct Offer {
+    cmd: String,
+    player: u32,
+}
+
+impl Offer {
+    pub fn draw(player: u32) -> Offer {
+        Offer {
+            cmd: CMD_DRAW_OFFER.to_string(),
+            player,
+        }
+    }
+
+    pub fn rematch(player: u32) -> Offer {
+        Offer {
+            cmd: CMD_REMATCH_OFFER.to_string(),
+            player,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::WrapErr;
+
+    #[test]
+    fn error_from_report() {
+        let report = Err::<(), _>(GameError::AlreadyMoved)
+            .wrap_err("get current action")
+            .wrap_err("handle click")
+            .unwrap_err();
+        let error = serde_json::to_value(Error::from_report(&report)).unwrap();
+        assert_eq!(error["code"], "already_moved");
+        assert_eq!(error["message"], "unit has been already moved");
+
+        let report = eyre::eyre!("mailbox closed");
+        let error = serde_json::to_value(Error::from_report(&report)).unwrap();
+        assert_eq!(error["code"], "internal");
+    }
+
+    #[test]
+    fn fogged_field_hides_seed() {
+        let game = Game::random(2, 3, Settings::default()).unwrap();
+        let field = serde_json::to_value(Field::new(&game)).unwrap();
+        assert_eq!(field["seed"], 3);
+        assert!(field.get("settings").is_some());
+
+        let view = View::new(&game.field, 0);
+        let field = serde_json::to_value(Field::with_view(&game, &view)).unwrap();
+        assert!(field.get("seed").is_none());
+        assert!(field.get("settings").is_none());
+        assert!(field.get("visible").is_some());
+    }
+}
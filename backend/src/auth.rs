@@ -1,5 +1,20 @@
 use crate::config::CONFIG;
+use crate::errors::ApiError;
+use crate::helpers::query_param;
+use actix_web::HttpRequest;
 use argon2rs::argon2i_simple;
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Claims {
+    // User id
+    pub sub: String,
+    // Expiration time as a unix timestamp
+    pub exp: i64,
+}
 
 pub fn hash(password: &str) -> String {
     argon2i_simple(&password, &CONFIG.auth_salt)
@@ -7,3 +22,134 @@ pub fn hash(password: &str) -> String {
         .map(|b| format!("{:02x}", b))
         .collect()
 }
+
+pub fn verify(password: &str, hashed_password: &str) -> bool {
+    hash(password) == hashed_password
+}
+
+// Token valid for jwt_expiration hours
+pub fn create_jwt(user_id: &str) -> Result<String, ApiError> {
+    let claims = Claims {
+        sub: user_id.to_string(),
+        exp: (Utc::now() + Duration::hours(CONFIG.jwt_expiration)).timestamp(),
+    };
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(CONFIG.jwt_key.as_ref()),
+    )
+    .map_err(|e| ApiError::CannotEncodeJwtToken(e.to_string()))
+}
+
+// Id of the user the token was issued to
+pub fn decode_jwt(token: &str) -> Result<Uuid, ApiError> {
+    let claims = decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(CONFIG.jwt_key.as_ref()),
+        &Validation::default(),
+    )
+    .map_err(|e| ApiError::CannotDecodeJwtToken(e.to_string()))?
+    .claims;
+    Uuid::parse_str(&claims.sub).map_err(|e| ApiError::CannotDecodeJwtToken(e.to_string()))
+}
+
+// Browsers can't set headers for websocket, so token may be passed in the query too
+pub fn get_token(req: &HttpRequest) -> Option<String> {
+    if let Some(header) = req.headers().get("Authorization") {
+        return header
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.to_string());
+    }
+
+    query_param(req, "token")
+}
+
+// Check that request is made by the user with the token
+pub fn authorize(req: &HttpRequest, user_id: Uuid) -> Result<(), ApiError> {
+    let token = get_token(req).ok_or_else(|| ApiError::Unauthorized("no token".to_string()))?;
+    if decode_jwt(&token)? != user_id {
+        return Err(ApiError::Forbidden("access to other user".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn verify_password() {
+        let hashed_password = hash("password");
+        assert!(verify("password", &hashed_password));
+        assert!(!verify("wrong_password", &hashed_password));
+    }
+
+    #[test]
+    fn encode_decode_jwt() {
+        let user_id = Uuid::new_v4();
+        let token = create_jwt(&user_id.to_string()).unwrap();
+        assert_eq!(decode_jwt(&token).unwrap(), user_id);
+    }
+
+    #[test]
+    fn decode_wrong_jwt() {
+        assert!(decode_jwt("wrong_token").is_err());
+    }
+
+    #[test]
+    fn decode_expired_jwt() {
+        let claims = Claims {
+            sub: Uuid::new_v4().to_string(),
+            exp: (Utc::now() - Duration::hours(1)).timestamp(),
+        };
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(CONFIG.jwt_key.as_ref()),
+        )
+        .unwrap();
+        assert!(decode_jwt(&token).is_err());
+    }
+
+    #[test]
+    fn get_token_from_header() {
+        let req = TestRequest::default()
+            .header("Authorization", "Bearer some_token")
+            .to_http_request();
+        assert_eq!(get_token(&req), Some("some_token".to_string()));
+    }
+
+    #[test]
+    fn get_token_from_query() {
+        let req = TestRequest::with_uri("/ws/?token=some_token").to_http_request();
+        assert_eq!(get_token(&req), Some("some_token".to_string()));
+    }
+
+    #[test]
+    fn authorize_user() {
+        let user_id = Uuid::new_v4();
+        let token = create_jwt(&user_id.to_string()).unwrap();
+        let req = TestRequest::default()
+            .header("Authorization", format!("Bearer {}", token))
+            .to_http_request();
+
+        assert!(authorize(&req, user_id).is_ok());
+        assert!(matches!(
+            authorize(&req, Uuid::new_v4()),
+            Err(ApiError::Forbidden(_))
+        ));
+        assert!(matches!(
+            authorize(&TestRequest::default().to_http_request(), user_id),
+            Err(ApiError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn no_token() {
+        let req = TestRequest::with_uri("/ws/").to_http_request();
+        assert_eq!(get_token(&req), None);
+    }
+}
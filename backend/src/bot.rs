@@ -0,0 +1,270 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::api::common::Point;
+use crate::game::Game;
+use crate::game_objects::hex::Hex;
+use crate::game_objects::unit::Unit;
+use crate::hex_coords::distance;
+
+#[derive(Error, Serialize, Debug, PartialEq)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum BotError {
+    #[error("at most {max} bots can play, at least one player must be human")]
+    TooManyBots { max: usize },
+}
+
+// Server side player
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Bot {
+    // Random legal turn
+    Easy,
+    // Maximal expected damage, weak units first
+    Greedy,
+}
+
+// What bot does in its turn: selects the unit, moves it and attacks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BotTurn {
+    pub unit: Point,
+    // Equals to unit if unit stays in place
+    pub move_to: Point,
+    pub attack: Option<Point>,
+}
+
+// Attack of the unit from the point it can reach
+#[derive(Debug, Clone, Copy)]
+struct Attack {
+    unit: Hex,
+    from: Point,
+    target: Hex,
+}
+
+impl Bot {
+    // Turn of the player, none if player should skip the turn
+    pub fn turn(&self, game: &Game, player: u32) -> Option<BotTurn> {
+        match self {
+            Bot::Easy => easy_turn(game, player),
+            Bot::Greedy => greedy_turn(game, player),
+        }
+    }
+}
+
+// Rng of the current turn, it comes from the game seed so the same game gets the same turn
+// High bit of the lower half keeps it apart from the rolls of the game
+fn turn_rng(game: &Game) -> StdRng {
+    StdRng::seed_from_u64(((game.seed as u64) << 32) | 1 << 31 | game.turn as u64)
+}
+
+fn easy_turn(game: &Game, player: u32) -> Option<BotTurn> {
+    let mut rng = turn_rng(game);
+
+    let unit = *unit_hexes(game, player).choose(&mut rng)?;
+    let move_to = *game.available_points(&Some(unit)).choose(&mut rng)?;
+    let attack = game
+        .targets(&unit, move_to)
+        .choose(&mut rng)
+        .map(|hex| hex.to_point());
+
+    Some(BotTurn {
+        unit: unit.to_point(),
+        move_to,
+        attack,
+    })
+}
+
+fn greedy_turn(game: &Game, player: u32) -> Option<BotTurn> {
+    let best_attack = attacks(game, player).into_iter().max_by(|a, b| {
+        let a_unit = a.unit.unit.unwrap();
+        let b_unit = b.unit.unit.unwrap();
+        let a_target = a.target.unit.unwrap();
+        let b_target = b.target.unit.unwrap();
+
+        expected_damage(&a_unit, &a.target)
+            .partial_cmp(&expected_damage(&b_unit, &b.target))
+            .unwrap()
+            // Weaker target is better
+            .then(b_target.hp.cmp(&a_target.hp))
+    });
+
+    if let Some(attack) = best_attack {
+        return Some(BotTurn {
+            unit: attack.unit.to_point(),
+            move_to: attack.from,
+            attack: Some(attack.target.to_point()),
+        });
+    }
+
+    // Nobody to attack, so get as close to enemies as possible
+    let enemies = enemy_points(game, player);
+    unit_hexes(game, player)
+        .into_iter()
+        .flat_map(|unit| {
+            game.available_points(&Some(unit))
+                .into_iter()
+                .map(move |point| (unit, point))
+        })
+        .min_by_key(|(_, point)| {
+            enemies
+                .iter()
+                .map(|enemy| distance(*point, *enemy))
+                .min()
+                .unwrap_or(0)
+        })
+        .map(|(unit, point)| BotTurn {
+            unit: unit.to_point(),
+            move_to: point,
+            attack: None,
+        })
+}
+
+// All attacks player can make this turn
+fn attacks(game: &Game, player: u32) -> Vec<Attack> {
+    let mut attacks = vec![];
+    for unit in unit_hexes(game, player) {
+        for from in game.available_points(&Some(unit)) {
+            for target in game.targets(&unit, from) {
+                attacks.push(Attack { unit, from, target });
+            }
+        }
+    }
+    attacks
+}
+
+// Average damage dealt to the unit in the target hex, damage above its hp is wasted
+fn expected_damage(unit: &Unit, target: &Hex) -> f64 {
+    let hp = target.unit.map(|unit| unit.hp).unwrap_or(0);
+    let rolls = unit.damage[0]..=unit.damage[1];
+    let num_of_rolls = rolls.clone().count() as f64;
+    rolls
+        .map(|damage| damage.saturating_sub(target.defence()).min(hp) as f64)
+        .sum::<f64>()
+        / num_of_rolls
+}
+
+fn unit_hexes(game: &Game, player: u32) -> Vec<Hex> {
+    game.field
+        .hexes
+        .iter()
+        .filter(|hex| matches!(hex.unit, Some(unit) if unit.is_my(player)))
+        .copied()
+        .collect()
+}
+
+fn enemy_points(game: &Game, player: u32) -> Vec<Point> {
+    game.field
+        .hexes
+        .iter()
+        .filter(|hex| matches!(hex.unit, Some(unit) if !unit.is_my(player)))
+        .map(|hex| hex.to_point())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 4x4 field with unit of player 0 in the corner
+    fn test_game() -> Game {
+        let mut game = Game::new(4, 4);
+        assert!(game
+            .set_unit(0, 0, Some(Unit::new(0, 5, [2, 4], 2)))
+            .is_ok());
+        game
+    }
+
+    #[test]
+    fn deserialize() {
+        let bots: Vec<Bot> = serde_json::from_str("[\"easy\",\"greedy\"]").unwrap();
+        assert_eq!(bots, vec![Bot::Easy, Bot::Greedy]);
+    }
+
+    #[test]
+    fn easy_makes_legal_turn() {
+        let mut game = test_game();
+        assert!(game
+            .set_unit(1, 1, Some(Unit::new(1, 5, [1, 1], 1)))
+            .is_ok());
+
+        for number in 0..20 {
+            game.turn = number;
+            let turn = Bot::Easy.turn(&game, 0).unwrap();
+            assert_eq!(turn.unit, Point { x: 0, y: 0 });
+
+            let unit = game.field.get_hex(0, 0);
+            assert!(game.available_points(&unit).contains(&turn.move_to));
+            if let Some(target) = turn.attack {
+                assert_eq!(target, Point { x: 1, y: 1 });
+                assert_eq!(distance(turn.move_to, target), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn easy_repeats_turn_of_the_same_game() {
+        let mut game = test_game();
+        assert!(game
+            .set_unit(3, 3, Some(Unit::new(1, 5, [1, 1], 1)))
+            .is_ok());
+        game.seed = 7;
+        game.turn = 4;
+
+        let turn = Bot::Easy.turn(&game, 0);
+        let restored: Game = serde_json::from_str(&serde_json::to_string(&game).unwrap()).unwrap();
+        assert_eq!(Bot::Easy.turn(&restored, 0), turn);
+    }
+
+    #[test]
+    fn greedy_maximizes_damage() {
+        let mut game = test_game();
+        assert!(game
+            .set_unit(2, 0, Some(Unit::new(1, 2, [1, 1], 1)))
+            .is_ok());
+        assert!(game
+            .set_unit(0, 2, Some(Unit::new(1, 10, [1, 1], 1)))
+            .is_ok());
+
+        // Damage above 2 is wasted on the weak unit
+        let turn = Bot::Greedy.turn(&game, 0).unwrap();
+        assert_eq!(turn.attack, Some(Point { x: 0, y: 2 }));
+        assert_eq!(distance(turn.move_to, Point { x: 0, y: 2 }), 1);
+    }
+
+    #[test]
+    fn greedy_attacks_weak_unit() {
+        let mut game = test_game();
+        assert!(game
+            .set_unit(2, 0, Some(Unit::new(1, 6, [1, 1], 1)))
+            .is_ok());
+        assert!(game
+            .set_unit(0, 2, Some(Unit::new(1, 4, [1, 1], 1)))
+            .is_ok());
+
+        // Expected damage is the same, so the weaker unit is attacked
+        let turn = Bot::Greedy.turn(&game, 0).unwrap();
+        assert_eq!(turn.attack, Some(Point { x: 0, y: 2 }));
+    }
+
+    #[test]
+    fn greedy_approaches_enemy() {
+        let mut game = test_game();
+        assert!(game
+            .set_unit(3, 3, Some(Unit::new(1, 5, [1, 1], 1)))
+            .is_ok());
+
+        let turn = Bot::Greedy.turn(&game, 0).unwrap();
+        assert_eq!(turn.attack, None);
+        assert_eq!(distance(turn.move_to, Point { x: 3, y: 3 }), 2);
+    }
+
+    #[test]
+    fn no_units() {
+        let game = Game::new(2, 2);
+        assert_eq!(Bot::Easy.turn(&game, 0), None);
+        assert_eq!(Bot::Greedy.turn(&game, 0), None);
+    }
+}
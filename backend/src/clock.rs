@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+
+const DEFAULT_TURN_SECONDS: u32 = 60;
+const DEFAULT_MAX_TIMEOUTS: u32 = 3;
+
+#[derive(Error, Debug, PartialEq, Serialize)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum TimerError {
+    #[error("{field} must be positive")]
+    NotPositive { field: String },
+}
+
+// Time limits of the match
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TimerSettings {
+    // Seconds for every turn
+    pub turn: u32,
+    // Seconds player can spend over turn time during the whole match, no bank if not set
+    pub bank: Option<u32>,
+    // Player forfeits after so many timeouts in a row
+    pub max_timeouts: u32,
+}
+
+impl Default for TimerSettings {
+    fn default() -> Self {
+        TimerSettings {
+            turn: DEFAULT_TURN_SECONDS,
+            bank: None,
+            max_timeouts: DEFAULT_MAX_TIMEOUTS,
+        }
+    }
+}
+
+impl TimerSettings {
+    pub fn validate(&self) -> Result<(), TimerError> {
+        for (field, value) in [("turn", self.turn), ("max_timeouts", self.max_timeouts)].iter() {
+            if *value == 0 {
+                return Err(TimerError::NotPositive {
+                    field: field.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+// Time players have in the running match
+#[derive(Debug, Clone)]
+pub struct Clock {
+    pub settings: TimerSettings,
+    // Bank left for every player
+    banks: Vec<Duration>,
+    // Timeouts in a row for every player
+    timeouts: Vec<u32>,
+}
+
+impl Clock {
+    pub fn new(settings: TimerSettings, num_of_players: usize) -> Clock {
+        let bank = Duration::from_secs(settings.bank.unwrap_or(0) as u64);
+        Clock {
+            settings,
+            banks: vec![bank; num_of_players],
+            timeouts: vec![0; num_of_players],
+        }
+    }
+
+    pub fn turn_time(&self) -> Duration {
+        Duration::from_secs(self.settings.turn as u64)
+    }
+
+    // Bank left, none if match has no bank
+    pub fn bank_left(&self, player: u32) -> Option<Duration> {
+        self.settings.bank?;
+        self.banks.get(player as usize).copied()
+    }
+
+    // Time for the whole turn of the player, turn runs out after it
+    pub fn time_for_turn(&self, player: u32) -> Duration {
+        self.turn_time() + self.bank_left(player).unwrap_or_default()
+    }
+
+    // Take time spent over turn time from the bank
+    pub fn spend(&mut self, player: u32, spent: Duration) {
+        let over = spent.checked_sub(self.turn_time()).unwrap_or_default();
+        if let Some(bank) = self.banks.get_mut(player as usize) {
+            *bank = bank.checked_sub(over).unwrap_or_default();
+        }
+    }
+
+    // Count one more timeout, return true if player has forfeited
+    pub fn timeout(&mut self, player: u32) -> bool {
+        match self.timeouts.get_mut(player as usize) {
+            Some(timeouts) => {
+                *timeouts += 1;
+                *timeouts >= self.settings.max_timeouts
+            }
+            None => false,
+        }
+    }
+
+    // Player has made a move by themselves
+    pub fn reset_timeouts(&mut self, player: u32) {
+        if let Some(timeouts) = self.timeouts.get_mut(player as usize) {
+            *timeouts = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn bank_settings() -> TimerSettings {
+        TimerSettings {
+            turn: 10,
+            bank: Some(30),
+            max_timeouts: 2,
+        }
+    }
+
+    #[test]
+    fn validate() {
+        assert!(TimerSettings::default().validate().is_ok());
+        assert!(bank_settings().validate().is_ok());
+        assert_eq!(
+            TimerSettings {
+                turn: 0,
+                ..TimerSettings::default()
+            }
+            .validate(),
+            Err(TimerError::NotPositive {
+                field: "turn".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_partial() {
+        let settings: TimerSettings = serde_json::from_str("{\"bank\": 120}").unwrap();
+        assert_eq!(settings.turn, DEFAULT_TURN_SECONDS);
+        assert_eq!(settings.bank, Some(120));
+    }
+
+    #[test]
+    fn no_bank() {
+        let mut clock = Clock::new(TimerSettings::default(), 2);
+        clock.spend(0, Duration::from_secs(100));
+        assert_eq!(clock.bank_left(0), None);
+        assert_eq!(
+            clock.time_for_turn(0),
+            Duration::from_secs(DEFAULT_TURN_SECONDS as u64)
+        );
+    }
+
+    #[test]
+    fn spend_bank() {
+        let mut clock = Clock::new(bank_settings(), 2);
+        assert_eq!(clock.time_for_turn(0), Duration::from_secs(40));
+
+        // Time within the turn is free
+        clock.spend(0, Duration::from_secs(8));
+        assert_eq!(clock.bank_left(0), Some(Duration::from_secs(30)));
+
+        clock.spend(0, Duration::from_secs(25));
+        assert_eq!(clock.bank_left(0), Some(Duration::from_secs(15)));
+        assert_eq!(clock.time_for_turn(0), Duration::from_secs(25));
+        assert_eq!(clock.bank_left(1), Some(Duration::from_secs(30)));
+
+        clock.spend(0, Duration::from_secs(100));
+        assert_eq!(clock.bank_left(0), Some(Duration::from_secs(0)));
+        assert_eq!(clock.time_for_turn(0), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn forfeit_after_timeouts_in_a_row() {
+        let mut clock = Clock::new(bank_settings(), 2);
+        assert!(!clock.timeout(0));
+        clock.reset_timeouts(0);
+        assert!(!clock.timeout(0));
+        assert!(!clock.timeout(1));
+        assert!(clock.timeout(0));
+    }
+}
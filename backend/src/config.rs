@@ -10,6 +10,34 @@ pub struct Config {
     pub auth_salt: String,
     pub jwt_expiration: i64,
     pub jwt_key: String,
+    // Directory where replays of finished games are saved
+    #[serde(default = "default_replays_dir")]
+    pub replays_dir: String,
+    // Directory where running games are saved to continue them after restart
+    #[serde(default = "default_snapshots_dir")]
+    pub snapshots_dir: String,
+    // Directory with map presets
+    #[serde(default = "default_presets_dir")]
+    pub presets_dir: String,
+    // SQLite database file
+    #[serde(default = "default_database_path")]
+    pub database_path: String,
+}
+
+fn default_replays_dir() -> String {
+    "replays".to_string()
+}
+
+fn default_snapshots_dir() -> String {
+    "snapshots".to_string()
+}
+
+fn default_presets_dir() -> String {
+    "presets".to_string()
+}
+
+fn default_database_path() -> String {
+    "battle-game.db".to_string()
 }
 
 impl Config {
@@ -20,10 +48,20 @@ impl Config {
             jwt_expiration: 48,
             jwt_key: "secret_key_change_me".to_string(),
             auth_salt: "hashing_salt_change_me".to_string(),
+            replays_dir: default_replays_dir(),
+            snapshots_dir: default_snapshots_dir(),
+            presets_dir: default_presets_dir(),
+            database_path: default_database_path(),
         }
     }
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
 lazy_static! {
     pub static ref CONFIG: Config = get_initial_config();
 }
@@ -0,0 +1,120 @@
+use super::Storage;
+use crate::errors::ApiError;
+use crate::models::match_result::MatchResult;
+use crate::models::rating::Rating;
+use crate::models::user::User;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+// Storage for tests, everything is lost when it is dropped
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    users: Mutex<Vec<User>>,
+    matches: Mutex<Vec<MatchResult>>,
+    ratings: Mutex<HashMap<Uuid, Rating>>,
+}
+
+// Check that no other user has the same handle or email
+fn check_unique(users: &[User], user: &User) -> Result<(), ApiError> {
+    for other in users.iter().filter(|other| other.id != user.id) {
+        if other.handle == user.handle {
+            return Err(ApiError::Conflict("handle is already taken".to_string()));
+        }
+        if other.email == user.email {
+            return Err(ApiError::Conflict("email is already taken".to_string()));
+        }
+    }
+    Ok(())
+}
+
+impl Storage for MemoryStorage {
+    fn get_users(&self) -> Result<Vec<User>, ApiError> {
+        Ok(self.users.lock().unwrap().clone())
+    }
+
+    fn find_user(&self, id: Uuid) -> Result<Option<User>, ApiError> {
+        let users = self.users.lock().unwrap();
+        Ok(users.iter().find(|user| user.id == id.to_string()).cloned())
+    }
+
+    fn find_user_by_email(&self, email: &str) -> Result<Option<User>, ApiError> {
+        let users = self.users.lock().unwrap();
+        Ok(users.iter().find(|user| user.email == email).cloned())
+    }
+
+    fn insert_user(&self, user: &User) -> Result<(), ApiError> {
+        let mut users = self.users.lock().unwrap();
+        check_unique(&users, user)?;
+        users.push(user.clone());
+        Ok(())
+    }
+
+    fn update_user(&self, user: &User) -> Result<(), ApiError> {
+        let mut users = self.users.lock().unwrap();
+        check_unique(&users, user)?;
+        match users.iter_mut().find(|other| other.id == user.id) {
+            Some(other) => {
+                *other = user.clone();
+                Ok(())
+            }
+            None => Err(ApiError::NotFound("user".to_string())),
+        }
+    }
+
+    fn delete_user(&self, id: Uuid) -> Result<(), ApiError> {
+        let mut users = self.users.lock().unwrap();
+        match users.iter().position(|user| user.id == id.to_string()) {
+            Some(position) => {
+                users.remove(position);
+                self.ratings.lock().unwrap().remove(&id);
+                Ok(())
+            }
+            None => Err(ApiError::NotFound("user".to_string())),
+        }
+    }
+
+    fn insert_match(&self, result: &MatchResult) -> Result<(), ApiError> {
+        self.matches.lock().unwrap().push(result.clone());
+        Ok(())
+    }
+
+    fn get_matches(&self, user_id: Uuid) -> Result<Vec<MatchResult>, ApiError> {
+        let mut matches: Vec<MatchResult> = self
+            .matches
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|result| result.players.contains(&user_id))
+            .cloned()
+            .collect();
+        matches.sort_by_key(|result| std::cmp::Reverse(result.finished_at));
+        Ok(matches)
+    }
+
+    fn get_rating(&self, user_id: Uuid) -> Result<Option<Rating>, ApiError> {
+        Ok(self.ratings.lock().unwrap().get(&user_id).copied())
+    }
+
+    fn update_ratings(
+        &self,
+        users: &[Uuid],
+        change: &mut dyn FnMut(&mut [Rating]),
+    ) -> Result<(), ApiError> {
+        let mut ratings = self.ratings.lock().unwrap();
+        let mut changed: Vec<Rating> = users
+            .iter()
+            .map(|user_id| {
+                ratings
+                    .get(user_id)
+                    .copied()
+                    .unwrap_or_else(|| Rating::new(*user_id))
+            })
+            .collect();
+        change(&mut changed);
+        for rating in changed {
+            ratings.insert(rating.user_id, rating);
+        }
+        Ok(())
+    }
+}
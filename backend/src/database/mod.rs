@@ -0,0 +1,55 @@
+use crate::config::CONFIG;
+use crate::errors::ApiError;
+use crate::models::match_result::MatchResult;
+use crate::models::rating::Rating;
+use crate::models::user::User;
+use actix_web::web;
+use std::fmt::Debug;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[cfg(test)]
+pub mod memory;
+pub mod sqlite;
+
+use sqlite::SqliteStorage;
+
+// Everything server keeps between restarts
+// Inserting or updating user with taken handle or email is a conflict
+pub trait Storage: Debug + Send + Sync {
+    // Users
+    fn get_users(&self) -> Result<Vec<User>, ApiError>;
+    fn find_user(&self, id: Uuid) -> Result<Option<User>, ApiError>;
+    fn find_user_by_email(&self, email: &str) -> Result<Option<User>, ApiError>;
+    fn insert_user(&self, user: &User) -> Result<(), ApiError>;
+    fn update_user(&self, user: &User) -> Result<(), ApiError>;
+    fn delete_user(&self, id: Uuid) -> Result<(), ApiError>;
+
+    // Matches
+    fn insert_match(&self, result: &MatchResult) -> Result<(), ApiError>;
+    // Matches the user played, the latest first
+    fn get_matches(&self, user_id: Uuid) -> Result<Vec<MatchResult>, ApiError>;
+
+    // Ratings
+    fn get_rating(&self, user_id: Uuid) -> Result<Option<Rating>, ApiError>;
+    // Change ratings of the users and save them at once, nobody else changes them meanwhile
+    // Users who have not played yet come with the initial rating
+    fn update_ratings(
+        &self,
+        users: &[Uuid],
+        change: &mut dyn FnMut(&mut [Rating]),
+    ) -> Result<(), ApiError>;
+}
+
+// Open database from config
+pub fn open_storage() -> Arc<dyn Storage> {
+    let path = &CONFIG.database_path;
+    info!("Opening database {}", path);
+    let storage = SqliteStorage::open(path).expect("Failed to open database");
+    Arc::new(storage)
+}
+
+pub fn add_user_storage(cfg: &mut web::ServiceConfig, storage: Arc<dyn Storage>) {
+    info!("Adding user storage...");
+    cfg.app_data(web::Data::from(storage));
+}
@@ -0,0 +1,530 @@
+use super::Storage;
+use crate::errors::ApiError;
+use crate::models::match_result::MatchResult;
+use crate::models::rating::Rating;
+#[cfg(test)]
+use crate::models::rating::INITIAL_RATING;
+use crate::models::user::User;
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension, Row};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+// Schema changes, applied in order
+// Never change applied migration, add a new one instead
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE users (
+        id TEXT PRIMARY KEY,
+        handle TEXT NOT NULL UNIQUE,
+        email TEXT NOT NULL UNIQUE,
+        password TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );",
+    "CREATE TABLE matches (
+        id TEXT PRIMARY KEY,
+        room_id TEXT NOT NULL,
+        replay_id TEXT,
+        winner TEXT,
+        finished_at TEXT NOT NULL
+    );
+    CREATE TABLE match_players (
+        match_id TEXT NOT NULL REFERENCES matches(id) ON DELETE CASCADE,
+        user_id TEXT NOT NULL,
+        PRIMARY KEY (match_id, user_id)
+    );
+    CREATE INDEX match_players_user_id ON match_players(user_id);
+    CREATE TABLE ratings (
+        user_id TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+        rating INTEGER NOT NULL,
+        games INTEGER NOT NULL
+    );",
+    // The same user can take several seats of the match
+    "CREATE TABLE match_seats (
+        match_id TEXT NOT NULL REFERENCES matches(id) ON DELETE CASCADE,
+        seat INTEGER NOT NULL,
+        user_id TEXT NOT NULL,
+        PRIMARY KEY (match_id, seat)
+    );
+    INSERT INTO match_seats (match_id, seat, user_id)
+        SELECT match_id,
+            (SELECT COUNT(*) FROM match_players other
+             WHERE other.match_id = player.match_id AND other.rowid < player.rowid),
+            user_id
+        FROM match_players player;
+    DROP TABLE match_players;
+    ALTER TABLE match_seats RENAME TO match_players;
+    CREATE INDEX match_players_user_id ON match_players(user_id);",
+];
+
+#[derive(Debug)]
+pub struct SqliteStorage {
+    // Connection can't be used from several threads at once
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<SqliteStorage, ApiError> {
+        SqliteStorage::new(Connection::open(path).map_err(database_error)?)
+    }
+
+    // Database which lives until the storage is dropped
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<SqliteStorage, ApiError> {
+        SqliteStorage::new(Connection::open_in_memory().map_err(database_error)?)
+    }
+
+    fn new(mut connection: Connection) -> Result<SqliteStorage, ApiError> {
+        connection
+            .execute_batch("PRAGMA foreign_keys = ON;")
+            .map_err(database_error)?;
+        migrate(&mut connection)?;
+        Ok(SqliteStorage {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+// Apply migrations database doesn't have yet
+fn migrate(connection: &mut Connection) -> Result<(), ApiError> {
+    let version: i64 = connection
+        .query_row("PRAGMA user_version;", params![], |row| row.get(0))
+        .map_err(database_error)?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        info!("Applying database migration {}", i + 1);
+        let transaction = connection.transaction().map_err(database_error)?;
+        transaction
+            .execute_batch(migration)
+            .map_err(database_error)?;
+        transaction
+            .execute_batch(&format!("PRAGMA user_version = {};", i + 1))
+            .map_err(database_error)?;
+        transaction.commit().map_err(database_error)?;
+    }
+
+    Ok(())
+}
+
+fn database_error(error: rusqlite::Error) -> ApiError {
+    if let rusqlite::Error::SqliteFailure(failure, Some(message)) = &error {
+        if failure.code == ErrorCode::ConstraintViolation {
+            if message.contains("users.handle") {
+                return ApiError::Conflict("handle is already taken".to_string());
+            }
+            if message.contains("users.email") {
+                return ApiError::Conflict("email is already taken".to_string());
+            }
+        }
+    }
+    ApiError::DatabaseError(error.to_string())
+}
+
+fn parse_uuid(s: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(s).map_err(|e| ApiError::DatabaseError(e.to_string()))
+}
+
+fn user_from_row(row: &Row) -> rusqlite::Result<User> {
+    Ok(User {
+        id: row.get(0)?,
+        handle: row.get(1)?,
+        email: row.get(2)?,
+        password: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+const USER_COLUMNS: &str = "id, handle, email, password, created_at";
+
+impl Storage for SqliteStorage {
+    fn get_users(&self) -> Result<Vec<User>, ApiError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM users ORDER BY created_at",
+                USER_COLUMNS
+            ))
+            .map_err(database_error)?;
+        let users = statement
+            .query_map(params![], user_from_row)
+            .map_err(database_error)?
+            .collect::<rusqlite::Result<Vec<User>>>()
+            .map_err(database_error)?;
+        Ok(users)
+    }
+
+    fn find_user(&self, id: Uuid) -> Result<Option<User>, ApiError> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                &format!("SELECT {} FROM users WHERE id = ?1", USER_COLUMNS),
+                params![id.to_string()],
+                user_from_row,
+            )
+            .optional()
+            .map_err(database_error)
+    }
+
+    fn find_user_by_email(&self, email: &str) -> Result<Option<User>, ApiError> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                &format!("SELECT {} FROM users WHERE email = ?1", USER_COLUMNS),
+                params![email],
+                user_from_row,
+            )
+            .optional()
+            .map_err(database_error)
+    }
+
+    fn insert_user(&self, user: &User) -> Result<(), ApiError> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                &format!(
+                    "INSERT INTO users ({}) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    USER_COLUMNS
+                ),
+                params![
+                    user.id,
+                    user.handle,
+                    user.email,
+                    user.password,
+                    user.created_at
+                ],
+            )
+            .map_err(database_error)?;
+        Ok(())
+    }
+
+    fn update_user(&self, user: &User) -> Result<(), ApiError> {
+        let connection = self.connection.lock().unwrap();
+        let updated = connection
+            .execute(
+                "UPDATE users SET handle = ?2, email = ?3, password = ?4 WHERE id = ?1",
+                params![user.id, user.handle, user.email, user.password],
+            )
+            .map_err(database_error)?;
+        if updated == 0 {
+            return Err(ApiError::NotFound("user".to_string()));
+        }
+        Ok(())
+    }
+
+    fn delete_user(&self, id: Uuid) -> Result<(), ApiError> {
+        let connection = self.connection.lock().unwrap();
+        let deleted = connection
+            .execute("DELETE FROM users WHERE id = ?1", params![id.to_string()])
+            .map_err(database_error)?;
+        if deleted == 0 {
+            return Err(ApiError::NotFound("user".to_string()));
+        }
+        Ok(())
+    }
+
+    fn insert_match(&self, result: &MatchResult) -> Result<(), ApiError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(database_error)?;
+        transaction
+            .execute(
+                "INSERT INTO matches (id, room_id, replay_id, winner, finished_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    result.id.to_string(),
+                    result.room_id.to_string(),
+                    result.replay_id.map(|id| id.to_string()),
+                    result.winner.map(|id| id.to_string()),
+                    result.finished_at
+                ],
+            )
+            .map_err(database_error)?;
+        for (seat, player) in result.players.iter().enumerate() {
+            transaction
+                .execute(
+                    "INSERT INTO match_players (match_id, seat, user_id) VALUES (?1, ?2, ?3)",
+                    params![result.id.to_string(), seat as i64, player.to_string()],
+                )
+                .map_err(database_error)?;
+        }
+        transaction.commit().map_err(database_error)
+    }
+
+    fn get_matches(&self, user_id: Uuid) -> Result<Vec<MatchResult>, ApiError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(
+                "SELECT m.id, m.room_id, m.replay_id, m.winner, m.finished_at
+                 FROM matches m
+                 WHERE m.id IN (SELECT match_id FROM match_players WHERE user_id = ?1)
+                 ORDER BY m.finished_at DESC",
+            )
+            .map_err(database_error)?;
+        let rows = statement
+            .query_map(params![user_id.to_string()], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .map_err(database_error)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(database_error)?;
+
+        let mut players_statement = connection
+            .prepare("SELECT user_id FROM match_players WHERE match_id = ?1 ORDER BY seat")
+            .map_err(database_error)?;
+        let mut matches = Vec::with_capacity(rows.len());
+        for (id, room_id, replay_id, winner, finished_at) in rows {
+            let players = players_statement
+                .query_map(params![id], |row| row.get::<_, String>(0))
+                .map_err(database_error)?
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(database_error)?;
+            matches.push(MatchResult {
+                id: parse_uuid(&id)?,
+                room_id: parse_uuid(&room_id)?,
+                replay_id: replay_id.as_deref().map(parse_uuid).transpose()?,
+                players: players
+                    .iter()
+                    .map(|player| parse_uuid(player))
+                    .collect::<Result<_, _>>()?,
+                winner: winner.as_deref().map(parse_uuid).transpose()?,
+                finished_at,
+            });
+        }
+        Ok(matches)
+    }
+
+    fn get_rating(&self, user_id: Uuid) -> Result<Option<Rating>, ApiError> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                "SELECT rating, games FROM ratings WHERE user_id = ?1",
+                params![user_id.to_string()],
+                |row| {
+                    Ok(Rating {
+                        user_id,
+                        rating: row.get(0)?,
+                        games: row.get(1)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(database_error)
+    }
+
+    fn update_ratings(
+        &self,
+        users: &[Uuid],
+        change: &mut dyn FnMut(&mut [Rating]),
+    ) -> Result<(), ApiError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(database_error)?;
+
+        let mut ratings = Vec::with_capacity(users.len());
+        for user_id in users {
+            let rating = transaction
+                .query_row(
+                    "SELECT rating, games FROM ratings WHERE user_id = ?1",
+                    params![user_id.to_string()],
+                    |row| {
+                        Ok(Rating {
+                            user_id: *user_id,
+                            rating: row.get(0)?,
+                            games: row.get(1)?,
+                        })
+                    },
+                )
+                .optional()
+                .map_err(database_error)?;
+            ratings.push(rating.unwrap_or_else(|| Rating::new(*user_id)));
+        }
+
+        change(&mut ratings);
+        for rating in &ratings {
+            transaction
+                .execute(
+                    "INSERT INTO ratings (user_id, rating, games) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(user_id) DO UPDATE SET rating = ?2, games = ?3",
+                    params![rating.user_id.to_string(), rating.rating, rating.games],
+                )
+                .map_err(database_error)?;
+        }
+        transaction.commit().map_err(database_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::create_random_user;
+    use chrono::{Duration, Utc};
+
+    fn test_user(handle: &str, email: &str) -> User {
+        User {
+            handle: handle.to_string(),
+            email: email.to_string(),
+            ..create_random_user()
+        }
+    }
+
+    #[test]
+    fn migrate_twice() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let mut connection = storage.connection.lock().unwrap();
+        assert!(migrate(&mut connection).is_ok());
+
+        let version: i64 = connection
+            .query_row("PRAGMA user_version;", params![], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn insert_find_update_delete_user() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let mut user = test_user("player", "player@test.com");
+        let id = Uuid::parse_str(&user.id).unwrap();
+
+        assert!(storage.insert_user(&user).is_ok());
+        assert_eq!(storage.find_user(id).unwrap().unwrap().handle, "player");
+        assert_eq!(
+            storage
+                .find_user_by_email("player@test.com")
+                .unwrap()
+                .unwrap()
+                .id,
+            user.id
+        );
+
+        user.handle = "new_player".to_string();
+        assert!(storage.update_user(&user).is_ok());
+        assert_eq!(storage.get_users().unwrap()[0].handle, "new_player");
+
+        assert!(storage.delete_user(id).is_ok());
+        assert!(storage.find_user(id).unwrap().is_none());
+        assert!(matches!(
+            storage.delete_user(id),
+            Err(ApiError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn insert_taken_handle_and_email() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        assert!(storage
+            .insert_user(&test_user("player", "player@test.com"))
+            .is_ok());
+
+        let result = storage.insert_user(&test_user("player", "other@test.com"));
+        assert!(matches!(result, Err(ApiError::Conflict(message)) if message.contains("handle")));
+        let result = storage.insert_user(&test_user("other", "player@test.com"));
+        assert!(matches!(result, Err(ApiError::Conflict(message)) if message.contains("email")));
+    }
+
+    #[test]
+    fn insert_and_get_matches() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let players = vec![Uuid::new_v4(), Uuid::new_v4()];
+        let now = Utc::now().naive_utc();
+        let first = MatchResult {
+            id: Uuid::new_v4(),
+            room_id: Uuid::new_v4(),
+            replay_id: Some(Uuid::new_v4()),
+            players: players.clone(),
+            winner: Some(players[1]),
+            finished_at: now - Duration::minutes(5),
+        };
+        let second = MatchResult {
+            id: Uuid::new_v4(),
+            replay_id: None,
+            players: vec![players[0]],
+            winner: None,
+            finished_at: now,
+            ..first.clone()
+        };
+
+        assert!(storage.insert_match(&first).is_ok());
+        assert!(storage.insert_match(&second).is_ok());
+        assert_eq!(
+            storage.get_matches(players[0]).unwrap(),
+            vec![second, first.clone()]
+        );
+        assert_eq!(storage.get_matches(players[1]).unwrap(), vec![first]);
+    }
+
+    #[test]
+    fn user_in_several_seats() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let user_id = Uuid::new_v4();
+        let result = MatchResult {
+            id: Uuid::new_v4(),
+            room_id: Uuid::new_v4(),
+            replay_id: None,
+            players: vec![user_id, Uuid::new_v4(), user_id],
+            winner: Some(user_id),
+            finished_at: Utc::now().naive_utc(),
+        };
+
+        assert!(storage.insert_match(&result).is_ok());
+        assert_eq!(storage.get_matches(user_id).unwrap(), vec![result]);
+    }
+
+    #[test]
+    fn migrate_match_players() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        for migration in &MIGRATIONS[..2] {
+            connection.execute_batch(migration).unwrap();
+        }
+        connection
+            .execute_batch(
+                "PRAGMA user_version = 2;
+                 INSERT INTO matches (id, room_id, finished_at) VALUES ('m', 'r', 'now');
+                 INSERT INTO match_players (match_id, user_id) VALUES ('m', 'b'), ('m', 'a');",
+            )
+            .unwrap();
+
+        assert!(migrate(&mut connection).is_ok());
+        let players = connection
+            .prepare("SELECT seat, user_id FROM match_players ORDER BY seat")
+            .unwrap()
+            .query_map(params![], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(players, vec![(0, "b".to_string()), (1, "a".to_string())]);
+    }
+
+    #[test]
+    fn update_and_get_rating() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let user = test_user("player", "player@test.com");
+        let user_id = Uuid::parse_str(&user.id).unwrap();
+        assert!(storage.insert_user(&user).is_ok());
+        assert!(storage.get_rating(user_id).unwrap().is_none());
+
+        let mut add_game = |ratings: &mut [Rating]| {
+            ratings[0].rating += 10;
+            ratings[0].games += 1;
+        };
+        assert!(storage.update_ratings(&[user_id], &mut add_game).is_ok());
+        assert!(storage.update_ratings(&[user_id], &mut add_game).is_ok());
+        let rating = storage.get_rating(user_id).unwrap().unwrap();
+        assert_eq!(rating.rating, INITIAL_RATING + 20);
+        assert_eq!(rating.games, 2);
+
+        // Nothing is saved if one of the ratings fails
+        let unknown = Uuid::new_v4();
+        assert!(storage
+            .update_ratings(&[user_id, unknown], &mut add_game)
+            .is_err());
+        assert_eq!(storage.get_rating(user_id).unwrap(), Some(rating));
+
+        // Rating is deleted with the user
+        assert!(storage.delete_user(user_id).is_ok());
+        assert!(storage.get_rating(user_id).unwrap().is_none());
+    }
+}
@@ -4,6 +4,7 @@ use actix_web::{
     HttpResponse,
 };
 
+use serde::Serialize;
 use std::fmt;
 
 #[derive(Debug)]
@@ -12,6 +13,17 @@ pub enum ApiError {
     CannotEncodeJwtToken(String),
     BlockingError(String),
     NotFound(String),
+    Unauthorized(String),
+    Forbidden(String),
+    BadRequest(String),
+    Conflict(String),
+    DatabaseError(String),
+}
+
+// Body of the response on client's errors
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
 }
 
 impl fmt::Display for ApiError {
@@ -21,14 +33,31 @@ impl fmt::Display for ApiError {
             ApiError::CannotDecodeJwtToken(s) => write!(f, "CannotDecodeJwtToken({})", s),
             ApiError::NotFound(s) => write!(f, "NotFound({})", s),
             ApiError::BlockingError(s) => write!(f, "BlockingError({})", s),
+            ApiError::Unauthorized(s) => write!(f, "Unauthorized({})", s),
+            ApiError::Forbidden(s) => write!(f, "Forbidden({})", s),
+            ApiError::BadRequest(s) => write!(f, "BadRequest({})", s),
+            ApiError::Conflict(s) => write!(f, "Conflict({})", s),
+            ApiError::DatabaseError(s) => write!(f, "DatabaseError({})", s),
         }
     }
 }
 
 impl ResponseError for ApiError {
     fn error_response(&self) -> HttpResponse {
-        match self {
-            _ => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+        let (status, error) = match self {
+            ApiError::CannotDecodeJwtToken(_) => (StatusCode::UNAUTHORIZED, None),
+            ApiError::Unauthorized(s) => (StatusCode::UNAUTHORIZED, Some(s)),
+            ApiError::Forbidden(s) => (StatusCode::FORBIDDEN, Some(s)),
+            ApiError::NotFound(s) => (StatusCode::NOT_FOUND, Some(s)),
+            ApiError::BadRequest(s) => (StatusCode::BAD_REQUEST, Some(s)),
+            ApiError::Conflict(s) => (StatusCode::CONFLICT, Some(s)),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, None),
+        };
+        match error {
+            Some(error) => HttpResponse::build(status).json(ErrorResponse {
+                error: error.to_string(),
+            }),
+            None => HttpResponse::new(status),
         }
     }
 }
@@ -1,32 +1,42 @@
 use crate::api::common::Point;
 use crate::game_objects::grid::Grid;
 use crate::game_objects::hex::Hex;
-use crate::game_objects::hex_objects::content::Content;
-use crate::game_objects::hex_objects::wall::Wall;
-use crate::game_objects::unit::Unit;
+use crate::game_objects::hex_objects::content::{Content, PASSABLE_TERRAIN};
+use crate::game_objects::kind::Kind;
+use crate::game_objects::unit::{Unit, DEFAULT_RANGE};
+use crate::generator;
+use crate::hex_coords;
+use crate::settings::{roll_range, Settings, Symmetry};
+use crate::sight;
 
 use eyre::{Result, WrapErr};
-use rand::Rng;
-use std::collections::HashMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 use thiserror::Error;
 use tracing::instrument;
 
-const NUM_X: (u32, u32) = (5, 15);
-const NUM_Y: (u32, u32) = (5, 15);
-const WALLS_PERCENT: (u8, u8) = (0, 40);
-const NUM_UNITS: (u8, u8) = (2, 6);
-const UNIT_HP: (u8, u8) = (1, 10);
-const UNIT_MIN_DAMAGE: (u8, u8) = (1, 5);
-const UNIT_DAMAGE_INTERVAL: (u8, u8) = (1, 5);
-const UNIT_SPEED: (u8, u8) = (1, 8);
-
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Game {
     pub field: Grid,
     pub selected_hex: Option<Hex>,
+    pub current_player: u32,
+    pub turn: u32,
+    // Seed of the game generation and all random rolls during the game
+    pub seed: u32,
+    // Number of rolls made since the start of the game
+    rolls: u32,
+    // Settings of the random game, none for the preset
+    #[serde(default)]
+    pub settings: Option<Settings>,
+    // Surviving melee defender strikes back once per round
+    #[serde(default)]
+    pub retaliation: bool,
 }
 
-#[derive(Error, Debug, PartialEq)]
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
 pub enum GameError {
     #[error("no hex")]
     NoHex,
@@ -48,12 +58,73 @@ pub enum GameError {
 
     #[error("unit has been already moved")]
     AlreadyMoved,
+
+    #[error("shot is blocked")]
+    Blocked,
+
+    #[error("no free hex")]
+    FieldIsFull,
+}
+
+// Result of the attack, hexes are taken after it
+#[derive(Debug)]
+pub struct Combat {
+    pub damage: u32,
+    // Damage dealt back by the defender, none if it hasn't struck back
+    pub retaliation: Option<u32>,
+    // Both the defender and the attacker may be here
+    pub hurt: Vec<Hex>,
+    pub die: Vec<Hex>,
+}
+
+// Cheapest paths from one hex to all hexes reachable from it
+#[derive(Debug)]
+pub struct Paths {
+    from: Point,
+    // Cost of the cheapest path to the point and the previous point on it
+    steps: HashMap<Point, (u32, Point)>,
+    // Reachable points from the cheapest to the most expensive
+    order: Vec<Point>,
+}
+
+impl Paths {
+    pub fn points(&self) -> Vec<Point> {
+        self.order.clone()
+    }
+
+    pub fn cost(&self, to: &Point) -> Option<u32> {
+        self.steps.get(to).map(|(cost, _)| *cost)
+    }
+
+    // Points of the cheapest path, both ends included
+    pub fn path(&self, to: Point) -> Result<Vec<Point>> {
+        if !self.steps.contains_key(&to) {
+            Err(GameError::NoHex).wrap_err("no finish hex in paths")?
+        }
+
+        let mut path = vec![to];
+        let mut current = to;
+        while current != self.from {
+            current = match self.steps.get(&current) {
+                Some((_, previous)) => *previous,
+                None => Err(GameError::NoHex)
+                    .wrap_err_with(|| format!("no hex before {:?} in paths", current))?,
+            };
+            path.push(current);
+        }
+
+        // path was restored from last point to first, so we reverse it
+        path.reverse();
+        Ok(path)
+    }
 }
 
 #[derive(Debug)]
 pub struct Selection {
     pub target: Hex,
     pub highlight_hexes: Vec<Point>,
+    // Enemies the unit can attack without moving
+    pub attack_hexes: Vec<Point>,
 }
 
 // Actions that user want to take now
@@ -71,44 +142,104 @@ impl Game {
         Game {
             field: Grid::new(num_x, num_y),
             selected_hex: None,
+            current_player: 0,
+            turn: 0,
+            seed: 0,
+            rolls: 0,
+            retaliation: false,
+            settings: None,
         }
     }
 
-    pub fn random(num_of_players: usize) -> Game {
-        let mut rng = rand::thread_rng();
-        let num_x = rng.gen_range(NUM_X.0, NUM_X.1 + 1);
-        let num_y = rng.gen_range(NUM_Y.0, NUM_Y.1 + 1);
-        let mut game = Game::new(num_x, num_y);
+    // Game on the ready field
+    pub fn with_field(field: Grid, seed: u32) -> Game {
+        Game {
+            field,
+            seed,
+            ..Game::new(0, 0)
+        }
+    }
 
-        assert!(WALLS_PERCENT.1 <= 100);
-        let walls_percent = rng.gen_range(WALLS_PERCENT.0, WALLS_PERCENT.1 + 1);
-        debug!("walls percent {:?}", walls_percent);
+    // The same seed and settings always give the same game
+    // Settings must be validated before
+    // Every unit can reach every enemy, units of each player start near their own anchor
+    pub fn random(num_of_players: usize, seed: u32, settings: Settings) -> Result<Game> {
+        // Field has its own rng, so the map seed gives the same field with any units
+        let mut map_rng = StdRng::seed_from_u64(settings.map_seed.unwrap_or(seed) as u64);
+        let mut rng = StdRng::seed_from_u64(seed as u64);
+        let num_x = roll_range(&mut map_rng, settings.num_x);
+        let symmetry = settings.symmetry;
+        let num_y = roll_range(&mut map_rng, settings.num_y);
+        let num_y = generator::rows(symmetry, num_y, settings.num_y);
+        let mut game = Game {
+            seed,
+            settings: Some(settings.clone()),
+            ..Game::new(num_x, num_y)
+        };
 
-        let num_of_walls = (num_x * num_y * walls_percent as u32 / 100) as u8;
-        for _ in 0..num_of_walls {
-            if let Err(e) = game.set_content_randomly(Content::Wall(Wall {})) {
-                panic!("Error while setting content for new game randomly:\n{}", e);
-            }
+        let walls_percent = roll_range(&mut map_rng, settings.walls_percent);
+        debug!("{:?} map, walls percent {:?}", settings.map, walls_percent);
+        settings
+            .map
+            .generator(walls_percent)
+            .generate(&mut game.field, &mut map_rng)
+            .wrap_err("new random game")?;
+        generator::symmetrize(&mut game.field, symmetry).wrap_err("new random game")?;
+
+        let terrain_percent = roll_range(&mut map_rng, settings.terrain_percent);
+        debug!("terrain percent {:?}", terrain_percent);
+
+        let num_of_terrain = num_x * num_y * terrain_percent as u32 / 100;
+        let mut placed = 0;
+        while placed < num_of_terrain {
+            let content = PASSABLE_TERRAIN[map_rng.gen_range(0, PASSABLE_TERRAIN.len())];
+            placed += generator::place_content(&mut game.field, content, symmetry, &mut map_rng)
+                .wrap_err("new random game")?;
         }
+        generator::connect(&mut game.field, symmetry).wrap_err("new random game")?;
 
-        let num_of_units = rng.gen_range(NUM_UNITS.0, NUM_UNITS.1 + 1);
-        debug!("unit number {:?}", num_of_units);
-        for player_number in 0..num_of_players {
-            for _ in 0..num_of_units {
-                let unit = Unit::random(
-                    UNIT_HP,
-                    UNIT_MIN_DAMAGE,
-                    UNIT_DAMAGE_INTERVAL,
-                    UNIT_SPEED,
-                    player_number as u32,
-                );
-                if let Err(e) = game.set_unit_randomly(unit) {
-                    panic!("Error while setting unit for new game randomly:\n{}", e);
+        // Every player gets units of the same kinds, none for the plain random unit
+        let army: Vec<Option<Kind>> = if settings.army.is_empty() {
+            let num_of_units = roll_range(&mut rng, settings.num_units);
+            vec![None; num_of_units as usize]
+        } else {
+            let mut army = vec![];
+            for squad in &settings.army {
+                let count = roll_range(&mut rng, squad.count);
+                for _ in 0..count {
+                    army.push(Some(squad.kind));
                 }
             }
+            army
+        };
+        debug!("army {:?}", army);
+
+        // With symmetry the second player gets copies of units of the first one
+        let anchors = generator::anchors(&game.field, num_of_players);
+        let players = match symmetry {
+            Symmetry::None => num_of_players,
+            _ => 1,
+        };
+        // Players take hexes in turns, so nobody gets only the worst ones
+        for kind in &army {
+            for (player_number, anchor) in anchors.iter().enumerate().take(players) {
+                let unit = match kind {
+                    Some(kind) => Unit::of_kind(*kind, player_number as u32, &mut rng),
+                    None => Unit::random(
+                        settings.unit_hp,
+                        settings.unit_min_damage,
+                        settings.unit_damage_interval,
+                        settings.unit_speed,
+                        player_number as u32,
+                        &mut rng,
+                    ),
+                };
+                generator::place_unit(&mut game, unit, *anchor, symmetry, &mut rng)
+                    .wrap_err("new random game")?;
+            }
         }
 
-        game
+        Ok(game)
     }
 
     // based on the state of the game and the position of the click, returns the action that should be performed
@@ -158,6 +289,7 @@ impl Game {
                 Ok(Selection {
                     target: self.selected_hex.clone().unwrap(),
                     highlight_hexes: self.available_points(&self.selected_hex),
+                    attack_hexes: self.attack_points(&self.selected_hex),
                 })
             }
             None => Err(GameError::NoUnit).wrap_err("select unit")?,
@@ -191,13 +323,11 @@ impl Game {
             return Err(GameError::NoMoves).wrap_err("no moves left")?;
         }
 
-        let mut hexmap: HashMap<Point, u32> = HashMap::with_capacity(self.field.hexes.len());
-
-        self.fill_path_hexmap(&from_hex, 0, unit.movements, &mut hexmap);
-
-        let path = match self.restore_path_from_hexmap(from_hex.to_point(), to, &hexmap) {
-            Ok(path) => path,
-            Err(e) => return Err(e.wrap_err("restore path")),
+        let paths = self.find_paths(&from_hex, unit.movements);
+        let path = paths.path(to).wrap_err("restore path")?;
+        let cost = match paths.cost(&to) {
+            Some(cost) => cost,
+            None => Err(GameError::NoHex).wrap_err("no cost of the path")?,
         };
 
         // TODO: try to remove double get_hex(from.x, from.y)
@@ -218,15 +348,14 @@ impl Game {
             None => Err(GameError::NoHex).wrap_err("hex to disappeared after pathfinding")?,
         };
 
-        // TODO: change movement correctly
-        unit.change_movements(unit.movements);
+        unit.change_movements(cost);
         to_hex.set_unit(Some(unit));
 
         Ok(path)
     }
 
     #[instrument(skip(self))]
-    pub fn attack(&mut self, to: Point) -> Result<(Vec<Hex>, Vec<Hex>)> {
+    pub fn attack(&mut self, to: Point) -> Result<Combat> {
         let from_hex = match self.selected_hex {
             Some(hex) => hex,
             None => Err(GameError::NoHex).wrap_err("attack from")?,
@@ -236,35 +365,41 @@ impl Game {
     }
 
     #[instrument(skip(self))]
-    fn attack_internal(&mut self, from_hex: Hex, to: Point) -> Result<(Vec<Hex>, Vec<Hex>)> {
+    fn attack_internal(&mut self, from_hex: Hex, to: Point) -> Result<Combat> {
         let from_unit = match from_hex.get_unit() {
             Some(unit) => unit,
             None => Err(GameError::NoUnit).wrap_err("attack from")?,
         };
+        if !from_unit.can_attack() {
+            Err(GameError::AlreadyMoved).wrap_err("attack from")?;
+        }
 
         // Check if we can attack to target hex
         // Due to borrow rules we have to get unmuted hex here
         match self.get_hex(to.x, to.y) {
-            Some(to_hex) => {
-                if !self.find_neighbours(&from_hex.to_point()).contains(&to_hex) {
-                    Err(GameError::WrongHex).wrap_err("not neighbour")?;
-                }
+            Some(_) => {
+                let from = from_hex.to_point();
+                self.check_target(from_unit, from, from, to)
+                    .wrap_err("attack to")?;
             }
             None => Err(GameError::NoHex).wrap_err("attack to")?,
         };
 
+        let dmg = self.roll(from_unit.damage[0], from_unit.damage[1]);
+        let retaliation = self.retaliation;
+
         let to_hex = match self.get_hex_mut(to.x, to.y) {
             Some(hex) => hex,
             None => Err(GameError::NoHex).wrap_err("attack to")?,
         };
+        // Terrain of the defender absorbs a part of the damage
+        let dmg = dmg.saturating_sub(to_hex.defence());
 
         let to_unit = match to_hex.get_unit_mut() {
             Some(unit) => unit,
             None => Err(GameError::NoUnit).wrap_err("attack to")?,
         };
 
-        let mut rng = rand::thread_rng();
-        let dmg = rng.gen_range(from_unit.damage[0], from_unit.damage[1] + 1);
         to_unit.change_hp(-(dmg as i32));
 
         let mut hurt: Vec<Hex> = vec![];
@@ -272,20 +407,88 @@ impl Game {
         if to_unit.hp == 0 {
             die.push(*to_hex);
             to_hex.set_unit(None);
-        } else {
+            return Ok(Combat {
+                damage: dmg,
+                retaliation: None,
+                hurt,
+                die,
+            });
+        }
+
+        let defender = *to_unit;
+        let can_strike_back = retaliation
+            && !defender.retaliated
+            && defender.range == DEFAULT_RANGE
+            && hex_coords::distance(from_hex.to_point(), to) <= defender.range;
+        if !can_strike_back {
             hurt.push(*to_hex);
+            return Ok(Combat {
+                damage: dmg,
+                retaliation: None,
+                hurt,
+                die,
+            });
         }
-        Ok((hurt, die))
+
+        if let Some(unit) = to_hex.get_unit_mut() {
+            unit.retaliated = true;
+        }
+        hurt.push(*to_hex);
+
+        let back_dmg = self.roll(defender.damage[0], defender.damage[1]);
+        let from_point = from_hex.to_point();
+        let from_hex = match self.get_hex_mut(from_point.x, from_point.y) {
+            Some(hex) => hex,
+            None => Err(GameError::NoHex).wrap_err("retaliation to")?,
+        };
+        let back_dmg = back_dmg.saturating_sub(from_hex.defence());
+        let from_unit = match from_hex.get_unit_mut() {
+            Some(unit) => unit,
+            None => Err(GameError::NoUnit).wrap_err("retaliation to")?,
+        };
+
+        from_unit.change_hp(-(back_dmg as i32));
+        if from_unit.hp == 0 {
+            die.push(*from_hex);
+            from_hex.set_unit(None);
+        } else {
+            hurt.push(*from_hex);
+        }
+        Ok(Combat {
+            damage: dmg,
+            retaliation: Some(back_dmg),
+            hurt,
+            die,
+        })
     }
 
     pub fn ends(&self) -> bool {
         self.field.players_alive().len() == 1
     }
 
+    // Pass turn to the next player who is still alive
+    pub fn change_player(&mut self, num_of_players: u32) {
+        loop {
+            self.current_player += 1;
+            self.current_player %= num_of_players;
+            if self.field.players_alive().contains(&self.current_player) {
+                break;
+            }
+        }
+        self.turn += 1;
+    }
+
+    // Random number in [min, max]
+    // Every roll gets its own rng, so saved game continues with the same rolls
+    fn roll(&mut self, min: u32, max: u32) -> u32 {
+        let mut rng = StdRng::seed_from_u64(((self.seed as u64) << 32) | self.rolls as u64);
+        self.rolls += 1;
+        roll_range(&mut rng, (min, max))
+    }
+
     // Private api
 
     // Unit staff
-    // TODO: remove pub after creating new games from presets
     #[instrument(skip(self))]
     pub fn set_unit(&mut self, x: u32, y: u32, unit: Option<Unit>) -> Result<()> {
         match self.get_hex_mut(x, y) {
@@ -322,16 +525,70 @@ impl Game {
             .collect()
     }
 
+    // Remove all units of the player, return hexes they were on
+    pub fn remove_player(&mut self, player: u32) -> Vec<Hex> {
+        let mut removed = vec![];
+        for hex in self.field.hexes.iter_mut() {
+            if matches!(hex.unit, Some(unit) if unit.player == player) {
+                removed.push(*hex);
+                hex.set_unit(None);
+            }
+        }
+        if matches!(self.selected_hex, Some(hex) if removed.contains(&hex)) {
+            self.deselect_unit();
+        }
+        removed
+    }
+
+    // Heal allies and restore movements at the end of the player's turn, return changed hexes
     pub fn restore_movements(&mut self, player: u32) -> Vec<Hex> {
-        let mut hexes = vec![];
+        let mut changed = self.heal_allies(player);
         for hex in self.get_unit_hexes_for_player_mut(player) {
             let unit = hex.get_unit_mut().unwrap();
-            if unit.has_moved() {
+            if unit.has_moved() || unit.retaliated {
                 unit.restore_movements();
-                hexes.push(*hex);
+                // Unit can strike back again in the next round
+                unit.retaliated = false;
+                changed.push(hex.to_point());
+            }
+        }
+        self.field
+            .hexes
+            .iter()
+            .filter(|hex| changed.contains(&hex.to_point()))
+            .copied()
+            .collect()
+    }
+
+    // Healers restore hp of the neighbour units of their player, return healed points
+    fn heal_allies(&mut self, player: u32) -> Vec<Point> {
+        let mut heals = vec![];
+        for hex in &self.field.hexes {
+            let heal = match hex.unit {
+                Some(unit) if unit.is_my(player) => unit.kind.stats().heal,
+                _ => continue,
+            };
+            if heal == 0 {
+                continue;
+            }
+            for neighbour in self.find_neighbours(&hex.to_point()) {
+                if matches!(neighbour.unit, Some(unit) if unit.is_my(player)) {
+                    heals.push((neighbour.to_point(), heal));
+                }
             }
         }
-        hexes
+
+        let mut healed = vec![];
+        for (point, heal) in heals {
+            if let Some(hex) = self.get_hex_mut(point.x, point.y) {
+                if let Some(unit) = hex.get_unit_mut() {
+                    if unit.heal(heal) {
+                        healed.push(point);
+                    }
+                }
+            }
+        }
+        healed
     }
 
     // Hex stuff
@@ -361,7 +618,6 @@ impl Game {
     }
 
     // Content stuff
-    // TODO: remove pub after creating new games from presets
     // Set content to the point (x, y)
     #[instrument(skip(self))]
     pub fn set_content(&mut self, x: u32, y: u32, content: Option<Content>) -> Result<()> {
@@ -388,151 +644,120 @@ impl Game {
             None => return vec![],
         };
 
-        let mut hexmap: HashMap<Point, u32> = HashMap::with_capacity(self.field.hexes.len());
-
-        self.fill_path_hexmap(from_hex, 0, unit.movements, &mut hexmap);
-
-        hexmap.into_keys().collect()
+        self.find_paths(from_hex, unit.movements).points()
     }
 
-    // TODO: We don't need two different functions for find available points and find path
-    fn fill_path_hexmap(
-        &self,
-        hex: &Hex,
-        value: u32,
-        max_value: u32,
-        hexmap: &mut HashMap<Point, u32>,
-    ) {
-        if value > max_value {
-            return;
-        }
-        let hexmap_value = hexmap.get(&hex.to_point());
-        // We proceed if we were not in this hex
-        // or if we were here, but through longer path
-        if hexmap_value.is_none() || hexmap_value.unwrap() > &value {
-            if hex.get_unit().is_some() && value != 0 {
-                return;
-            }
-            if hex.get_content().is_some() {
-                return;
-            }
+    // Unit has movement points left to go somewhere from the hex
+    pub fn can_move(&self, hex: &Hex) -> bool {
+        self.available_points(&Some(*hex))
+            .iter()
+            .any(|point| *point != hex.to_point())
+    }
 
-            hexmap.insert(hex.to_point(), value);
-            for hex in self.find_neighbours(&hex.to_point()) {
-                self.fill_path_hexmap(&hex, value + 1, max_value, hexmap)
-            }
+    // Return points of enemies the unit in given hex can attack without moving
+    pub fn attack_points(&self, from: &Option<Hex>) -> Vec<Point> {
+        match from {
+            Some(hex) => self
+                .targets(hex, hex.to_point())
+                .iter()
+                .map(Hex::to_point)
+                .collect(),
+            None => vec![],
         }
     }
 
-    fn find_neighbours(&self, point: &Point) -> Vec<Hex> {
-        let mut hexes: Vec<Hex> = Vec::with_capacity(6);
-        let min_x: u32 = if point.x == 0 { point.x } else { point.x - 1 };
-        let min_y: u32 = if point.y == 0 { point.y } else { point.y - 1 };
-        for x in min_x..point.x + 2 {
-            for y in min_y..point.y + 2 {
-                if x == point.x && y == point.y {
-                    continue;
-                }
-                let new_hex = match self.get_hex(x, y) {
-                    Some(hex) => hex,
-                    None => continue,
-                };
-
-                // We iterate through nine hexes, but hex has only six neighbours
-                // For even and odd rows we need to drop different hexes,
-                // that are defined by this formulas
-                if point.y % 2 == 0 {
-                    if x == point.x.overflowing_sub(1).0 && y != point.y {
-                        continue;
-                    }
-                } else if x == point.x + 1 && y != point.y {
-                    continue;
-                }
-
-                hexes.push(new_hex);
-            }
+    // Enemies the unit from the hex could attack after moving to the point
+    pub fn targets(&self, unit_hex: &Hex, from: Point) -> Vec<Hex> {
+        let unit = match unit_hex.unit {
+            Some(unit) => unit,
+            None => return vec![],
+        };
+        let moves = from != unit_hex.to_point();
+        if !unit.can_attack() || (moves && !unit.kind.stats().attack_after_move) {
+            return vec![];
         }
 
-        hexes
+        self.field
+            .hexes
+            .iter()
+            .filter(|hex| matches!(hex.unit, Some(target) if !target.is_my(unit.player)))
+            .filter(|hex| {
+                self.check_target(unit, unit_hex.to_point(), from, hex.to_point())
+                    .is_ok()
+            })
+            .copied()
+            .collect()
     }
 
-    fn restore_path_from_hexmap(
+    // Target must be in range of the unit and nothing must stand in the way
+    // Hex the unit has left doesn't block shots
+    fn check_target(
         &self,
+        unit: Unit,
+        left: Point,
         from: Point,
         to: Point,
-        hexmap: &HashMap<Point, u32>,
-    ) -> Result<Vec<Point>> {
-        if !hexmap.contains_key(&from) {
-            Err(GameError::NoHex).wrap_err("no start hex in hexmap")?
+    ) -> std::result::Result<(), GameError> {
+        if hex_coords::distance(from, to) > unit.range {
+            return Err(GameError::WrongHex);
         }
-
-        let path_length = match hexmap.get(&to) {
-            Some(length) => *length + 1,
-            None => Err(GameError::NoHex).wrap_err("no finish hex in hexmap")?,
-        };
-
-        let mut path = Vec::with_capacity(path_length as usize);
-        path.push(to);
-
-        for i in (0..path_length - 1).rev() {
-            // filter all hexes which are neighbours of previous hex in path
-            // then find at least one neighbour with needed distance from initial point
-            match hexmap
-                .iter()
-                .filter(|&(k, _)| {
-                    self.find_neighbours(
-                        &path.last().expect("empty path in restore path algorithm"),
-                    )
-                    .iter()
-                    .map(|hex| hex.to_point())
-                    .any(|point| point == *k)
-                })
-                .find(|&(_, v)| *v == i)
-            {
-                Some((point, _)) => path.push(*point),
-                None => Err(GameError::NoHex)
-                    .wrap_err_with(|| format!("no hex in hexmap for distance {}", i))?,
-            }
+        let clear = sight::line_is_clear(&self.field, from, to, |hex| {
+            sight::blocks_sight(hex) || (hex.unit.is_some() && hex.to_point() != left)
+        });
+        if !clear {
+            return Err(GameError::Blocked);
         }
-
-        // path was restored from last point to first, so we reverse it
-        path.reverse();
-        Ok(path)
+        Ok(())
     }
 
-    fn set_content_randomly(&mut self, content: Content) -> Result<()> {
-        let mut rng = rand::thread_rng();
-        loop {
-            let x = rng.gen_range(0, self.field.num_x);
-            let y = rng.gen_range(0, self.field.num_y);
-            debug!("\ngetting hex with coordinates {}:{}", x, y);
-            let hex = match self.field.get_hex_mut(x, y) {
-                Some(hex) => hex,
-                None => Err(GameError::NoHex).wrap_err_with(|| format!("get hex {}:{}", x, y))?,
-            };
+    // Dijkstra search of the cheapest paths not longer than max_cost from the hex
+    // Units and impassable terrain block the way, the hex itself is always reachable
+    pub fn find_paths(&self, from_hex: &Hex, max_cost: u32) -> Paths {
+        let from = from_hex.to_point();
+        let mut paths = Paths {
+            from,
+            steps: HashMap::new(),
+            order: vec![],
+        };
+        let mut queue = BinaryHeap::new();
+        paths.steps.insert(from, (0, from));
+        queue.push(Reverse((0, from.x, from.y)));
+
+        while let Some(Reverse((cost, x, y))) = queue.pop() {
+            let point = Point { x, y };
+            // Point was reached cheaper after it had been queued
+            if paths.steps[&point].0 < cost {
+                continue;
+            }
+            paths.order.push(point);
 
-            if hex.is_empty() {
-                hex.set_content(Some(content));
-                break Ok(());
+            for hex in self.find_neighbours(&point) {
+                if hex.unit.is_some() {
+                    continue;
+                }
+                let new_cost = match hex.move_cost() {
+                    Some(step) if cost + step <= max_cost => cost + step,
+                    _ => continue,
+                };
+                let next = hex.to_point();
+                if let Some((old_cost, _)) = paths.steps.get(&next) {
+                    if *old_cost <= new_cost {
+                        continue;
+                    }
+                }
+                paths.steps.insert(next, (new_cost, point));
+                queue.push(Reverse((new_cost, next.x, next.y)));
             }
         }
-    }
 
-    fn set_unit_randomly(&mut self, unit: Unit) -> Result<()> {
-        let mut rng = rand::thread_rng();
-        loop {
-            let x = rng.gen_range(0, self.field.num_x);
-            let y = rng.gen_range(0, self.field.num_y);
-            let hex = match self.field.get_hex_mut(x, y) {
-                Some(hex) => hex,
-                None => Err(GameError::NoHex).wrap_err_with(|| format!("get hex {}:{}", x, y))?,
-            };
+        paths
+    }
 
-            if hex.is_empty() {
-                hex.set_unit(Some(unit));
-                break Ok(());
-            }
-        }
+    pub fn find_neighbours(&self, point: &Point) -> Vec<Hex> {
+        hex_coords::neighbours(*point)
+            .into_iter()
+            .filter_map(|point| self.get_hex(point.x, point.y))
+            .collect()
     }
 }
 
@@ -540,6 +765,7 @@ impl Game {
 mod test {
     use super::*;
     use crate::game_objects::hex_objects::wall::Wall;
+    use crate::settings::{MapKind, Squad};
 
     // Test game is a game with 2x2 field
     // There are 2 units (U) and 1 wall (W) on it
@@ -607,6 +833,177 @@ mod test {
         }
     }
 
+    #[test]
+    fn random_with_same_seed() {
+        let game = Game::random(2, 42, Settings::default()).unwrap();
+        let same_game = Game::random(2, 42, Settings::default()).unwrap();
+
+        assert_eq!(game.seed, 42);
+        assert_eq!(
+            serde_json::to_string(&game.field).unwrap(),
+            serde_json::to_string(&same_game.field).unwrap(),
+        );
+    }
+
+    #[test]
+    fn random_with_settings() {
+        let settings = Settings {
+            num_x: (4, 4),
+            num_y: (6, 6),
+            walls_percent: (0, 0),
+            terrain_percent: (0, 0),
+            num_units: (3, 3),
+            unit_hp: (2, 2),
+            unit_min_damage: (1, 1),
+            unit_damage_interval: (0, 0),
+            unit_speed: (4, 4),
+            army: vec![],
+            symmetry: Symmetry::None,
+            map: MapKind::Uniform,
+            map_seed: None,
+        };
+        let game = Game::random(2, 9, settings.clone()).unwrap();
+
+        assert_eq!(game.settings, Some(settings));
+        assert_eq!(game.field.num_x, 4);
+        assert_eq!(game.field.num_y, 6);
+        assert!(game.field.hexes.iter().all(|hex| hex.content.is_none()));
+
+        let units: Vec<&Unit> = game
+            .field
+            .hexes
+            .iter()
+            .filter_map(|hex| hex.unit.as_ref())
+            .collect();
+        assert_eq!(units.len(), 6);
+        assert!(units
+            .iter()
+            .all(|unit| unit.hp == 2 && unit.damage == [1, 1] && unit.speed == 4));
+    }
+
+    #[test]
+    fn random_with_map_seed() {
+        let settings = Settings {
+            map: MapKind::Caves,
+            map_seed: Some(11),
+            ..Settings::default()
+        };
+        let game = Game::random(2, 1, settings.clone()).unwrap();
+        let other_game = Game::random(2, 2, settings).unwrap();
+
+        let contents = |game: &Game| {
+            let contents: Vec<_> = game.field.hexes.iter().map(|hex| hex.content).collect();
+            serde_json::to_string(&contents).unwrap()
+        };
+        assert_eq!(contents(&game), contents(&other_game));
+    }
+
+    #[test]
+    fn random_with_terrain() {
+        let settings = Settings {
+            num_x: (10, 10),
+            num_y: (10, 10),
+            walls_percent: (20, 20),
+            terrain_percent: (30, 30),
+            ..Settings::default()
+        };
+        let game = Game::random(2, 5, settings).unwrap();
+
+        let count = |passable: bool| {
+            game.field
+                .hexes
+                .iter()
+                .filter_map(|hex| hex.content)
+                .filter(|content| content.move_cost().is_some() == passable)
+                .count()
+        };
+        // Obstacles between units may be cleared
+        assert!(count(false) <= 20);
+        assert_eq!(count(true), 30);
+        assert!(game
+            .field
+            .hexes
+            .iter()
+            .filter(|hex| hex.unit.is_some())
+            .all(|hex| hex.move_cost().is_some()));
+    }
+
+    #[test]
+    fn attack_with_same_seed() {
+        // Attack strong unit several times and remember its hp after each attack
+        fn hp_after_attacks() -> Vec<u32> {
+            let (mut game, unit, _) = test_game();
+            let from = Point { x: 0, y: 0 };
+            let to = Point { x: 1, y: 1 };
+            let attacking_unit = Unit {
+                damage: [1, 5],
+                ..unit
+            };
+            let defending_unit = Unit { hp: 100, ..unit };
+            assert!(game.set_unit(from.x, from.y, Some(attacking_unit)).is_ok());
+            assert!(game.set_unit(to.x, to.y, Some(defending_unit)).is_ok());
+
+            let from = game.get_hex(from.x, from.y).unwrap();
+            (0..10)
+                .map(|_| {
+                    assert!(game.attack_internal(from, to).is_ok());
+                    game.get_unit(to.x, to.y).unwrap().unwrap().hp
+                })
+                .collect()
+        }
+
+        assert_eq!(hp_after_attacks(), hp_after_attacks());
+    }
+
+    #[test]
+    fn serialize_deserialize() {
+        let mut game = Game::random(2, 13, Settings::default()).unwrap();
+        let unit_hex = *game
+            .field
+            .hexes
+            .iter()
+            .find(|hex| hex.unit.is_some())
+            .unwrap();
+        assert!(game.select_unit(unit_hex.to_point()).is_ok());
+        game.change_player(2);
+
+        let game_string = serde_json::to_string(&game).unwrap();
+        let restored: Game = serde_json::from_str(&game_string).unwrap();
+
+        assert_eq!(restored.selected_hex, Some(unit_hex));
+        assert_eq!(restored.current_player, 1);
+        assert_eq!(restored.turn, 1);
+        assert_eq!(restored.seed, 13);
+        assert_eq!(serde_json::to_string(&restored).unwrap(), game_string);
+    }
+
+    #[test]
+    fn restored_game_rolls_the_same() {
+        let (mut game, unit, _) = test_game();
+        let from = Point { x: 0, y: 0 };
+        let to = Point { x: 1, y: 1 };
+        let attacking_unit = Unit {
+            damage: [1, 5],
+            ..unit
+        };
+        assert!(game.set_unit(from.x, from.y, Some(attacking_unit)).is_ok());
+        assert!(game
+            .set_unit(to.x, to.y, Some(Unit { hp: 100, ..unit }))
+            .is_ok());
+        let from = game.get_hex(from.x, from.y).unwrap();
+        assert!(game.attack_internal(from, to).is_ok());
+
+        let mut restored: Game =
+            serde_json::from_str(&serde_json::to_string(&game).unwrap()).unwrap();
+        assert!(game.attack_internal(from, to).is_ok());
+        assert!(restored.attack_internal(from, to).is_ok());
+
+        assert_eq!(
+            game.get_unit(to.x, to.y).unwrap().unwrap().hp,
+            restored.get_unit(to.x, to.y).unwrap().unwrap().hp
+        );
+    }
+
     #[test]
     fn move_unit_success() {
         let (mut game, _, _) = test_game();
@@ -640,6 +1037,144 @@ mod test {
         assert!(game.get_hex(to.x, to.y).unwrap().get_content().is_some());
     }
 
+    // Unit with speed 4 in the row of swamp, road, empty hex and water
+    fn terrain_game() -> Game {
+        let mut game = Game::new(5, 1);
+        assert!(game
+            .set_unit(0, 0, Some(Unit::new(0, 5, [1, 1], 4)))
+            .is_ok());
+        assert!(game.set_content(1, 0, Some(Content::Swamp)).is_ok());
+        assert!(game.set_content(2, 0, Some(Content::Road)).is_ok());
+        assert!(game.set_content(4, 0, Some(Content::Water)).is_ok());
+        game
+    }
+
+    #[test]
+    fn available_points_with_terrain() {
+        let game = terrain_game();
+        let mut points = game.available_points(&game.get_hex(0, 0));
+        points.sort_by_key(|point| point.x);
+
+        assert_eq!(
+            points,
+            vec![
+                Point { x: 0, y: 0 },
+                Point { x: 1, y: 0 },
+                Point { x: 2, y: 0 }
+            ]
+        );
+    }
+
+    #[test]
+    fn move_unit_spends_path_cost() {
+        let mut game = terrain_game();
+        let from = game.get_hex(0, 0).unwrap();
+
+        let path = game.move_unit_internal(from, Point { x: 1, y: 0 }).unwrap();
+        assert_eq!(path, vec![Point { x: 0, y: 0 }, Point { x: 1, y: 0 }]);
+        assert_eq!(game.get_unit(1, 0).unwrap().unwrap().movements, 1);
+
+        // One point is enough for the road
+        let from = game.get_hex(1, 0).unwrap();
+        assert!(game.move_unit_internal(from, Point { x: 2, y: 0 }).is_ok());
+        assert_eq!(game.get_unit(2, 0).unwrap().unwrap().movements, 0);
+    }
+
+    #[test]
+    fn move_again_with_points_left() {
+        let mut game = terrain_game();
+        assert!(game.select_unit(Point { x: 0, y: 0 }).is_ok());
+        assert!(game.move_unit(Point { x: 1, y: 0 }).is_ok());
+
+        // Unit stays selected at the new hex with the points left, it can even go back
+        let selection = game.select_unit(Point { x: 1, y: 0 }).unwrap();
+        assert_eq!(selection.target.unit.unwrap().movements, 1);
+        assert_eq!(
+            selection.highlight_hexes,
+            vec![
+                Point { x: 1, y: 0 },
+                Point { x: 0, y: 0 },
+                Point { x: 2, y: 0 }
+            ]
+        );
+        assert!(game.can_move(&selection.target));
+        assert!(matches!(
+            game.get_current_action(Point { x: 2, y: 0 }, 0),
+            Ok(Action::Move)
+        ));
+
+        assert!(game.move_unit(Point { x: 2, y: 0 }).is_ok());
+        let selection = game.select_unit(Point { x: 2, y: 0 }).unwrap();
+        assert!(!game.can_move(&selection.target));
+        let result = game.move_unit(Point { x: 3, y: 0 });
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<GameError>(),
+            Some(&GameError::NoMoves)
+        );
+    }
+
+    #[test]
+    fn move_until_no_points_left() {
+        let mut game = Game::new(5, 1);
+        assert!(game
+            .set_unit(0, 0, Some(Unit::new(0, 5, [1, 1], 3)))
+            .is_ok());
+        assert!(game.select_unit(Point { x: 0, y: 0 }).is_ok());
+        assert!(game.move_unit(Point { x: 1, y: 0 }).is_ok());
+        assert!(game.select_unit(Point { x: 1, y: 0 }).is_ok());
+
+        // Two points left are not enough for three hexes
+        assert!(game.move_unit(Point { x: 4, y: 0 }).is_err());
+        assert!(game.get_unit(1, 0).unwrap().is_some());
+
+        // Exactly the points left
+        assert!(game.move_unit(Point { x: 3, y: 0 }).is_ok());
+        let selection = game.select_unit(Point { x: 3, y: 0 }).unwrap();
+        assert_eq!(selection.target.unit.unwrap().movements, 0);
+        assert_eq!(selection.highlight_hexes, vec![Point { x: 3, y: 0 }]);
+        assert!(!game.can_move(&selection.target));
+
+        for to in [Point { x: 4, y: 0 }, Point { x: 2, y: 0 }].iter() {
+            let result = game.move_unit(*to);
+            assert_eq!(
+                result.unwrap_err().downcast_ref::<GameError>(),
+                Some(&GameError::NoMoves)
+            );
+        }
+        assert!(game.get_unit(3, 0).unwrap().is_some());
+    }
+
+    #[test]
+    fn move_unit_into_water() {
+        let mut game = terrain_game();
+        assert!(game
+            .set_unit(3, 0, Some(Unit::new(0, 5, [1, 1], 4)))
+            .is_ok());
+        let from = game.get_hex(3, 0).unwrap();
+
+        assert!(game.move_unit_internal(from, Point { x: 4, y: 0 }).is_err());
+        assert!(!game
+            .available_points(&Some(from))
+            .contains(&Point { x: 4, y: 0 }));
+    }
+
+    #[test]
+    fn terrain_defence() {
+        let mut game = Game::new(2, 1);
+        assert!(game
+            .set_unit(0, 0, Some(Unit::new(0, 5, [3, 3], 2)))
+            .is_ok());
+        assert!(game
+            .set_unit(1, 0, Some(Unit::new(1, 5, [1, 1], 2)))
+            .is_ok());
+        assert!(game.set_content(1, 0, Some(Content::Hill)).is_ok());
+
+        let from = game.get_hex(0, 0).unwrap();
+        let combat = game.attack_internal(from, Point { x: 1, y: 0 }).unwrap();
+        assert_eq!(combat.damage, 1);
+        assert_eq!(game.get_unit(1, 0).unwrap().unwrap().hp, 4);
+    }
+
     #[test]
     fn move_unit_out_from_field() {
         let (mut game, _, _) = test_game();
@@ -707,7 +1242,7 @@ mod test {
         let result = game.attack_internal(from, to);
 
         assert!(result.is_ok());
-        let (hurt, die) = result.unwrap();
+        let Combat { hurt, die, .. } = result.unwrap();
         assert_eq!(hurt.len(), 1);
         assert_eq!(die.len(), 0);
         assert_eq!(
@@ -734,7 +1269,7 @@ mod test {
         let from = game.get_hex(from.x, from.y).unwrap();
         let result = game.attack_internal(from, to);
         assert!(result.is_ok());
-        let (hurt, die) = result.unwrap();
+        let Combat { hurt, die, .. } = result.unwrap();
         assert_eq!(hurt.len(), 0);
         assert_eq!(die.len(), 1);
         assert_eq!(die[0].unit.as_ref().unwrap().hp, 0);
@@ -755,7 +1290,7 @@ mod test {
         let from = game.get_hex(from.x, from.y).unwrap();
         let result = game.attack_internal(from, to);
         assert!(result.is_ok());
-        let (hurt, die) = result.unwrap();
+        let Combat { hurt, die, .. } = result.unwrap();
         assert_eq!(hurt.len(), 0);
         assert_eq!(die.len(), 1);
         assert_eq!(die[0].unit.as_ref().unwrap().hp, 0);
@@ -1031,7 +1566,7 @@ mod test {
     fn available_points_unit_speed_greaer_then_field() {
         let game = test_big_game();
         let mut hex = game.get_hex(2, 9).unwrap();
-        let mut unit = hex.get_unit_mut().unwrap();
+        let unit = hex.get_unit_mut().unwrap();
         unit.movements = 1000;
 
         let points = game.available_points(&Some(hex));
@@ -1039,26 +1574,31 @@ mod test {
         assert_eq!(points.len(), 79);
     }
 
-    fn hexmap() -> HashMap<Point, u32> {
-        let mut hexmap: HashMap<Point, u32> = HashMap::new();
-
-        // path
-        hexmap.insert(Point { x: 0, y: 4 }, 0);
-        hexmap.insert(Point { x: 1, y: 5 }, 1);
-        hexmap.insert(Point { x: 1, y: 6 }, 2);
-        hexmap.insert(Point { x: 2, y: 6 }, 3);
-        hexmap.insert(Point { x: 3, y: 5 }, 4);
-        hexmap.insert(Point { x: 3, y: 4 }, 5);
-
-        // additional
-        hexmap.insert(Point { x: 1, y: 3 }, 1);
-        hexmap.insert(Point { x: 1, y: 4 }, 1);
-        hexmap.insert(Point { x: 0, y: 5 }, 1);
-        hexmap.insert(Point { x: 3, y: 7 }, 3);
-        hexmap.insert(Point { x: 1, y: 8 }, 4);
-        hexmap.insert(Point { x: 0, y: 8 }, 5);
-
-        hexmap
+    // Paths from (0, 4) to (3, 4) and some dead ends
+    fn paths() -> Paths {
+        let from = Point { x: 0, y: 4 };
+        let mut steps = HashMap::new();
+        let path = [(0, 4), (1, 5), (1, 6), (2, 6), (3, 5), (3, 4)];
+        for (i, pair) in path.windows(2).enumerate() {
+            let previous = Point {
+                x: pair[0].0,
+                y: pair[0].1,
+            };
+            let point = Point {
+                x: pair[1].0,
+                y: pair[1].1,
+            };
+            steps.insert(point, (i as u32 + 1, previous));
+        }
+        steps.insert(from, (0, from));
+        steps.insert(Point { x: 1, y: 3 }, (1, from));
+        steps.insert(Point { x: 0, y: 5 }, (1, from));
+
+        Paths {
+            from,
+            order: steps.keys().copied().collect(),
+            steps,
+        }
     }
 
     fn assert_are_neighbours(game: &Game, point1: &Point, point2: &Point) {
@@ -1069,123 +1609,385 @@ mod test {
             .any(|point| point == *point2))
     }
 
-    #[test]
-    fn restore_path_from_hexmap_correct_path() {
-        let map = hexmap();
-        let game = test_big_game();
-        let start_point = Point { x: 0, y: 4 };
-        let end_point = Point { x: 3, y: 4 };
+    fn assert_no_hex<T: std::fmt::Debug>(result: Result<T>) {
+        assert!(result.is_err());
+        assert_eq!(
+            GameError::NoHex,
+            *result.unwrap_err().downcast_ref::<GameError>().unwrap()
+        );
+    }
 
-        let path = game.restore_path_from_hexmap(start_point, end_point, &map);
-        assert!(path.is_ok());
-        let path = path.unwrap();
+    #[test]
+    fn path_from_paths() {
+        let path = paths().path(Point { x: 3, y: 4 }).unwrap();
         assert_eq!(path.len(), 6);
+        assert_eq!(*path.first().unwrap(), Point { x: 0, y: 4 });
+        assert_eq!(*path.last().unwrap(), Point { x: 3, y: 4 });
+    }
 
-        // check if previouse point in result is neighbour of current
-        for (i, point) in path.iter().enumerate() {
-            if i == 0 {
-                // skip first one because it has no previous
-                continue;
-            }
+    #[test]
+    fn path_to_unreachable_hex() {
+        assert_no_hex(paths().path(Point { x: 5, y: 5 }));
+    }
+
+    #[test]
+    fn path_discontinuous() {
+        let mut paths = paths();
+        paths.steps.remove(&Point { x: 3, y: 5 });
+        assert_no_hex(paths.path(Point { x: 3, y: 4 }));
+    }
 
-            assert_are_neighbours(&game, point, &path[i - 1]);
+    #[test]
+    fn path_from_equals_to() {
+        let path = paths().path(Point { x: 0, y: 4 }).unwrap();
+        assert_eq!(path, vec![Point { x: 0, y: 4 }]);
+    }
+
+    #[test]
+    fn find_paths_goes_around_obstacles() {
+        let mut game = test_big_game();
+        let from = Point { x: 0, y: 4 };
+        let to = Point { x: 3, y: 4 };
+        for wall in &[(1, 4), (2, 4), (1, 3), (2, 3)] {
+            assert!(game
+                .set_content(wall.0, wall.1, Some(Content::Wall(Wall {})))
+                .is_ok());
+        }
+
+        let paths = game.find_paths(&game.get_hex(from.x, from.y).unwrap(), 10);
+        let path = paths.path(to).unwrap();
+        for pair in path.windows(2) {
+            assert_are_neighbours(&game, &pair[0], &pair[1]);
+            assert!(game
+                .get_hex(pair[1].x, pair[1].y)
+                .unwrap()
+                .content
+                .is_none());
         }
+        assert_eq!(paths.cost(&to), Some(path.len() as u32 - 1));
+        assert_eq!(*path.first().unwrap(), from);
+        assert_eq!(*path.last().unwrap(), to);
 
-        assert_eq!(*path.first().unwrap(), start_point);
-        assert_eq!(*path.last().unwrap(), end_point);
+        // Every point is settled once, the cheapest first
+        let points = paths.points();
+        let costs: Vec<u32> = points
+            .iter()
+            .map(|point| paths.cost(point).unwrap())
+            .collect();
+        assert!(costs.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(points.len(), paths.steps.len());
     }
 
     #[test]
-    fn restore_path_from_hexmap_no_start_hex() {
-        let mut map = hexmap();
-        let game = test_big_game();
-        let start_point = Point { x: 0, y: 4 };
-        let end_point = Point { x: 3, y: 4 };
+    fn attack_unit_far_away() {
+        let mut game = test_big_game();
+        let from = Point { x: 0, y: 0 };
+        let to = Point { x: 4, y: 2 };
+        let from = game.get_hex(from.x, from.y).unwrap();
+        let result = game.attack_internal(from, to);
 
-        map.remove(&start_point);
+        assert!(result.is_err());
+        match result.unwrap_err().downcast_ref::<GameError>() {
+            Some(GameError::WrongHex) => {}
+            _ => unreachable!("wrong error type"),
+        }
+    }
 
-        let path = game.restore_path_from_hexmap(start_point, end_point, &map);
-        assert!(path.is_err());
+    // Archer of player 0 in the corner of 6x3 field, enemies in range 3
+    fn ranged_game() -> Game {
+        let mut game = Game::new(6, 3);
+        let archer = Unit {
+            range: 3,
+            ..Unit::new(0, 5, [1, 1], 2)
+        };
+        assert!(game.set_unit(0, 0, Some(archer)).is_ok());
+        assert!(game
+            .set_unit(3, 0, Some(Unit::new(1, 5, [1, 1], 2)))
+            .is_ok());
+        assert!(game
+            .set_unit(4, 0, Some(Unit::new(1, 5, [1, 1], 2)))
+            .is_ok());
+        game
+    }
+
+    fn attack_error(game: &mut Game, from: Point, to: Point) -> Option<GameError> {
+        let from = game.get_hex(from.x, from.y).unwrap();
+        match game.attack_internal(from, to) {
+            Ok(_) => None,
+            Err(error) => error.downcast_ref::<GameError>().cloned(),
+        }
+    }
+
+    #[test]
+    fn ranged_attack_in_range() {
+        let mut game = ranged_game();
+        let archer = Point { x: 0, y: 0 };
+
+        assert_eq!(attack_error(&mut game, archer, Point { x: 3, y: 0 }), None);
+        assert_eq!(game.get_unit(3, 0).unwrap().unwrap().hp, 4);
         assert_eq!(
-            GameError::NoHex,
-            *path.unwrap_err().downcast_ref::<GameError>().unwrap()
+            attack_error(&mut game, archer, Point { x: 4, y: 0 }),
+            Some(GameError::WrongHex)
+        );
+        assert_eq!(
+            game.attack_points(&game.get_hex(0, 0)),
+            vec![Point { x: 3, y: 0 }]
         );
     }
 
     #[test]
-    fn restore_path_from_hexmap_no_finish_hex() {
-        let mut map = hexmap();
-        let game = test_big_game();
-        let start_point = Point { x: 0, y: 4 };
-        let end_point = Point { x: 3, y: 4 };
-
-        map.remove(&end_point);
+    fn ranged_attack_blocked() {
+        let mut game = ranged_game();
+        let archer = Point { x: 0, y: 0 };
+        assert!(game.set_content(1, 0, Some(Content::Wall(Wall {}))).is_ok());
+        assert_eq!(
+            attack_error(&mut game, archer, Point { x: 3, y: 0 }),
+            Some(GameError::Blocked)
+        );
 
-        let path = game.restore_path_from_hexmap(start_point, end_point, &map);
-        assert!(path.is_err());
+        // Units block shots too, even own ones
+        let mut game = ranged_game();
+        assert!(game
+            .set_unit(2, 0, Some(Unit::new(0, 5, [1, 1], 2)))
+            .is_ok());
         assert_eq!(
-            GameError::NoHex,
-            *path.unwrap_err().downcast_ref::<GameError>().unwrap()
+            attack_error(&mut game, archer, Point { x: 3, y: 0 }),
+            Some(GameError::Blocked)
         );
+        assert!(game.attack_points(&game.get_hex(0, 0)).is_empty());
     }
 
     #[test]
-    fn restore_path_from_hexmap_empty_hexmap() {
-        let map: HashMap<Point, u32> = HashMap::new();
-        let game = test_big_game();
-        let start_point = Point { x: 0, y: 4 };
-        let end_point = Point { x: 3, y: 4 };
+    fn ranged_attack_at_the_edge() {
+        let mut game = ranged_game();
+        assert!(game
+            .set_unit(0, 2, Some(Unit::new(1, 5, [1, 1], 2)))
+            .is_ok());
 
-        let path = game.restore_path_from_hexmap(start_point, end_point, &map);
-        assert!(path.is_err());
+        // Corner to corner along the edge of the grid
         assert_eq!(
-            GameError::NoHex,
-            *path.unwrap_err().downcast_ref::<GameError>().unwrap()
+            attack_error(&mut game, Point { x: 0, y: 0 }, Point { x: 0, y: 2 }),
+            None
+        );
+        assert_eq!(
+            attack_error(&mut game, Point { x: 0, y: 0 }, Point { x: 0, y: 3 }),
+            Some(GameError::NoHex)
         );
     }
 
     #[test]
-    fn restore_path_from_hexmap_discontinuous() {
-        let mut map = hexmap();
-        let game = test_big_game();
-        let start_point = Point { x: 0, y: 4 };
-        let end_point = Point { x: 3, y: 4 };
+    fn targets_after_move() {
+        let mut game = ranged_game();
+        let unit = game.get_unit(0, 0).unwrap();
+        assert!(game.set_unit(0, 0, None).is_ok());
+        assert!(game.set_unit(1, 0, unit).is_ok());
+        let archer = game.get_hex(1, 0).unwrap();
+
+        // Hex the archer leaves doesn't block shots from the new place
+        let targets: Vec<Point> = game
+            .targets(&archer, Point { x: 0, y: 0 })
+            .iter()
+            .map(Hex::to_point)
+            .collect();
+        assert_eq!(targets, vec![Point { x: 3, y: 0 }]);
+    }
+
+    // Attacker of player 0 and defender of player 1 side by side, defender strikes back
+    fn retaliation_game(defender: Unit) -> Game {
+        let mut game = Game::new(4, 1);
+        game.retaliation = true;
+        assert!(game
+            .set_unit(0, 0, Some(Unit::new(0, 5, [1, 1], 2)))
+            .is_ok());
+        assert!(game.set_unit(1, 0, Some(defender)).is_ok());
+        game
+    }
+
+    fn strike(game: &mut Game, from: Point, to: Point) -> Combat {
+        let from = game.get_hex(from.x, from.y).unwrap();
+        game.attack_internal(from, to).unwrap()
+    }
+
+    #[test]
+    fn melee_defender_strikes_back_once() {
+        let mut game = retaliation_game(Unit::new(1, 5, [2, 2], 2));
+        let attacker = Point { x: 0, y: 0 };
+        let defender = Point { x: 1, y: 0 };
+
+        let combat = strike(&mut game, attacker, defender);
+        assert_eq!(combat.damage, 1);
+        assert_eq!(combat.retaliation, Some(2));
+        let hurt: Vec<Point> = combat.hurt.iter().map(Hex::to_point).collect();
+        assert_eq!(hurt, vec![defender, attacker]);
+        assert_eq!(game.get_unit(0, 0).unwrap().unwrap().hp, 3);
+
+        // Only once per round
+        let combat = strike(&mut game, attacker, defender);
+        assert_eq!(combat.retaliation, None);
+        assert_eq!(game.get_unit(0, 0).unwrap().unwrap().hp, 3);
+
+        game.restore_movements(1);
+        let combat = strike(&mut game, attacker, defender);
+        assert_eq!(combat.retaliation, Some(2));
+        assert_eq!(game.get_unit(0, 0).unwrap().unwrap().hp, 1);
+    }
+
+    #[test]
+    fn attacker_dies_from_retaliation() {
+        let mut game = retaliation_game(Unit::new(1, 5, [9, 9], 2));
+        let combat = strike(&mut game, Point { x: 0, y: 0 }, Point { x: 1, y: 0 });
+
+        assert_eq!(combat.hurt.len(), 1);
+        assert_eq!(combat.die.len(), 1);
+        assert_eq!(combat.die[0].to_point(), Point { x: 0, y: 0 });
+        assert!(game.get_unit(0, 0).unwrap().is_none());
+    }
+
+    #[test]
+    fn no_retaliation() {
+        // Rule is off
+        let mut game = retaliation_game(Unit::new(1, 5, [2, 2], 2));
+        game.retaliation = false;
+        let combat = strike(&mut game, Point { x: 0, y: 0 }, Point { x: 1, y: 0 });
+        assert_eq!(combat.retaliation, None);
+        assert_eq!(combat.hurt.len(), 1);
+
+        // Ranged defender doesn't strike back
+        let archer = Unit {
+            range: 3,
+            ..Unit::new(1, 5, [2, 2], 2)
+        };
+        let mut game = retaliation_game(archer);
+        let combat = strike(&mut game, Point { x: 0, y: 0 }, Point { x: 1, y: 0 });
+        assert_eq!(combat.retaliation, None);
+
+        // Melee defender can't reach the shooter
+        let mut game = retaliation_game(Unit::new(1, 5, [2, 2], 2));
+        let shooter = Unit {
+            range: 3,
+            ..Unit::new(0, 5, [1, 1], 2)
+        };
+        assert!(game.set_unit(3, 0, Some(shooter)).is_ok());
+        let combat = strike(&mut game, Point { x: 3, y: 0 }, Point { x: 1, y: 0 });
+        assert_eq!(combat.retaliation, None);
+        assert_eq!(game.get_unit(3, 0).unwrap().unwrap().hp, 5);
+
+        // Dead defender doesn't strike back
+        let mut game = retaliation_game(Unit::new(1, 1, [2, 2], 2));
+        let combat = strike(&mut game, Point { x: 0, y: 0 }, Point { x: 1, y: 0 });
+        assert_eq!(combat.retaliation, None);
+        assert_eq!(combat.die.len(), 1);
+    }
+
+    #[test]
+    fn remove_player() {
+        let (mut game, unit, _) = test_game();
+        let enemy = Unit::new(2, 5, [5, 5], 3);
+        assert!(game.set_unit(0, 1, Some(enemy)).is_ok());
+        assert!(game.select_unit(Point { x: 0, y: 0 }).is_ok());
+
+        let removed = game.remove_player(unit.player);
+        assert_eq!(removed.len(), 2);
+        assert!(removed
+            .iter()
+            .all(|hex| hex.unit.unwrap().player == unit.player));
+        assert!(game.selected_hex.is_none());
+        assert!(game.ends());
+        assert_eq!(game.get_unit(0, 1).unwrap().unwrap().player, enemy.player);
+    }
+
+    #[test]
+    fn archer_does_not_attack_after_move() {
+        let mut game = ranged_game();
+        let archer = Unit {
+            kind: Kind::Archer,
+            ..game.get_unit(0, 0).unwrap().unwrap()
+        };
+        assert!(game.set_unit(0, 0, Some(archer)).is_ok());
+        let hex = game.get_hex(0, 0).unwrap();
 
-        map.remove(&Point { x: 3, y: 5 });
+        // Target would be in range after the step
+        assert!(game.targets(&hex, Point { x: 1, y: 0 }).is_empty());
 
-        let path = game.restore_path_from_hexmap(start_point, end_point, &map);
-        assert!(path.is_err());
+        assert!(game.move_unit_internal(hex, Point { x: 1, y: 0 }).is_ok());
+        assert!(game.attack_points(&game.get_hex(1, 0)).is_empty());
         assert_eq!(
-            GameError::NoHex,
-            *path.unwrap_err().downcast_ref::<GameError>().unwrap()
+            attack_error(&mut game, Point { x: 1, y: 0 }, Point { x: 3, y: 0 }),
+            Some(GameError::AlreadyMoved)
         );
     }
 
     #[test]
-    fn restore_path_from_hexmap_from_equals_to() {
-        let map = hexmap();
-        let game = test_big_game();
-        let start_point = Point { x: 0, y: 4 };
+    fn healer_heals_neighbours() {
+        let mut game = Game::new(4, 1);
+        let healer = Unit {
+            kind: Kind::Healer,
+            range: 0,
+            ..Unit::new(0, 3, [0, 0], 2)
+        };
+        let mut wounded = Unit::new(0, 5, [1, 1], 2);
+        wounded.change_hp(-4);
+        let enemy = Unit {
+            hp: 1,
+            ..Unit::new(1, 5, [1, 1], 2)
+        };
+        assert!(game.set_unit(0, 0, Some(wounded)).is_ok());
+        assert!(game.set_unit(1, 0, Some(healer)).is_ok());
+        assert!(game.set_unit(2, 0, Some(enemy)).is_ok());
 
-        let path = game.restore_path_from_hexmap(start_point, start_point, &map);
-        assert!(path.is_ok());
-        let path = path.unwrap();
-        assert_eq!(path.len(), 1);
-        assert_eq!(path[0], start_point);
+        let changed: Vec<Point> = game
+            .restore_movements(0)
+            .iter()
+            .map(Hex::to_point)
+            .collect();
+        assert_eq!(changed, vec![Point { x: 0, y: 0 }]);
+        assert_eq!(game.get_unit(0, 0).unwrap().unwrap().hp, 3);
+        assert_eq!(game.get_unit(2, 0).unwrap().unwrap().hp, 1);
+
+        // Not above the maximum
+        game.restore_movements(0);
+        game.restore_movements(0);
+        assert_eq!(game.get_unit(0, 0).unwrap().unwrap().hp, 5);
+
+        // Healer can't attack
+        assert_eq!(
+            attack_error(&mut game, Point { x: 1, y: 0 }, Point { x: 2, y: 0 }),
+            Some(GameError::WrongHex)
+        );
     }
 
     #[test]
-    fn attack_unit_far_away() {
-        let mut game = test_big_game();
-        let from = Point { x: 0, y: 0 };
-        let to = Point { x: 4, y: 2 };
-        let from = game.get_hex(from.x, from.y).unwrap();
-        let result = game.attack_internal(from, to);
+    fn random_game_with_army() {
+        let settings = Settings {
+            army: vec![
+                Squad {
+                    kind: Kind::Archer,
+                    count: (1, 3),
+                },
+                Squad {
+                    kind: Kind::Cavalry,
+                    count: (2, 2),
+                },
+            ],
+            ..Settings::default()
+        };
+        let game = Game::random(2, 7, settings).unwrap();
 
-        assert!(result.is_err());
-        match result.unwrap_err().downcast_ref::<GameError>() {
-            Some(GameError::WrongHex) => {}
-            _ => unreachable!("wrong error type"),
+        let units: Vec<Unit> = game.field.hexes.iter().filter_map(|hex| hex.unit).collect();
+        for player in 0..2 {
+            let kinds = |kind| {
+                units
+                    .iter()
+                    .filter(|unit| unit.player == player && unit.kind == kind)
+                    .count()
+            };
+            assert_eq!(kinds(Kind::Cavalry), 2);
+            assert!(kinds(Kind::Archer) >= 1 && kinds(Kind::Archer) <= 3);
+            assert_eq!(kinds(Kind::Infantry), 0);
         }
+        assert!(units
+            .iter()
+            .filter(|unit| unit.kind == Kind::Archer)
+            .all(|unit| unit.range == Kind::Archer.stats().range));
     }
 }
@@ -1,14 +1,72 @@
 use super::hex::Hex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::convert::TryFrom;
+use thiserror::Error;
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Error, Debug, PartialEq)]
+pub enum GridError {
+    #[error("{num_x}x{num_y} grid can not have {hexes} hexes")]
+    WrongSize {
+        num_x: u32,
+        num_y: u32,
+        hexes: usize,
+    },
+
+    #[error("hex ({x}, {y}) is out of grid or repeated")]
+    WrongHex { x: u32, y: u32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "GridData")]
 pub struct Grid {
     pub num_x: u32,
     pub num_y: u32,
     pub hexes: Vec<Hex>,
 }
 
+// Grid as it comes from json, hexes may go in any order
+#[derive(Deserialize)]
+struct GridData {
+    num_x: u32,
+    num_y: u32,
+    hexes: Vec<Hex>,
+}
+
+impl TryFrom<GridData> for Grid {
+    type Error = GridError;
+
+    // Put hexes in the order index() expects, every hex of the grid must be there once
+    fn try_from(data: GridData) -> Result<Self, Self::Error> {
+        let GridData {
+            num_x,
+            num_y,
+            mut hexes,
+        } = data;
+        if hexes.len() != num_x as usize * num_y as usize {
+            return Err(GridError::WrongSize {
+                num_x,
+                num_y,
+                hexes: hexes.len(),
+            });
+        }
+
+        hexes.sort_by_key(|hex| (hex.x, hex.y));
+        let grid = Grid {
+            num_x,
+            num_y,
+            hexes,
+        };
+        for (index, hex) in grid.hexes.iter().enumerate() {
+            if grid.index(hex.x, hex.y) != Some(index) {
+                return Err(GridError::WrongHex { x: hex.x, y: hex.y });
+            }
+        }
+
+        Ok(grid)
+    }
+}
+
 impl Grid {
     pub fn new(num_x: u32, num_y: u32) -> Grid {
         let hexes: Vec<Hex> = (0..num_x)
@@ -29,17 +87,28 @@ impl Grid {
         }
     }
 
+    // Position of the hex in hexes, they go column by column
+    fn index(&self, x: u32, y: u32) -> Option<usize> {
+        if x >= self.num_x || y >= self.num_y {
+            return None;
+        }
+        Some((x * self.num_y + y) as usize)
+    }
+
     // get hex with further change
     pub fn get_hex_mut(&mut self, x: u32, y: u32) -> Option<&mut Hex> {
-        self.hexes.iter_mut().find(|hex| hex.x == x && hex.y == y)
+        let index = self.index(x, y)?;
+        let hex = self.hexes.get_mut(index)?;
+        debug_assert_eq!((hex.x, hex.y), (x, y), "hexes are out of order");
+        Some(hex)
     }
 
     // get hex with further change
     pub fn get_hex(&self, x: u32, y: u32) -> Option<Hex> {
-        match self.hexes.iter().find(|hex| hex.x == x && hex.y == y) {
-            None => None,
-            Some(hex) => Some(*hex),
-        }
+        let index = self.index(x, y)?;
+        let hex = self.hexes.get(index).copied()?;
+        debug_assert_eq!((hex.x, hex.y), (x, y), "hexes are out of order");
+        Some(hex)
     }
 
     // get number of players on the field
@@ -102,6 +171,19 @@ mod test {
         assert!(hex.is_none());
     }
 
+    #[test]
+    fn get_hex_by_index() {
+        let grid = Grid::new(4, 7);
+        for x in 0..4 {
+            for y in 0..7 {
+                let hex = grid.get_hex(x, y).unwrap();
+                assert_eq!((hex.x, hex.y), (x, y));
+            }
+        }
+        assert!(grid.get_hex(4, 0).is_none());
+        assert!(grid.get_hex(0, 7).is_none());
+    }
+
     #[test]
     fn serialize() {
         let unit = Unit::new(1, 1, [120, 130], 1);
@@ -134,4 +216,47 @@ mod test {
             ),
         );
     }
+
+    #[test]
+    fn deserialize() {
+        let mut grid = Grid::new(3, 2);
+        let hex = grid.get_hex_mut(2, 1).unwrap();
+        hex.set_unit(Some(Unit::new(1, 1, [1, 2], 1)));
+        hex.set_content(Some(Content::Wall(Wall {})));
+
+        let grid_string = serde_json::to_string(&grid).unwrap();
+        let restored: Grid = serde_json::from_str(&grid_string).unwrap();
+
+        assert_eq!(restored.num_x, 3);
+        assert_eq!(restored.num_y, 2);
+        assert_eq!(restored.hexes.len(), 6);
+        assert_eq!(serde_json::to_string(&restored).unwrap(), grid_string);
+    }
+
+    #[test]
+    fn deserialize_shuffled() {
+        let mut grid = Grid::new(3, 2);
+        grid.get_hex_mut(2, 1)
+            .unwrap()
+            .set_content(Some(Content::Wall(Wall {})));
+        let grid_string = serde_json::to_string(&grid).unwrap();
+
+        grid.hexes.reverse();
+        let restored: Grid = serde_json::from_str(&serde_json::to_string(&grid).unwrap()).unwrap();
+
+        assert_eq!(serde_json::to_string(&restored).unwrap(), grid_string);
+        assert!(restored.get_hex(2, 1).unwrap().content.is_some());
+    }
+
+    #[test]
+    fn deserialize_wrong_hexes() {
+        let mut grid = Grid::new(3, 2);
+        grid.hexes.pop();
+        let error = serde_json::from_str::<Grid>(&serde_json::to_string(&grid).unwrap());
+        assert!(error.is_err());
+
+        grid.hexes.push(grid.hexes[0]);
+        let error = serde_json::from_str::<Grid>(&serde_json::to_string(&grid).unwrap());
+        assert!(error.unwrap_err().to_string().contains("repeated"));
+    }
 }
@@ -1,9 +1,9 @@
 use super::hex_objects::content::Content;
 use super::unit::Unit;
 use crate::api::common::Point;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Debug, Copy)]
+#[derive(Clone, Serialize, Deserialize, Debug, Copy)]
 pub struct Hex {
     pub x: u32,
     pub y: u32,
@@ -44,6 +44,26 @@ impl Hex {
         self.content.is_none() && self.unit.is_none()
     }
 
+    // Movement points to enter the hex, the open field costs 1
+    pub fn move_cost(&self) -> Option<u32> {
+        match self.get_content() {
+            Some(content) => content.move_cost(),
+            None => Some(1),
+        }
+    }
+
+    pub fn defence(&self) -> u32 {
+        match self.get_content() {
+            Some(content) => content.defence(),
+            None => 0,
+        }
+    }
+
+    // Unit can be put here
+    pub fn is_free(&self) -> bool {
+        self.unit.is_none() && self.move_cost().is_some()
+    }
+
     pub fn to_point(&self) -> Point {
         Point {
             x: self.x,
@@ -94,4 +114,31 @@ mod test {
             ),
         );
     }
+
+    #[test]
+    fn deserialize_without_anything() {
+        let hex: Hex = serde_json::from_str("{\"x\":1,\"y\":2}").unwrap();
+
+        assert_eq!(hex.x, 1);
+        assert_eq!(hex.y, 2);
+        assert!(hex.unit.is_none());
+        assert!(hex.content.is_none());
+    }
+
+    #[test]
+    fn deserialize_with_unit_and_content_wall() {
+        let hex = Hex {
+            x: 1,
+            y: 2,
+            unit: Some(Unit::new(1, 10, [2, 4], 4)),
+            content: Some(Content::Wall(Wall {})),
+        };
+        let hex_string = serde_json::to_string(&hex).unwrap();
+        let restored: Hex = serde_json::from_str(&hex_string).unwrap();
+
+        assert_eq!(restored, hex);
+        assert_eq!(restored.unit.unwrap().hp, 10);
+        assert_eq!(restored.unit.unwrap().damage, [2, 4]);
+        assert!(restored.content.is_some());
+    }
 }
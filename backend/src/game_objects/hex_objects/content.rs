@@ -1,11 +1,50 @@
 use super::wall::Wall;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Debug, Copy)]
+#[derive(Clone, Serialize, Deserialize, Debug, Copy)]
 #[serde(tag = "type")]
 pub enum Content {
     #[serde(rename = "wall")]
     Wall(Wall),
+    #[serde(rename = "forest")]
+    Forest,
+    #[serde(rename = "swamp")]
+    Swamp,
+    #[serde(rename = "road")]
+    Road,
+    #[serde(rename = "water")]
+    Water,
+    #[serde(rename = "hill")]
+    Hill,
+}
+
+// Terrain the random generator sprinkles over the field, units can stand on all of it
+pub const PASSABLE_TERRAIN: [Content; 4] = [
+    Content::Forest,
+    Content::Swamp,
+    Content::Road,
+    Content::Hill,
+];
+
+impl Content {
+    // Movement points to enter the hex, none if units can't go there
+    pub fn move_cost(&self) -> Option<u32> {
+        match self {
+            Content::Wall(_) | Content::Water => None,
+            Content::Road => Some(1),
+            Content::Forest | Content::Hill => Some(2),
+            Content::Swamp => Some(3),
+        }
+    }
+
+    // Damage dealt to the unit standing here is reduced by it
+    pub fn defence(&self) -> u32 {
+        match self {
+            Content::Forest => 1,
+            Content::Hill => 2,
+            _ => 0,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -21,4 +60,24 @@ mod test {
         let content_string = serde_json::to_string(&content).unwrap();
         assert_eq!(content_string, "{\"type\":\"wall\"}");
     }
+
+    #[test]
+    fn deserialize() {
+        let content: Content = serde_json::from_str("{\"type\":\"wall\"}").unwrap();
+        assert!(matches!(content, Content::Wall(_)));
+    }
+
+    #[test]
+    fn terrain() {
+        let content: Content = serde_json::from_str("{\"type\":\"swamp\"}").unwrap();
+        assert!(matches!(content, Content::Swamp));
+        assert_eq!(
+            serde_json::to_string(&Content::Forest).unwrap(),
+            "{\"type\":\"forest\"}"
+        );
+
+        assert_eq!(content.move_cost(), Some(3));
+        assert_eq!(Content::Water.move_cost(), None);
+        assert_eq!(Content::Hill.defence(), 2);
+    }
 }
@@ -1,4 +1,4 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Debug, Copy)]
+#[derive(Clone, Serialize, Deserialize, Debug, Copy)]
 pub struct Wall {}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+// Units of the saved games and presets without kind are infantry
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    #[default]
+    Infantry,
+    Archer,
+    Cavalry,
+    Healer,
+}
+
+// Stats of the kind from the units.json catalogue, ranges are [min, max]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct KindStats {
+    pub kind: Kind,
+    pub hp: (u8, u8),
+    pub min_damage: (u8, u8),
+    pub damage_interval: (u8, u8),
+    pub speed: (u8, u8),
+    pub sight: u32,
+    pub range: u32,
+    // Unit can attack in the turn it has moved
+    pub attack_after_move: bool,
+    // Hp restored to neighbour allies at the end of the player's turn
+    pub heal: u32,
+    // Most units of the kind in one army, none for no limit
+    #[serde(default)]
+    pub max_per_army: Option<u8>,
+}
+
+lazy_static! {
+    static ref CATALOGUE: Vec<KindStats> =
+        serde_json::from_str(include_str!("../../units.json")).expect("parse units.json");
+}
+
+impl Kind {
+    pub fn stats(self) -> &'static KindStats {
+        CATALOGUE
+            .iter()
+            .find(|stats| stats.kind == self)
+            .unwrap_or_else(|| panic!("no {:?} in units.json", self))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn catalogue_has_every_kind() {
+        for kind in &[Kind::Infantry, Kind::Archer, Kind::Cavalry, Kind::Healer] {
+            let stats = kind.stats();
+            assert!(stats.hp.0 > 0 && stats.hp.0 <= stats.hp.1, "{:?}", kind);
+            assert!(stats.min_damage.0 <= stats.min_damage.1, "{:?}", kind);
+            assert!(
+                stats.damage_interval.0 <= stats.damage_interval.1,
+                "{:?}",
+                kind
+            );
+            assert!(stats.speed.0 <= stats.speed.1, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn serialize_kind() {
+        assert_eq!(
+            serde_json::to_string(&Kind::Cavalry).unwrap(),
+            "\"cavalry\""
+        );
+        assert_eq!(
+            serde_json::from_str::<Kind>("\"healer\"").unwrap(),
+            Kind::Healer
+        );
+    }
+}
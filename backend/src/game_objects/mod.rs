@@ -1,4 +1,5 @@
 pub mod grid;
 pub mod hex;
 pub mod hex_objects;
+pub mod kind;
 pub mod unit;
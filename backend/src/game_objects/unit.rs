@@ -1,24 +1,59 @@
 use rand::Rng;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Debug, Copy)]
+use crate::game_objects::kind::Kind;
+use crate::settings::roll_range;
+
+// Distance unit sees at in the fog of war
+pub const DEFAULT_SIGHT: u32 = 4;
+
+pub fn default_sight() -> u32 {
+    DEFAULT_SIGHT
+}
+
+// Melee units attack only neighbours
+pub const DEFAULT_RANGE: u32 = 1;
+
+pub fn default_range() -> u32 {
+    DEFAULT_RANGE
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Copy, PartialEq)]
 pub struct Unit {
+    #[serde(default)]
+    pub kind: Kind,
     pub player: u32,
     pub hp: u32,
+    // Healing doesn't restore hp above it, zero for units saved before healing
+    #[serde(default)]
+    pub max_hp: u32,
     pub damage: [u32; 2],
     pub speed: u32,
     pub movements: u32,
+    #[serde(default = "default_sight")]
+    pub sight: u32,
+    // Distance of the attack, line of fire must be clear for ranged attacks
+    #[serde(default = "default_range")]
+    pub range: u32,
+    // Unit has struck back since the last turn of its player
+    #[serde(default)]
+    pub retaliated: bool,
 }
 
 impl Unit {
     pub fn new(player: u32, hp: u32, damage: [u32; 2], speed: u32) -> Unit {
         assert!(damage[0] <= damage[1]);
         Unit {
+            kind: Kind::Infantry,
             player,
             hp,
+            max_hp: hp,
             damage,
             speed,
             movements: speed,
+            sight: DEFAULT_SIGHT,
+            range: DEFAULT_RANGE,
+            retaliated: false,
         }
     }
 
@@ -28,18 +63,35 @@ impl Unit {
         damage_interval: (u8, u8),
         speed_min_max: (u8, u8),
         player: u32,
+        rng: &mut impl Rng,
     ) -> Unit {
-        let mut rng = rand::thread_rng();
-
-        let hp = rng.gen_range(hp_min_max.0, hp_min_max.1 + 1) as u32;
-        let damage_min = rng.gen_range(damage_min_max.0, damage_min_max.1) as u32;
-        let damage_max =
-            damage_min + rng.gen_range(damage_interval.0, damage_interval.1 + 1) as u32;
-        let speed = rng.gen_range(speed_min_max.0, speed_min_max.1 + 1) as u32;
+        let hp = roll_range(rng, hp_min_max) as u32;
+        let damage_min = roll_range(rng, damage_min_max) as u32;
+        let damage_max = damage_min + roll_range(rng, damage_interval) as u32;
+        let speed = roll_range(rng, speed_min_max) as u32;
 
         Unit::new(player, hp, [damage_min, damage_max], speed)
     }
 
+    // Unit with stats rolled from the catalogue ranges of the kind
+    pub fn of_kind(kind: Kind, player: u32, rng: &mut impl Rng) -> Unit {
+        let stats = kind.stats();
+        let unit = Unit::random(
+            stats.hp,
+            stats.min_damage,
+            stats.damage_interval,
+            stats.speed,
+            player,
+            rng,
+        );
+        Unit {
+            kind,
+            sight: stats.sight,
+            range: stats.range,
+            ..unit
+        }
+    }
+
     pub fn change_hp(&mut self, diff: i32) {
         let hp = (self.hp as i32) + diff;
         self.hp = if hp >= 0 { hp as u32 } else { 0 };
@@ -55,6 +107,14 @@ impl Unit {
         self.movements = self.speed;
     }
 
+    // Restore hp up to the maximum, return whether anything has changed
+    pub fn heal(&mut self, hp: u32) -> bool {
+        let healed = (self.hp + hp).min(self.max_hp.max(self.hp));
+        let changed = healed != self.hp;
+        self.hp = healed;
+        changed
+    }
+
     pub fn has_moved(self) -> bool {
         self.movements != self.speed
     }
@@ -62,7 +122,65 @@ impl Unit {
     pub fn is_my(self, player: u32) -> bool {
         self.player == player
     }
+
+    // Some kinds can't attack after moving
+    pub fn can_attack(self) -> bool {
+        !self.has_moved() || self.kind.stats().attack_after_move
+    }
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn of_kind_uses_catalogue() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let unit = Unit::of_kind(Kind::Cavalry, 1, &mut rng);
+            let stats = Kind::Cavalry.stats();
+            assert_eq!(unit.kind, Kind::Cavalry);
+            assert_eq!(unit.range, stats.range);
+            assert!(unit.speed >= stats.speed.0 as u32 && unit.speed <= stats.speed.1 as u32);
+            assert_eq!(unit.max_hp, unit.hp);
+        }
+    }
+
+    #[test]
+    fn heal_up_to_max_hp() {
+        let mut unit = Unit::new(0, 5, [1, 2], 2);
+        unit.change_hp(-3);
+        assert!(unit.heal(2));
+        assert_eq!(unit.hp, 4);
+        assert!(unit.heal(2));
+        assert_eq!(unit.hp, 5);
+        assert!(!unit.heal(2));
+    }
+
+    #[test]
+    fn archer_attacks_only_before_moving() {
+        let mut archer = Unit {
+            kind: Kind::Archer,
+            ..Unit::new(0, 5, [1, 2], 2)
+        };
+        let mut infantry = Unit::new(0, 5, [1, 2], 2);
+        assert!(archer.can_attack());
+
+        archer.change_movements(1);
+        infantry.change_movements(1);
+        assert!(!archer.can_attack());
+        assert!(infantry.can_attack());
+    }
+
+    #[test]
+    fn deserialize_without_kind() {
+        let unit: Unit = serde_json::from_str(
+            "{\"player\":1,\"hp\":5,\"damage\":[1,2],\"speed\":2,\"movements\":2}",
+        )
+        .unwrap();
+        assert_eq!(unit.kind, Kind::Infantry);
+        assert_eq!(unit.max_hp, 0);
+    }
+}
@@ -1,20 +1,37 @@
 // TODO: Refactor it
-use actix::{Actor, Addr, Context, Handler};
+use actix::{Actor, ActorContext, Addr, AsyncContext, Context, Handler, SpawnHandle};
 
+use chrono::Utc;
 use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
 
+use crate::bot::{Bot, BotError};
+use crate::clock::{Clock, TimerError, TimerSettings};
 use crate::communicator;
+use crate::config::CONFIG;
+use crate::database::Storage;
+use crate::lobby::Lobby;
+use crate::models::match_result::{self, MatchResult};
+use crate::preset::{Preset, PresetError};
+use crate::replay::{Event, Replay};
+use crate::settings::SettingsError;
+use crate::sight::View;
+use crate::snapshot::Snapshot;
 use crate::websocket::Websocket;
 
 use crate::api::common::Point;
 use crate::api::inner;
-use crate::api::request;
-use crate::api::request::{Click, SkipTurn, StartGame};
+use crate::api::request::{
+    AcceptDraw, ChooseView, Click, OfferDraw, Rematch, SkipTurn, StartGame, Surrender,
+};
 use crate::api::response::{
-    Attacking, ConnectionQueue, Deselecting, Die, End, EndState, Error, Field, Hurt, Moving,
-    Selecting, State, Update,
+    Attacking, ConnectionQueue, Deselecting, Die, End, EndState, Error, ErrorCode, Field, Hurt,
+    Moving, Offer, Selecting, State, Timer, Turn, Update, Vision,
 };
-use crate::game::{Action, Game};
+use crate::game::{Action, Game, GameError};
+use crate::game_objects::hex::Hex;
 use crate::game_objects::hex_objects::content::Content;
 use crate::game_objects::hex_objects::wall::Wall;
 use crate::game_objects::unit::Unit;
@@ -28,13 +45,88 @@ const STATE_SELECT: &str = "select";
 const STATE_ACTION: &str = "action";
 const STATE_ATTACK: &str = "attack";
 
+// Pause before bot's turn, so players can follow the game
+const BOT_DELAY: Duration = Duration::from_millis(500);
+// Time disconnected player has to come back before the game is stopped
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Client in the room, players keep their seats for a while after disconnect
+#[derive(Debug)]
+pub struct Seat {
+    pub client: inner::Client,
+    // Deadline to reconnect, none while client is connected
+    pub reconnect_until: Option<Instant>,
+    // Player whose view spectator watches the fog of war game with, none to see everything
+    pub view: Option<u32>,
+}
+
+impl Seat {
+    pub fn new(client: inner::Client) -> Seat {
+        Seat {
+            client,
+            reconnect_until: None,
+            view: None,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.reconnect_until.is_none()
+    }
+}
+
+// Addresses of connected clients
+fn addresses(seats: &[Seat]) -> Vec<Addr<Websocket>> {
+    seats
+        .iter()
+        .filter(|seat| seat.is_connected())
+        .map(|seat| seat.client.address.clone())
+        .collect()
+}
+
 #[derive(Debug)]
 pub struct GameServer {
-    pub clients: Vec<Addr<Websocket>>,
+    pub id: Uuid,
+    pub lobby: Addr<Lobby>,
+    pub clients: Vec<Seat>,
+    pub storage: Arc<dyn Storage>,
     pub game: Game,
-    pub current_player: usize,
     pub num_of_players: usize,
+    // Preset the game was made from, none for random game
+    pub preset: Option<Preset>,
+    // Bots taking the last player seats
+    pub bots: Vec<Bot>,
+    // Scheduled turn of the bot
+    pub bot_turn: Option<SpawnHandle>,
+    // Time limits of the game, none for no limits
+    pub timer: Option<TimerSettings>,
+    // Time players have left, none if game has no time limits
+    pub clock: Option<Clock>,
+    // Timeout of the turn with its number
+    pub turn_timer: Option<(u32, SpawnHandle)>,
+    pub turn_started: Instant,
+    // Players see only what their units see
+    pub fog: bool,
+    // What every player has been shown of the field, empty without fog
+    pub views: Vec<View>,
+    // Player who made the first turn, it changes on every rematch
+    pub first_player: u32,
+    // Players who gave up the current game
+    pub surrendered: Vec<u32>,
+    // Players agreed to end the current game in a draw
+    pub draw_offers: Vec<u32>,
+    // Users who played the last game, in order of their seats
+    pub last_players: Vec<Uuid>,
+    // Players of the last game who want to play again
+    pub rematch_offers: Vec<u32>,
     pub game_started: bool,
+    // Record of the current game, none if game is not started
+    pub replay: Option<Replay>,
+    // Game restored from snapshot, it is continued on the next game start
+    pub saved_game: Option<Game>,
+    // Users in the seats of the saved game, in order of their seats
+    pub saved_players: Vec<Uuid>,
+    // Directory the running game is saved to, nothing is saved without it
+    pub snapshots_dir: Option<String>,
 }
 
 impl Actor for GameServer {
@@ -44,60 +136,221 @@ impl Actor for GameServer {
 impl Handler<inner::Request<Click>> for GameServer {
     type Result = ();
 
-    fn handle(&mut self, message: inner::Request<Click>, _: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, message: inner::Request<Click>, ctx: &mut Self::Context) -> Self::Result {
         debug!("Handle click");
 
-        let click = message.payload;
-
-        if !self.check_player_turn(&message.sender) {
-            // TODO: make error
+        if let Err(error) = self.check_turn(&message.sender) {
             debug!("Error: wrong player clicked");
+            message.reply_error(error);
             return;
         }
 
-        // Choose what action should be done now
-        if let Err(error) = match self
-            .game
-            .get_current_action(click.target, self.current_player as u32)
-        {
-            Ok(action) => {
-                debug!("Selected hex: {:?}", self.game.selected_hex);
-                debug!("Action: {:?}", action);
-                match action {
-                    Action::Deselect => Ok(self.deselect_unit()),
-                    Action::Select => {
-                        // This arm is for selection and reselsection
-                        // If we have no unit selected, we can safely call deselect
-                        self.deselect_unit();
-                        self.select_unit(click.target)
-                    }
-                    Action::Move => self.move_unit(click.target),
-                    Action::Attack => self.attack_unit(click.target),
-                }
-            }
-            Err(error) => Err(error.wrap_err("determinate action")),
-        } {
+        self.reset_timeouts();
+        if let Err(error) = self.click(message.payload.target) {
             // If some error occured during choosing action or action itself,
             // we print this error and send it to frontend
-            error!("{:?}", error.wrap_err("handle click"));
-            self.send_error(request::CMD_CLICK.to_string());
+            let error = error.wrap_err("handle click");
+            error!("{:?}", error);
+            message.reply_error(Error::from_report(&error));
         };
+
+        self.schedule_bot_turn(ctx);
+        self.schedule_turn_timer(ctx);
     }
 }
 
 impl Handler<inner::Request<SkipTurn>> for GameServer {
     type Result = ();
 
-    fn handle(&mut self, message: inner::Request<SkipTurn>, _: &mut Self::Context) -> Self::Result {
+    fn handle(
+        &mut self,
+        message: inner::Request<SkipTurn>,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
         debug!("Handle skip turn");
 
-        if !self.check_player_turn(&message.sender) {
-            // TODO: make error
-            debug!("Error: wrong player clicked");
+        if let Err(error) = self.check_turn(&message.sender) {
+            debug!("Error: wrong player skipped turn");
+            message.reply_error(error);
             return;
         }
 
-        self.next_turn()
+        self.reset_timeouts();
+        self.skip_turn();
+        self.schedule_bot_turn(ctx);
+        self.schedule_turn_timer(ctx);
+    }
+}
+
+impl Handler<inner::Request<Surrender>> for GameServer {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        message: inner::Request<Surrender>,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        debug!("Handle surrender");
+
+        match self.playing_player(&message.sender) {
+            Some(player) => self.surrender(player),
+            None => {
+                debug!("Error: only players in the game can surrender");
+                message.reply_error(not_a_player());
+            }
+        }
+
+        self.schedule_bot_turn(ctx);
+        self.schedule_turn_timer(ctx);
+    }
+}
+
+impl Handler<inner::Request<OfferDraw>> for GameServer {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        message: inner::Request<OfferDraw>,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        debug!("Handle offer draw");
+
+        match self.playing_player(&message.sender) {
+            Some(player) => self.agree_to_draw(player),
+            None => {
+                debug!("Error: only players in the game can offer draw");
+                message.reply_error(not_a_player());
+            }
+        }
+
+        self.schedule_turn_timer(ctx);
+    }
+}
+
+impl Handler<inner::Request<AcceptDraw>> for GameServer {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        message: inner::Request<AcceptDraw>,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        debug!("Handle accept draw");
+
+        let player = match self.playing_player(&message.sender) {
+            Some(player) => player,
+            None => {
+                debug!("Error: only players in the game can accept draw");
+                message.reply_error(not_a_player());
+                return;
+            }
+        };
+        if self.draw_offers.is_empty() {
+            debug!("Error: nobody has offered draw");
+            message.reply_error(Error::with_code(
+                ErrorCode::NoDrawOffer,
+                "nobody has offered draw".to_string(),
+            ));
+            return;
+        }
+
+        self.agree_to_draw(player);
+        self.schedule_turn_timer(ctx);
+    }
+}
+
+impl Handler<inner::Request<Rematch>> for GameServer {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        message: inner::Request<Rematch>,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        debug!("Handle rematch");
+
+        if self.game_started {
+            debug!("Error: game has been already started");
+            message.reply_error(Error::with_code(
+                ErrorCode::GameAlreadyStarted,
+                "game has been already started".to_string(),
+            ));
+            return;
+        }
+        let player = match self.last_player(&message.sender) {
+            Some(player) => player,
+            None => {
+                debug!("Error: only players of the last game can ask for rematch");
+                message.reply_error(Error::with_code(
+                    ErrorCode::NotLastPlayer,
+                    "only players of the last game can ask for rematch".to_string(),
+                ));
+                return;
+            }
+        };
+
+        if !self.rematch_offers.contains(&player) {
+            self.rematch_offers.push(player);
+        }
+        self.send_other_players(player, Offer::rematch(player));
+
+        // Bots always agree
+        if self.rematch_offers.len() == self.last_players.len() {
+            self.rematch();
+        }
+
+        self.schedule_bot_turn(ctx);
+        self.schedule_turn_timer(ctx);
+    }
+}
+
+impl Handler<inner::Request<ChooseView>> for GameServer {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        message: inner::Request<ChooseView>,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let player = message.payload.player;
+        let index = self
+            .clients
+            .iter()
+            .position(|seat| seat.client.address == message.sender);
+        let index = match index {
+            // Players always see with their own eyes
+            Some(index) if index >= self.num_of_humans() => index,
+            _ => {
+                debug!("Error: only spectators can choose the view");
+                message.reply_error(Error::with_code(
+                    ErrorCode::NotASpectator,
+                    "only spectators can choose the view".to_string(),
+                ));
+                return;
+            }
+        };
+        if matches!(player, Some(player) if player as usize >= self.num_of_players) {
+            debug!("Error: no player {:?} to watch", player);
+            message.reply_error(Error::with_code(
+                ErrorCode::NoPlayer,
+                "no such player to watch".to_string(),
+            ));
+            return;
+        }
+
+        self.clients[index].view = player;
+        if !self.game_started {
+            return;
+        }
+
+        let address = vec![message.sender];
+        let field = match self.seat_view(index) {
+            Some(view) => Field::with_view(&self.game, view),
+            None => Field::new(&self.game),
+        };
+        communicator::broadcast(&State::new(STATE_WAIT.to_string()), address.clone());
+        communicator::broadcast(&field, address.clone());
+        communicator::broadcast(&State::new(STATE_WATCH.to_string()), address);
     }
 }
 
@@ -107,27 +360,77 @@ impl Handler<inner::Request<StartGame>> for GameServer {
     fn handle(
         &mut self,
         message: inner::Request<StartGame>,
-        _: &mut Self::Context,
+        ctx: &mut Self::Context,
     ) -> Self::Result {
         debug!("Handle start game");
         if self.game_started {
             debug!("Error: game has been already started");
+            message.reply_error(Error::with_code(
+                ErrorCode::GameAlreadyStarted,
+                "game has been already started".to_string(),
+            ));
             return;
         }
-        if self.clients.len() < 2 {
-            debug!("Error: at least 2 players needed to start game");
-            return;
+        match self.saved_game.take() {
+            Some(game) if self.seat_saved_players() => self.start_game(game),
+            Some(game) => {
+                debug!("Error: players of the saved game are not in the room");
+                message.reply_error(Error::with_code(
+                    ErrorCode::WaitingForPlayers,
+                    "players of the saved game are not in the room".to_string(),
+                ));
+                self.saved_game = Some(game);
+            }
+            None => {
+                if self.clients.len() + message.payload.bots.len() < 2 {
+                    debug!("Error: at least 2 players needed to start game");
+                    message.reply_error(Error::with_code(
+                        ErrorCode::NotEnoughPlayers,
+                        "at least 2 players needed to start game".to_string(),
+                    ));
+                    return;
+                }
+                let request_id = message.request_id;
+                let sender = message.sender;
+                let payload = message.payload;
+                let seed = payload.seed.unwrap_or_else(rand::random);
+                if let Err(error) = self.new_game(seed, payload) {
+                    let response = start_error(&error);
+                    error!("{:?}", error.wrap_err("start game"));
+                    communicator::broadcast(&response.for_request(request_id), vec![sender]);
+                }
+            }
         }
-        self.num_of_players = self.clients.len();
-        self.new_game();
+
+        self.schedule_bot_turn(ctx);
+        self.schedule_turn_timer(ctx);
     }
 }
 
 impl Handler<inner::NewClient> for GameServer {
     type Result = ();
 
-    fn handle(&mut self, client: inner::NewClient, _: &mut Self::Context) -> Self::Result {
-        self.clients.push(client.address);
+    fn handle(&mut self, message: inner::NewClient, _: &mut Self::Context) -> Self::Result {
+        let client = message.client;
+        let held = self.clients.iter().position(|seat| {
+            !seat.is_connected()
+                && seat.client.session == client.session
+                && seat.client.user_id == client.user_id
+        });
+
+        match held {
+            Some(index) => {
+                self.clients[index] = Seat::new(client);
+                self.resume_player(index);
+            }
+            None => {
+                // Client joined in the middle of the turn, it didn't get the timer on its start
+                if let Some(timer) = self.timer_message().filter(|_| self.game_started) {
+                    communicator::broadcast(&timer, vec![client.address.clone()]);
+                }
+                self.clients.push(Seat::new(client));
+            }
+        }
 
         self.broadcast_connection_state();
     }
@@ -136,72 +439,255 @@ impl Handler<inner::NewClient> for GameServer {
 impl Handler<inner::LooseClient> for GameServer {
     type Result = ();
 
-    fn handle(&mut self, client: inner::LooseClient, _: &mut Self::Context) -> Self::Result {
-        let index = self
+    fn handle(&mut self, message: inner::LooseClient, ctx: &mut Self::Context) -> Self::Result {
+        let index = match self
             .clients
             .iter()
-            .position(|address| *address == client.address)
-            .unwrap();
-
-        self.clients.remove(index);
+            .position(|seat| seat.client.address == message.address)
+        {
+            Some(index) => index,
+            None => return,
+        };
 
-        // Stop game if one of active players leaves
-        if self.game_started && index < self.num_of_players {
-            self.stop_game();
+        let is_player = self.game_started && index < self.num_of_humans();
+        if is_player && message.disconnected {
+            self.hold_seat(index, ctx);
+        } else {
+            self.clients.remove(index);
+            // Seats have moved, players of the last game should agree again
+            self.rematch_offers.clear();
+            // Stop game if one of active players leaves
+            if is_player {
+                self.stop_game();
+                self.schedule_turn_timer(ctx);
+            }
         }
 
         self.broadcast_connection_state();
     }
 }
 
+impl Handler<inner::CloseRoom> for GameServer {
+    type Result = ();
+
+    fn handle(&mut self, _: inner::CloseRoom, ctx: &mut Self::Context) -> Self::Result {
+        debug!("Close room {}", self.id);
+        ctx.stop();
+    }
+}
+
+// Error for the client whose options don't let the game start, with details of the problem
+fn start_error(report: &eyre::Report) -> Error {
+    if let Some(error) = report.downcast_ref::<SettingsError>() {
+        Error::with_code(ErrorCode::InvalidSettings, error.to_string()).with_details(error)
+    } else if let Some(error) = report.downcast_ref::<TimerError>() {
+        Error::with_code(ErrorCode::InvalidTimer, error.to_string()).with_details(error)
+    } else if let Some(error) = report.downcast_ref::<PresetError>() {
+        let code = match error {
+            PresetError::NotEnoughClients { .. } => ErrorCode::NotEnoughPlayers,
+            _ => ErrorCode::InvalidPreset,
+        };
+        Error::with_code(code, error.to_string()).with_details(error)
+    } else if let Some(error) = report.downcast_ref::<BotError>() {
+        Error::with_code(ErrorCode::TooManyBots, error.to_string()).with_details(error)
+    } else {
+        Error::from_report(report)
+    }
+}
+
+// Error for the client who is not playing the running game
+fn not_a_player() -> Error {
+    Error::with_code(
+        ErrorCode::NotAPlayer,
+        "only players in the game can do it".to_string(),
+    )
+}
+
 impl GameServer {
-    pub fn new() -> GameServer {
+    pub fn new(id: Uuid, lobby: Addr<Lobby>, storage: Arc<dyn Storage>) -> GameServer {
         GameServer {
+            id,
+            lobby,
             clients: vec![],
+            storage,
             game: Game::new(0, 0),
-            current_player: 0,
             num_of_players: 0, // Undefined number of players on the start
+            preset: None,
+            bots: vec![],
+            bot_turn: None,
+            timer: None,
+            clock: None,
+            turn_timer: None,
+            turn_started: Instant::now(),
+            fog: false,
+            views: vec![],
+            first_player: 0,
+            surrendered: vec![],
+            draw_offers: vec![],
+            last_players: vec![],
+            rematch_offers: vec![],
             game_started: false,
+            replay: None,
+            saved_game: None,
+            saved_players: vec![],
+            snapshots_dir: Some(CONFIG.snapshots_dir.clone()),
+        }
+    }
+
+    // Room with the game from snapshot, waiting for players to continue it
+    pub fn resume(snapshot: Snapshot, lobby: Addr<Lobby>, storage: Arc<dyn Storage>) -> GameServer {
+        GameServer {
+            num_of_players: snapshot.num_of_players,
+            first_player: snapshot.first_player,
+            preset: snapshot.preset,
+            bots: snapshot.bots,
+            timer: snapshot.timer,
+            fog: snapshot.fog,
+            replay: snapshot.replay,
+            saved_game: Some(snapshot.game),
+            saved_players: snapshot.players,
+            ..GameServer::new(snapshot.room_id, lobby, storage)
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            room_id: self.id,
+            num_of_players: self.num_of_players,
+            players: self
+                .clients
+                .iter()
+                .take(self.num_of_humans())
+                .map(|seat| seat.client.user_id)
+                .collect(),
+            first_player: self.first_player,
+            preset: self.preset.clone(),
+            bots: self.bots.clone(),
+            timer: self.timer,
+            fog: self.fog,
+            game: self.game.clone(),
+            replay: self.replay.clone(),
         }
     }
 
     // Messages
     pub fn broadcast<T: Serialize>(&self, msg: T) {
-        communicator::broadcast(&msg, self.clients.clone())
+        communicator::broadcast(&msg, addresses(&self.clients))
+    }
+
+    // Address of the human player, none for bots and disconnected players
+    fn player_address(&self, player: u32) -> Option<Addr<Websocket>> {
+        if (player as usize) < self.num_of_humans() {
+            self.clients
+                .get(player as usize)
+                .filter(|seat| seat.is_connected())
+                .map(|seat| seat.client.address.clone())
+        } else {
+            None
+        }
+    }
+
+    fn send_player<T: Serialize>(&self, player: u32, msg: T) {
+        if let Some(address) = self.player_address(player) {
+            communicator::broadcast(&msg, vec![address]);
+        }
     }
 
     fn send_current_player<T: Serialize>(&self, msg: T) {
-        // TODO: here and below is potential bug due to self.clients can not have index of self.current_player
-        communicator::broadcast(&msg, vec![self.clients[self.current_player].clone()]);
+        self.send_player(self.game.current_player, msg);
     }
 
-    fn send_other_players<T: Serialize>(&self, msg: T) {
-        // TODO: May be optimized
-        let mut other_players: Vec<Addr<Websocket>> = Vec::new();
-        for i in 0..self.num_of_players {
-            if i != self.current_player {
-                other_players.push(self.clients[i].clone());
+    // Send to all players except the given one
+    fn send_other_players<T: Serialize>(&self, player: u32, msg: T) {
+        let other_players = (0..self.num_of_players as u32)
+            .filter(|other| *other != player)
+            .filter_map(|other| self.player_address(other))
+            .collect();
+        communicator::broadcast(&msg, other_players);
+    }
+
+    // View the client of the seat gets in the fog of war, none if it sees everything
+    fn seat_view(&self, index: usize) -> Option<&View> {
+        let player = if index < self.num_of_humans() {
+            Some(index as u32)
+        } else {
+            self.clients.get(index)?.view
+        };
+        self.views.get(player? as usize)
+    }
+
+    // Send every client the message made for what it sees, clients with none get nothing
+    fn broadcast_seen<T, F>(&self, message: F)
+    where
+        T: Serialize,
+        F: Fn(Option<&View>) -> Option<T>,
+    {
+        for (index, seat) in self.clients.iter().enumerate() {
+            if !seat.is_connected() {
+                continue;
+            }
+            if let Some(msg) = message(self.seat_view(index)) {
+                communicator::broadcast(&msg, vec![seat.client.address.clone()]);
             }
         }
-        communicator::broadcast(&msg, other_players);
     }
 
-    fn send_error(&self, error_message: String) {
-        let error = Error::new(error_message);
-        communicator::broadcast(&error, vec![self.clients[self.current_player].clone()]);
+    // Hexes the client sees out of the given ones
+    fn broadcast_seen_hexes<T: Serialize>(&self, hexes: &[Hex], message: fn(Vec<Hex>) -> T) {
+        self.broadcast_seen(|view| {
+            let hexes = hexes
+                .iter()
+                .filter(|hex| match view {
+                    Some(view) => view.sees(&hex.to_point()),
+                    None => true,
+                })
+                .copied()
+                .collect();
+            Some(message(hexes))
+        });
+    }
+
+    // Show players what has changed in the fog after units moved or died
+    fn update_vision(&mut self) {
+        for player in 0..self.views.len() {
+            let view = View::new(&self.game.field, player as u32);
+            let old_view = std::mem::replace(&mut self.views[player], view);
+            let hexes = old_view.changes(&self.views[player]);
+            if hexes.is_empty() && old_view.visible == self.views[player].visible {
+                continue;
+            }
+
+            let msg = Vision::new(&self.views[player], hexes);
+            let viewers = self
+                .clients
+                .iter()
+                .enumerate()
+                .filter(|(index, seat)| {
+                    seat.is_connected()
+                        && self.seat_view(*index).map(|view| view.player) == Some(player as u32)
+                })
+                .map(|(_, seat)| seat.client.address.clone())
+                .collect();
+            communicator::broadcast(&msg, viewers);
+        }
     }
 
     fn broadcast_connection_state(&self) {
-        for (player_number, player_address) in self.clients.iter().enumerate() {
+        for (player_number, seat) in self.clients.iter().enumerate() {
             let msg = &ConnectionQueue::new(
                 self.clients.len() as u32,
                 (player_number + 1) as u32,
                 self.game_started,
             );
-            communicator::broadcast(msg, vec![player_address.clone()]);
+            communicator::broadcast(msg, addresses(std::slice::from_ref(seat)));
         }
     }
 
+    fn notify_lobby(&self) {
+        self.lobby
+            .do_send(inner::RoomState::new(self.id, self.game_started));
+    }
+
     // Units
     fn select_unit(&mut self, target: Point) -> Result<()> {
         let selection = self.game.select_unit(target).wrap_err("select unit")?;
@@ -209,12 +695,24 @@ impl GameServer {
         self.send_current_player(&Selecting::new(
             selection.target.to_point(),
             &selection.highlight_hexes,
+            &self.seen_targets(selection.attack_hexes),
         ));
         self.send_current_player(&State::new(STATE_ACTION.to_string()));
 
         Ok(())
     }
 
+    // Targets the current player sees, all of them without fog
+    fn seen_targets(&self, points: Vec<Point>) -> Vec<Point> {
+        match self.views.get(self.game.current_player as usize) {
+            Some(view) => points
+                .into_iter()
+                .filter(|point| view.sees(point))
+                .collect(),
+            None => points,
+        }
+    }
+
     fn deselect_unit(&mut self) {
         if let Some(hex) = self.game.selected_hex {
             self.send_current_player(Deselecting::new(hex.to_point()));
@@ -225,33 +723,62 @@ impl GameServer {
 
     fn move_unit(&mut self, to: Point) -> Result<()> {
         let path = self.game.move_unit(to).wrap_err("move unit")?;
+        self.record_click(to);
         let hexes = self
             .game
             .hexes_from_points(path)
             .wrap_err("hexes from point")?;
-        let message = Moving::new(hexes);
-
-        self.broadcast(&message);
+        // Others see the move only if they see the whole path
+        let player = self.game.current_player;
+        self.broadcast_seen(|view| match view {
+            Some(view)
+                if view.player != player && !hexes.iter().all(|hex| view.sees(&hex.to_point())) =>
+            {
+                None
+            }
+            _ => Some(Moving::new(hexes.clone())),
+        });
+        self.update_vision();
 
-        self.game
+        let selection = self
+            .game
             .select_unit(to)
             .wrap_err("select unit after move")?;
 
-        self.send_current_player(State::new(STATE_ATTACK.to_string()));
+        // Unit may go on with the points left, and it may have got new targets
+        self.send_current_player(Selecting::new(
+            to,
+            &selection.highlight_hexes,
+            &self.seen_targets(selection.attack_hexes),
+        ));
+        let state = match self.game.can_move(&selection.target) {
+            true => STATE_ACTION,
+            false => STATE_ATTACK,
+        };
+        self.send_current_player(State::new(state.to_string()));
 
         Ok(())
     }
 
     fn attack_unit(&mut self, to: Point) -> Result<()> {
-        let (hurt, die) = self.game.attack(to).wrap_err("attack")?;
+        if !self.seen_targets(vec![to]).contains(&to) {
+            Err(GameError::WrongHex).wrap_err("target is in the fog")?;
+        }
+        let combat = self.game.attack(to).wrap_err("attack")?;
+        // Record before the turn ends, the last attack saves the replay
+        self.record_click(to);
 
         // if selected_hex is none, we must fall on .attack function befoure
         let from = self.game.selected_hex.unwrap();
 
         self.deselect_unit();
-        self.broadcast(Attacking::new(from.to_point(), to));
-        self.broadcast(Hurt::new(hurt));
-        self.broadcast(Die::new(die));
+        self.broadcast_seen(|view| match view {
+            Some(view) if !view.sees(&from.to_point()) || !view.sees(&to) => None,
+            _ => Some(Attacking::new(from.to_point(), to, &combat)),
+        });
+        self.broadcast_seen_hexes(&combat.hurt, Hurt::new);
+        self.broadcast_seen_hexes(&combat.die, Die::new);
+        self.update_vision();
         self.next_turn();
 
         Ok(())
@@ -259,66 +786,984 @@ impl GameServer {
 
     // Game logics
     pub fn next_turn(&mut self) {
-        let hexes_to_change = self.game.restore_movements(self.current_player as u32);
-        let message = Update::new(hexes_to_change);
-        debug!("Changes: {:?}", message);
-        self.broadcast(message);
+        if let Some(clock) = &mut self.clock {
+            clock.spend(self.game.current_player, self.turn_started.elapsed());
+        }
+
+        let hexes_to_change = self.game.restore_movements(self.game.current_player);
+        debug!("Changes: {:?}", hexes_to_change);
+        self.broadcast_seen_hexes(&hexes_to_change, Update::new);
+        self.update_vision();
 
         self.deselect_unit();
 
         if self.game.ends() {
-            self.send_current_player(End::new(EndState::Win));
-            self.send_other_players(End::new(EndState::Lose));
-            self.game_started = false;
-            self.broadcast_connection_state();
+            self.end_game();
             return;
         }
 
         self.send_current_player(State::new(STATE_WAIT.to_string()));
-        self.change_player();
+        self.game.change_player(self.num_of_players as u32);
         self.send_current_player(State::new(STATE_ACTION.to_string()));
+        self.save_snapshot();
 
         debug!("Game state: {:?}", self.game);
     }
 
-    fn change_player(&mut self) {
-        loop {
-            self.current_player += 1;
-            self.current_player %= self.num_of_players;
-            if self
-                .game
-                .field
-                .players_alive()
-                .contains(&(self.current_player as u32))
-            {
-                break;
-            }
+    // Human player who still has units in the running game
+    fn playing_player(&self, addr: &Addr<Websocket>) -> Option<u32> {
+        if !self.game_started {
+            return None;
+        }
+        let player = (0..self.num_of_humans() as u32)
+            .find(|player| self.player_address(*player).as_ref() == Some(addr))?;
+        if self.game.field.players_alive().contains(&player) {
+            Some(player)
+        } else {
+            None
         }
     }
 
+    // Player of the last game who is still in the same seat
+    fn last_player(&self, addr: &Addr<Websocket>) -> Option<u32> {
+        self.clients
+            .iter()
+            .zip(self.last_players.iter())
+            .position(|(seat, user_id)| {
+                seat.client.address == *addr && seat.client.user_id == *user_id
+            })
+            .map(|player| player as u32)
+    }
+
     fn check_player_turn(&self, addr: &Addr<Websocket>) -> bool {
-        self.clients[self.current_player] == *addr
+        self.player_address(self.game.current_player).as_ref() == Some(addr)
     }
 
-    pub fn new_game(&mut self) {
-        let game = Game::random(self.num_of_players);
+    // Error for the client if it can't make the turn now
+    fn check_turn(&self, addr: &Addr<Websocket>) -> std::result::Result<(), Error> {
+        if !self.game_started {
+            return Err(Error::with_code(
+                ErrorCode::GameNotStarted,
+                "game is not started".to_string(),
+            ));
+        }
+        if !self.check_player_turn(addr) {
+            return Err(Error::with_code(
+                ErrorCode::NotYourTurn,
+                "not your turn".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn num_of_humans(&self) -> usize {
+        self.num_of_players - self.bots.len()
+    }
+
+    fn click(&mut self, target: Point) -> Result<()> {
+        // Choose what action should be done now
+        let action = self
+            .game
+            .get_current_action(target, self.game.current_player)
+            .wrap_err("determinate action")?;
+        debug!("Selected hex: {:?}", self.game.selected_hex);
+        debug!("Action: {:?}", action);
+        match action {
+            Action::Deselect => {
+                self.deselect_unit();
+                self.record_click(target);
+                Ok(())
+            }
+            Action::Select => {
+                // This arm is for selection and reselsection
+                // If we have no unit selected, we can safely call deselect
+                self.deselect_unit();
+                self.select_unit(target)?;
+                self.record_click(target);
+                Ok(())
+            }
+            Action::Move => self.move_unit(target),
+            Action::Attack => self.attack_unit(target),
+        }
+    }
+
+    fn skip_turn(&mut self) {
+        self.record(Event::SkipTurn {
+            player: self.game.current_player,
+        });
+
+        self.next_turn()
+    }
+
+    // Create game from preset if it is set or random game with settings otherwise
+    pub fn new_game(&mut self, seed: u32, options: StartGame) -> Result<()> {
+        debug!("New game with seed {} and options {:?}", seed, options);
+        let StartGame {
+            preset,
+            settings,
+            bots,
+            timer,
+            fog,
+            retaliation,
+            ..
+        } = options;
+        let settings = settings.unwrap_or_default();
+        if let Some(timer) = &timer {
+            timer.validate().wrap_err("new game timer")?;
+        }
+        let (num_of_players, preset, mut game) = match preset.as_deref() {
+            Some(name) => {
+                let preset = Preset::load(&CONFIG.presets_dir, name)?;
+                let num_of_players = preset.num_of_players();
+                if bots.len() >= num_of_players {
+                    Err(BotError::TooManyBots {
+                        max: num_of_players - 1,
+                    })
+                    .wrap_err("new game from preset")?;
+                }
+                if num_of_players - bots.len() > self.clients.len() {
+                    Err(PresetError::NotEnoughClients {
+                        clients: num_of_players - bots.len(),
+                    })
+                    .wrap_err("new game from preset")?;
+                }
+                let game = preset.to_game(seed)?;
+                (num_of_players, Some(preset), game)
+            }
+            None => {
+                let num_of_players = self.clients.len() + bots.len();
+                settings
+                    .validate(num_of_players)
+                    .wrap_err("new random game")?;
+                let game =
+                    Game::random(num_of_players, seed, settings).wrap_err("new random game")?;
+                (num_of_players, None, game)
+            }
+        };
+        self.num_of_players = num_of_players;
+        self.preset = preset;
+        self.bots = bots;
+        self.timer = timer;
+        self.fog = fog;
+        self.first_player = game.current_player;
+        game.retaliation = retaliation;
+
+        self.replay = Some(Replay::new(self.num_of_players, &game));
+        self.start_game(game);
+
+        Ok(())
+    }
+
+    // Start new game or continue saved one
+    fn start_game(&mut self, mut game: Game) {
+        // Clients know nothing about selection in saved game
+        game.deselect_unit();
         self.broadcast(State::new(STATE_WAIT.to_string()));
 
-        self.broadcast(Field::new(&game));
+        self.views = if self.fog {
+            (0..self.num_of_players as u32)
+                .map(|player| View::new(&game.field, player))
+                .collect()
+        } else {
+            vec![]
+        };
+        self.broadcast_seen(|view| {
+            Some(match view {
+                Some(view) => Field::with_view(&game, view),
+                None => Field::new(&game),
+            })
+        });
         // TODO: Make a function broadcast to spectators
         communicator::broadcast(
             &State::new(STATE_WATCH.to_string()),
-            self.clients.clone()[self.num_of_players..self.clients.len()].to_vec(),
+            addresses(&self.clients[self.num_of_humans()..]),
         );
-        self.send_current_player(State::new(STATE_ACTION.to_string()));
         self.game = game;
+        self.send_current_player(State::new(STATE_ACTION.to_string()));
+        self.clock = self
+            .timer
+            .map(|timer| Clock::new(timer, self.num_of_players));
+        self.surrendered.clear();
+        self.draw_offers.clear();
+        self.last_players.clear();
+        self.rematch_offers.clear();
         self.game_started = true;
+        self.notify_lobby();
+        self.save_snapshot();
+    }
+
+    // The only player with units left wins
+    fn end_game(&mut self) {
+        let winner = self
+            .game
+            .field
+            .players_alive()
+            .into_iter()
+            .next()
+            .unwrap_or(self.game.current_player);
+        self.send_player(winner, End::new(EndState::Win));
+        // Players who surrendered already know the result
+        let losers: Vec<Addr<Websocket>> = (0..self.num_of_players as u32)
+            .filter(|player| *player != winner && !self.surrendered.contains(player))
+            .filter_map(|player| self.player_address(player))
+            .collect();
+        communicator::broadcast(&End::new(EndState::Lose), losers);
+        self.finish_game(Some(winner));
+    }
+
+    fn draw(&mut self) {
+        info!("Game in room {} ends in a draw", self.id);
+        self.broadcast(End::new(EndState::Draw));
+        self.finish_game(None);
+    }
+
+    // Save results of the finished game, players stay in the room for a rematch
+    fn finish_game(&mut self, winner: Option<u32>) {
+        self.game_started = false;
+        self.save_match(winner);
+        self.last_players = self.clients[..self.num_of_humans()]
+            .iter()
+            .map(|seat| seat.client.user_id)
+            .collect();
+        self.clients.retain(Seat::is_connected);
+        self.save_replay();
+        self.remove_snapshot();
+        self.notify_lobby();
+        self.broadcast_connection_state();
+    }
+
+    // Player gives up, game goes on if several players are still alive
+    fn surrender(&mut self, player: u32) {
+        info!("Player {} surrenders", player);
+        self.record(Event::Surrender { player });
+        self.surrendered.push(player);
+
+        let removed = self.game.remove_player(player);
+        self.broadcast_seen_hexes(&removed, Die::new);
+        self.update_vision();
+        self.send_player(player, End::new(EndState::Surrendered));
+
+        if self.game.ends() || player == self.game.current_player {
+            self.next_turn();
+        } else {
+            self.check_draw();
+        }
+    }
+
+    // Game ends in a draw when all players still alive agree, bots never do
+    fn agree_to_draw(&mut self, player: u32) {
+        if !self.draw_offers.contains(&player) {
+            self.draw_offers.push(player);
+        }
+        self.send_other_players(player, Offer::draw(player));
+        self.check_draw();
+    }
+
+    fn check_draw(&mut self) {
+        if self.draw_offers.is_empty() {
+            return;
+        }
+        let alive = self.game.field.players_alive();
+        if alive.iter().all(|player| self.draw_offers.contains(player)) {
+            self.draw();
+        }
+    }
+
+    // New game with the same players and preset or settings, the next player goes first
+    fn rematch(&mut self) {
+        let first_player = (self.first_player + 1) % self.num_of_players as u32;
+        info!(
+            "Rematch in room {}, player {} goes first",
+            self.id, first_player
+        );
+
+        let seed = rand::random();
+        let game = match &self.preset {
+            Some(preset) => preset.to_game(seed),
+            None => {
+                let settings = self.game.settings.clone().unwrap_or_default();
+                Game::random(self.num_of_players, seed, settings)
+            }
+        };
+        let mut game = match game {
+            Ok(game) => game,
+            Err(error) => {
+                error!("{:?}", error.wrap_err("rematch"));
+                return;
+            }
+        };
+        game.current_player = first_player;
+        game.retaliation = self.game.retaliation;
+        self.first_player = first_player;
+        self.replay = Some(Replay::new(self.num_of_players, &game));
+        self.start_game(game);
     }
 
     pub fn stop_game(&mut self) {
         debug!("Stop game");
         self.broadcast(End::new(EndState::Disconnected));
         self.game_started = false;
+        // Nobody can come back to the stopped game
+        self.clients.retain(Seat::is_connected);
+        self.save_replay();
+        self.remove_snapshot();
+        self.notify_lobby();
         self.broadcast_connection_state();
     }
+
+    // Timer
+    // Start the clock when a new turn of a human player begins
+    fn schedule_turn_timer(&mut self, ctx: &mut Context<Self>) {
+        let turn = self.game.turn;
+        match self.turn_timer {
+            Some((timer_turn, _)) if self.game_started && timer_turn == turn => return,
+            Some((_, handle)) => {
+                ctx.cancel_future(handle);
+                self.turn_timer = None;
+            }
+            None => {}
+        }
+
+        let player = self.game.current_player;
+        if !self.game_started || player as usize >= self.num_of_humans() {
+            return;
+        }
+        let time_for_turn = match &self.clock {
+            Some(clock) => clock.time_for_turn(player),
+            None => return,
+        };
+
+        self.turn_started = Instant::now();
+        if let Some(timer) = self.timer_message() {
+            self.broadcast(timer);
+        }
+
+        // Old timer may fire after the new turn has started, then it does nothing
+        let started = self.turn_started;
+        let handle = ctx.run_later(time_for_turn, move |server, ctx| {
+            if server.turn_started != started {
+                return;
+            }
+            server.turn_timer = None;
+            server.turn_timeout();
+            server.schedule_bot_turn(ctx);
+            server.schedule_turn_timer(ctx);
+        });
+        self.turn_timer = Some((turn, handle));
+    }
+
+    // Time the current player has left, none if there is no limit
+    fn timer_message(&self) -> Option<Timer> {
+        let clock = self.clock.as_ref()?;
+        let player = self.game.current_player;
+        if player as usize >= self.num_of_humans() {
+            return None;
+        }
+
+        let time_left = clock
+            .time_for_turn(player)
+            .checked_sub(self.turn_started.elapsed())
+            .unwrap_or_default();
+        Some(Timer::new(player, time_left, clock.bank_left(player)))
+    }
+
+    // Player has done something by themselves
+    fn reset_timeouts(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            clock.reset_timeouts(self.game.current_player);
+        }
+    }
+
+    // Turn is skipped, player forfeits after too many timeouts in a row
+    fn turn_timeout(&mut self) {
+        if !self.game_started {
+            return;
+        }
+
+        let player = self.game.current_player;
+        info!("Player {} has run out of time", player);
+        let forfeit = match &mut self.clock {
+            Some(clock) => clock.timeout(player),
+            None => false,
+        };
+
+        if forfeit {
+            self.forfeit(player);
+        } else {
+            self.skip_turn();
+        }
+    }
+
+    // Player loses all units, game goes on if several players are still alive
+    fn forfeit(&mut self, player: u32) {
+        info!("Player {} forfeits", player);
+        self.record(Event::Forfeit { player });
+
+        self.deselect_unit();
+        let removed = self.game.remove_player(player);
+        self.broadcast_seen_hexes(&removed, Die::new);
+        self.update_vision();
+        self.next_turn();
+    }
+
+    // Put users of the saved game back into their seats, other clients watch the game
+    // Return false if some of them are not in the room
+    fn seat_saved_players(&mut self) -> bool {
+        let mut indexes = vec![];
+        for user_id in &self.saved_players {
+            let index = self.clients.iter().enumerate().position(|(index, seat)| {
+                seat.client.user_id == *user_id && !indexes.contains(&index)
+            });
+            match index {
+                Some(index) => indexes.push(index),
+                None => return false,
+            }
+        }
+
+        let mut clients: Vec<Option<Seat>> = self.clients.drain(..).map(Some).collect();
+        let mut seats: Vec<Seat> = indexes
+            .iter()
+            .filter_map(|index| clients[*index].take())
+            .collect();
+        seats.extend(clients.into_iter().flatten());
+        self.clients = seats;
+        true
+    }
+
+    // Reconnect
+    // Player has some time to come back before the game is stopped
+    fn hold_seat(&mut self, index: usize, ctx: &mut Context<Self>) {
+        let seat = &mut self.clients[index];
+        info!(
+            "Player {} disconnected, seat is held for {:?}",
+            index, RECONNECT_TIMEOUT
+        );
+        seat.reconnect_until = Some(Instant::now() + RECONNECT_TIMEOUT);
+
+        let session = seat.client.session;
+        ctx.run_later(RECONNECT_TIMEOUT, move |server, ctx| {
+            server.check_reconnect(session);
+            server.schedule_turn_timer(ctx);
+        });
+    }
+
+    // Stop the game if player has not come back in time
+    // Player could reconnect and disconnect again, then the deadline is later
+    fn check_reconnect(&mut self, session: Uuid) {
+        let expired = self.clients.iter().any(|seat| {
+            seat.client.session == session
+                && matches!(seat.reconnect_until, Some(until) if until <= Instant::now())
+        });
+        if self.game_started && expired {
+            info!("Session {} has not reconnected in time", session);
+            self.stop_game();
+        }
+    }
+
+    // Send reconnected player everything to continue the game
+    fn resume_player(&mut self, index: usize) {
+        info!("Player {} reconnected", index);
+        let address = vec![self.clients[index].client.address.clone()];
+
+        // Client accepts field only after it gets a state
+        communicator::broadcast(&State::new(STATE_WAIT.to_string()), address.clone());
+        let field = match self.seat_view(index) {
+            Some(view) => Field::with_view(&self.game, view),
+            None => Field::new(&self.game),
+        };
+        communicator::broadcast(&field, address.clone());
+        communicator::broadcast(&Turn::new(self.game.current_player), address.clone());
+        if let Some(timer) = self.timer_message() {
+            communicator::broadcast(&timer, address.clone());
+        }
+
+        if index as u32 != self.game.current_player {
+            return;
+        }
+        let state = match self.game.selected_hex {
+            Some(hex) => {
+                let highlight_hexes = self.game.available_points(&Some(hex));
+                let attack_hexes = self.seen_targets(self.game.attack_points(&Some(hex)));
+                communicator::broadcast(
+                    &Selecting::new(hex.to_point(), &highlight_hexes, &attack_hexes),
+                    address.clone(),
+                );
+                match hex.unit {
+                    Some(unit) if unit.has_moved() && !self.game.can_move(&hex) => STATE_ATTACK,
+                    _ => STATE_ACTION,
+                }
+            }
+            None => STATE_ACTION,
+        };
+        communicator::broadcast(&State::new(state.to_string()), address);
+    }
+
+    // Bots
+    fn current_bot(&self) -> Option<Bot> {
+        let index = (self.game.current_player as usize).checked_sub(self.num_of_humans())?;
+        self.bots.get(index).copied()
+    }
+
+    // Let the bot play if it is its turn now
+    fn schedule_bot_turn(&mut self, ctx: &mut Context<Self>) {
+        if self.game_started && self.bot_turn.is_none() && self.current_bot().is_some() {
+            self.bot_turn = Some(ctx.run_later(BOT_DELAY, |server, ctx| {
+                server.bot_turn = None;
+                server.play_bot_turn();
+                server.schedule_bot_turn(ctx);
+                server.schedule_turn_timer(ctx);
+            }));
+        }
+    }
+
+    fn play_bot_turn(&mut self) {
+        // Game could be stopped while bot was waiting
+        let bot = match self.current_bot() {
+            Some(bot) if self.game_started => bot,
+            _ => return,
+        };
+
+        let player = self.game.current_player;
+        let turn = bot.turn(&self.game, player);
+        debug!("Bot {:?} of player {} plays {:?}", bot, player, turn);
+
+        let result = match turn {
+            Some(turn) => self.click(turn.unit).and_then(|_| {
+                if turn.move_to != turn.unit {
+                    self.click(turn.move_to)?;
+                }
+                match turn.attack {
+                    Some(target) => self.click(target),
+                    None => {
+                        self.skip_turn();
+                        Ok(())
+                    }
+                }
+            }),
+            None => {
+                self.skip_turn();
+                Ok(())
+            }
+        };
+
+        // Bot must not get stuck in its turn
+        if let Err(error) = result {
+            error!("{:?}", error.wrap_err("play bot turn"));
+            self.skip_turn();
+        }
+    }
+
+    // Snapshot
+    fn save_snapshot(&self) {
+        let dir = match &self.snapshots_dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        if let Err(error) = self.snapshot().save(dir) {
+            error!("{:?}", error.wrap_err("save snapshot"));
+        }
+    }
+
+    fn remove_snapshot(&self) {
+        let dir = match &self.snapshots_dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        if let Err(error) = Snapshot::remove(dir, self.id) {
+            error!("{:?}", error.wrap_err("remove snapshot"));
+        }
+    }
+
+    // Matches
+    // Bots are not saved
+    fn save_match(&self, winner: Option<u32>) {
+        let humans = self.num_of_humans();
+        let result = MatchResult {
+            id: Uuid::new_v4(),
+            room_id: self.id,
+            replay_id: self.replay.as_ref().map(|replay| replay.id),
+            players: self.clients[..humans]
+                .iter()
+                .map(|seat| seat.client.user_id)
+                .collect(),
+            winner: winner
+                .map(|winner| winner as usize)
+                .filter(|winner| *winner < humans)
+                .and_then(|winner| self.clients.get(winner))
+                .map(|seat| seat.client.user_id),
+            finished_at: Utc::now().naive_utc(),
+        };
+        match match_result::finish(&*self.storage, &result) {
+            Ok(()) => info!("Match {} saved", result.id),
+            Err(error) => error!("Failed to save match: {}", error),
+        }
+    }
+
+    // Replay
+    fn record(&mut self, event: Event) {
+        if let Some(replay) = &mut self.replay {
+            replay.record(event);
+        }
+    }
+
+    // Only accepted clicks get into the replay
+    fn record_click(&mut self, target: Point) {
+        self.record(Event::Click {
+            player: self.game.current_player,
+            target,
+        });
+    }
+
+    fn save_replay(&mut self) {
+        if let Some(replay) = self.replay.take() {
+            match replay.save(&CONFIG.replays_dir) {
+                Ok(path) => info!("Replay saved to {:?}", path),
+                Err(error) => error!("{:?}", error.wrap_err("save replay")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::memory::MemoryStorage;
+    use crate::settings::Settings;
+
+    // Context of the room which is not running, handlers are called directly
+    fn context() -> Context<GameServer> {
+        let (_, receiver) = actix::dev::channel::channel(16);
+        Context::with_receiver(receiver)
+    }
+
+    fn server(clients: &[inner::Client], ctx: &mut Context<GameServer>) -> GameServer {
+        let (lobby, _) = actix::dev::channel::channel(16);
+        let storage = Arc::new(MemoryStorage::default());
+        let mut server = GameServer::new(Uuid::new_v4(), Addr::new(lobby), storage);
+        // Tests must not leave saved games behind
+        server.snapshots_dir = None;
+        for client in clients {
+            server.handle(inner::NewClient::new(client.clone()), ctx);
+        }
+        server
+    }
+
+    // Room with the running random game of the clients, the game has no replay
+    fn server_with_game(clients: &[inner::Client], ctx: &mut Context<GameServer>) -> GameServer {
+        let mut server = server(clients, ctx);
+        server.num_of_players = clients.len();
+        server.start_game(Game::random(clients.len(), 1, Settings::default()).unwrap());
+        server
+    }
+
+    // Stop the game without saving its replay
+    fn stop(mut server: GameServer) {
+        server.replay = None;
+        if server.game_started {
+            server.stop_game();
+        }
+    }
+
+    // Play the game and agree to the rematch, the game has no replay
+    fn rematch_of(options: &str) -> GameServer {
+        let mut ctx = context();
+        let players = [inner::Client::test(), inner::Client::test()];
+        let mut server = server(&players, &mut ctx);
+        server
+            .new_game(3, serde_json::from_str(options).unwrap())
+            .unwrap();
+        server.replay = None;
+
+        server.handle(request(&players[1], Surrender {}), &mut ctx);
+        server.handle(request(&players[0], Rematch {}), &mut ctx);
+        server.handle(request(&players[1], Rematch {}), &mut ctx);
+        assert!(server.game_started);
+        server
+    }
+
+    fn request<T>(client: &inner::Client, payload: T) -> inner::Request<T> {
+        inner::Request::new(client.clone(), None, payload)
+    }
+
+    fn reconnect(client: &inner::Client) -> inner::Client {
+        inner::Client::new(
+            inner::Client::test().address,
+            client.user_id,
+            client.session,
+        )
+    }
+
+    #[actix_rt::test]
+    async fn reclaim_seat_by_session() {
+        let mut ctx = context();
+        let players = [inner::Client::test(), inner::Client::test()];
+        let mut server = server_with_game(&players, &mut ctx);
+
+        let lost = inner::LooseClient::new(players[0].address.clone(), true);
+        server.handle(lost, &mut ctx);
+        assert!(server.game_started);
+        assert_eq!(server.clients.len(), 2);
+        assert!(!server.clients[0].is_connected());
+
+        let client = reconnect(&players[0]);
+        server.handle(inner::NewClient::new(client.clone()), &mut ctx);
+        assert_eq!(server.clients.len(), 2);
+        assert!(server.clients[0].is_connected());
+        assert_eq!(server.clients[0].client.address, client.address);
+        assert_eq!(server.playing_player(&client.address), Some(0));
+
+        stop(server);
+    }
+
+    #[actix_rt::test]
+    async fn held_seat_is_not_taken_by_others() {
+        let mut ctx = context();
+        let players = [inner::Client::test(), inner::Client::test()];
+        let mut server = server_with_game(&players, &mut ctx);
+        let lost = inner::LooseClient::new(players[0].address.clone(), true);
+        server.handle(lost, &mut ctx);
+
+        // Another session of the same user and another user with the session only watch
+        let other_session = inner::Client::new(
+            inner::Client::test().address,
+            players[0].user_id,
+            Uuid::new_v4(),
+        );
+        let other_user = inner::Client::new(
+            inner::Client::test().address,
+            Uuid::new_v4(),
+            players[0].session,
+        );
+        server.handle(inner::NewClient::new(other_session), &mut ctx);
+        server.handle(inner::NewClient::new(other_user), &mut ctx);
+        assert_eq!(server.clients.len(), 4);
+        assert!(!server.clients[0].is_connected());
+
+        stop(server);
+    }
+
+    #[actix_rt::test]
+    async fn game_stops_when_seat_is_not_reclaimed() {
+        let mut ctx = context();
+        let players = [inner::Client::test(), inner::Client::test()];
+        let mut server = server_with_game(&players, &mut ctx);
+        let lost = inner::LooseClient::new(players[0].address.clone(), true);
+        server.handle(lost, &mut ctx);
+
+        // Deadline has passed
+        server.clients[0].reconnect_until = Some(Instant::now());
+        server.check_reconnect(players[0].session);
+        assert!(!server.game_started);
+        assert_eq!(server.clients.len(), 1);
+
+        // Too late to come back
+        server.handle(inner::NewClient::new(reconnect(&players[0])), &mut ctx);
+        assert!(!server.game_started);
+        assert_eq!(server.clients.len(), 2);
+    }
+
+    #[test]
+    fn start_errors_have_codes() {
+        let report = Err::<(), _>(TimerError::NotPositive {
+            field: "turn".to_string(),
+        })
+        .wrap_err("new game timer")
+        .unwrap_err();
+        let error = serde_json::to_value(start_error(&report)).unwrap();
+        assert_eq!(error["code"], "invalid_timer");
+        assert_eq!(error["details"]["error"], "not_positive");
+
+        let report = Err::<(), _>(SettingsError::TooBig {
+            field: "unit_hp".to_string(),
+            max: 100,
+        })
+        .wrap_err("new random game")
+        .unwrap_err();
+        let error = serde_json::to_value(start_error(&report)).unwrap();
+        assert_eq!(error["code"], "invalid_settings");
+        assert_eq!(error["details"]["error"], "too_big");
+        assert_eq!(error["details"]["field"], "unit_hp");
+
+        let report = eyre::Report::new(BotError::TooManyBots { max: 1 });
+        let error = serde_json::to_value(start_error(&report)).unwrap();
+        assert_eq!(error["code"], "too_many_bots");
+        assert_eq!(error["details"]["max"], 1);
+
+        let report = eyre::Report::new(PresetError::NotEnoughClients { clients: 2 });
+        let error = serde_json::to_value(start_error(&report)).unwrap();
+        assert_eq!(error["code"], "not_enough_players");
+    }
+
+    #[actix_rt::test]
+    async fn saved_game_waits_for_its_players() {
+        let mut ctx = context();
+        let players = [inner::Client::test(), inner::Client::test()];
+        let server = server_with_game(&players, &mut ctx);
+        let snapshot = server.snapshot();
+        stop(server);
+
+        let (lobby, _) = actix::dev::channel::channel(16);
+        let storage = Arc::new(MemoryStorage::default());
+        let mut server = GameServer::resume(snapshot, Addr::new(lobby), storage);
+        server.snapshots_dir = None;
+        let start = || serde_json::from_str::<StartGame>("{}").unwrap();
+
+        // The room is full, but a stranger can't take the seat
+        let stranger = inner::Client::test();
+        for client in &[&stranger, &players[1]] {
+            server.handle(inner::NewClient::new((*client).clone()), &mut ctx);
+        }
+        server.handle(request(&stranger, start()), &mut ctx);
+        assert!(!server.game_started);
+
+        // Players come back in other order with new websockets
+        let player = reconnect(&players[0]);
+        server.handle(inner::NewClient::new(player.clone()), &mut ctx);
+        server.handle(request(&player, start()), &mut ctx);
+        assert!(server.game_started);
+        assert_eq!(server.playing_player(&player.address), Some(0));
+        assert_eq!(server.playing_player(&players[1].address), Some(1));
+        assert_eq!(server.clients[2].client.address, stranger.address);
+
+        stop(server);
+    }
+
+    #[actix_rt::test]
+    async fn leaving_player_stops_game() {
+        let mut ctx = context();
+        let players = [inner::Client::test(), inner::Client::test()];
+        let mut server = server_with_game(&players, &mut ctx);
+
+        let left = inner::LooseClient::new(players[1].address.clone(), false);
+        server.handle(left, &mut ctx);
+        assert!(!server.game_started);
+        assert_eq!(server.clients.len(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn surrender_ends_game_of_two() {
+        let mut ctx = context();
+        let players = [inner::Client::test(), inner::Client::test()];
+        let mut server = server_with_game(&players, &mut ctx);
+
+        server.handle(request(&players[1], Surrender {}), &mut ctx);
+        assert!(!server.game_started);
+        assert_eq!(server.surrendered, vec![1]);
+        assert_eq!(server.game.field.players_alive().len(), 1);
+        assert_eq!(
+            server.last_players,
+            vec![players[0].user_id, players[1].user_id]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn only_players_surrender() {
+        let mut ctx = context();
+        let players = [inner::Client::test(), inner::Client::test()];
+        let mut server = server_with_game(&players, &mut ctx);
+        let spectator = inner::Client::test();
+        server.handle(inner::NewClient::new(spectator.clone()), &mut ctx);
+
+        server.handle(request(&spectator, Surrender {}), &mut ctx);
+        assert!(server.game_started);
+        assert!(server.surrendered.is_empty());
+
+        stop(server);
+    }
+
+    #[actix_rt::test]
+    async fn draw_when_everyone_agrees() {
+        let mut ctx = context();
+        let players = [inner::Client::test(), inner::Client::test()];
+        let mut server = server_with_game(&players, &mut ctx);
+
+        // Nothing to accept yet
+        server.handle(request(&players[1], AcceptDraw {}), &mut ctx);
+        assert!(server.draw_offers.is_empty());
+
+        server.handle(request(&players[0], OfferDraw {}), &mut ctx);
+        assert!(server.game_started);
+        assert_eq!(server.draw_offers, vec![0]);
+
+        server.handle(request(&players[1], AcceptDraw {}), &mut ctx);
+        assert!(!server.game_started);
+        assert_eq!(server.game.field.players_alive().len(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn rematch_when_everyone_asks() {
+        let mut ctx = context();
+        let players = [inner::Client::test(), inner::Client::test()];
+        let mut server = server_with_game(&players, &mut ctx);
+        let first_player = server.first_player;
+
+        // No rematch while the game goes on
+        server.handle(request(&players[0], Rematch {}), &mut ctx);
+        assert!(server.rematch_offers.is_empty());
+
+        server.handle(request(&players[1], Surrender {}), &mut ctx);
+        server.handle(request(&players[0], Rematch {}), &mut ctx);
+        assert!(!server.game_started);
+        assert_eq!(server.rematch_offers, vec![0]);
+
+        // Spectators don't take part
+        let spectator = inner::Client::test();
+        server.handle(inner::NewClient::new(spectator.clone()), &mut ctx);
+        server.handle(request(&spectator, Rematch {}), &mut ctx);
+        assert_eq!(server.rematch_offers, vec![0]);
+
+        server.handle(request(&players[1], Rematch {}), &mut ctx);
+        assert!(server.game_started);
+        assert!(server.rematch_offers.is_empty());
+        assert!(server.surrendered.is_empty());
+        assert_eq!(server.first_player, (first_player + 1) % 2);
+        assert_eq!(server.game.current_player, server.first_player);
+
+        stop(server);
+    }
+
+    #[actix_rt::test]
+    async fn no_rematch_after_player_has_left() {
+        let mut ctx = context();
+        let players = [inner::Client::test(), inner::Client::test()];
+        let mut server = server_with_game(&players, &mut ctx);
+
+        server.handle(request(&players[1], Surrender {}), &mut ctx);
+        server.handle(request(&players[0], Rematch {}), &mut ctx);
+        let left = inner::LooseClient::new(players[1].address.clone(), false);
+        server.handle(left, &mut ctx);
+        assert!(server.rematch_offers.is_empty());
+
+        // The new client sits in the seat of the one who left
+        let newcomer = inner::Client::test();
+        server.handle(inner::NewClient::new(newcomer.clone()), &mut ctx);
+        server.handle(request(&newcomer, Rematch {}), &mut ctx);
+        assert!(server.rematch_offers.is_empty());
+        assert!(!server.game_started);
+    }
+
+    #[actix_rt::test]
+    async fn rematch_with_the_same_preset() {
+        let server = rematch_of("{\"preset\": \"duel\"}");
+        let preset = Preset::load("presets", "duel").unwrap();
+
+        assert!(server.game.settings.is_none());
+        assert_eq!(
+            serde_json::to_value(&server.game.field).unwrap(),
+            serde_json::to_value(&preset.to_game(0).unwrap().field).unwrap()
+        );
+
+        stop(server);
+    }
+
+    #[actix_rt::test]
+    async fn rematch_with_the_same_settings() {
+        let server = rematch_of("{\"settings\": {\"num_x\": [6, 6], \"num_y\": [8, 8]}}");
+
+        assert_eq!(server.game.field.num_x, 6);
+        assert_eq!(server.game.field.num_y, 8);
+        assert_eq!(server.game.settings.as_ref().unwrap().num_x, (6, 6));
+
+        stop(server);
+    }
 }
@@ -0,0 +1,104 @@
+use std::collections::HashSet;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::api::common::Point;
+use crate::game_objects::grid::Grid;
+use crate::game_objects::hex_objects::content::Content;
+use crate::game_objects::hex_objects::wall::Wall;
+use crate::hex_coords::neighbours;
+
+use super::{max_obstacles, MapGenerator};
+
+use eyre::Result;
+
+// Rounds of smoothing of the random walls
+const STEPS: u32 = 4;
+
+// Caves grown by cellular automata: random walls are smoothed into solid rock and open halls
+pub struct Caves {
+    pub walls_percent: u8,
+}
+
+impl MapGenerator for Caves {
+    fn generate(&self, grid: &mut Grid, rng: &mut StdRng) -> Result<()> {
+        let mut walls: HashSet<Point> = grid
+            .hexes
+            .iter()
+            .filter(|_| rng.gen_range(0, 100) < self.walls_percent)
+            .map(|hex| hex.to_point())
+            .collect();
+
+        // Hex turns into rock among rocks and opens among open hexes
+        for _ in 0..STEPS {
+            walls = grid
+                .hexes
+                .iter()
+                .map(|hex| hex.to_point())
+                .filter(|point| match walls_around(grid, &walls, *point) {
+                    around if around >= 4 => true,
+                    around if around <= 1 => false,
+                    _ => walls.contains(point),
+                })
+                .collect();
+        }
+
+        // Smoothing may grow walls over the limit, the most open ones are removed first
+        let mut sorted: Vec<Point> = walls.iter().copied().collect();
+        sorted.sort_by_key(|point| (point.x, point.y));
+        sorted.shuffle(rng);
+        sorted.sort_by_key(|point| walls_around(grid, &walls, *point));
+        let excess = sorted
+            .len()
+            .saturating_sub(max_obstacles(grid, self.walls_percent));
+
+        for point in sorted.into_iter().skip(excess) {
+            if let Some(hex) = grid.get_hex_mut(point.x, point.y) {
+                hex.set_content(Some(Content::Wall(Wall {})));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Edge of the field counts as rock
+fn walls_around(grid: &Grid, walls: &HashSet<Point>, point: Point) -> usize {
+    let inside: Vec<Point> = neighbours(point)
+        .into_iter()
+        .filter(|point| point.x < grid.num_x && point.y < grid.num_y)
+        .collect();
+    6 - inside.len() + inside.iter().filter(|point| walls.contains(point)).count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn walls_are_within_limit() {
+        for seed in 0..10 {
+            let mut grid = Grid::new(20, 15);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let caves = Caves { walls_percent: 45 };
+            assert!(caves.generate(&mut grid, &mut rng).is_ok());
+
+            let walls = grid
+                .hexes
+                .iter()
+                .filter(|hex| hex.content.is_some())
+                .count();
+            assert!(walls > 0 && walls <= 20 * 15 * 45 / 100, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn edge_is_rock() {
+        let grid = Grid::new(5, 5);
+        let walls = HashSet::new();
+        assert_eq!(walls_around(&grid, &walls, Point { x: 0, y: 0 }), 3);
+        assert_eq!(walls_around(&grid, &walls, Point { x: 2, y: 2 }), 0);
+    }
+}
@@ -0,0 +1,93 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::api::common::Point;
+use crate::game_objects::grid::Grid;
+use crate::game_objects::hex_objects::content::Content;
+use crate::game_objects::hex_objects::wall::Wall;
+use crate::hex_coords::line;
+
+use super::{max_obstacles, MapGenerator};
+
+use eyre::Result;
+
+// Range [min, max] of the room side
+const ROOM_SIDE: (u32, u32) = (2, 5);
+
+// Rooms cut out of the solid rock, every room is joined by the corridor to the previous one
+pub struct Corridors {
+    pub walls_percent: u8,
+}
+
+impl MapGenerator for Corridors {
+    fn generate(&self, grid: &mut Grid, rng: &mut StdRng) -> Result<()> {
+        for hex in grid.hexes.iter_mut() {
+            hex.set_content(Some(Content::Wall(Wall {})));
+        }
+
+        let max_walls = max_obstacles(grid, self.walls_percent);
+        let mut walls = grid.hexes.len();
+        let mut previous: Option<Point> = None;
+        // Rooms may overlap, so there is a limit of them for the field with few walls
+        for _ in 0..grid.hexes.len() {
+            if walls <= max_walls {
+                break;
+            }
+            let width = rng.gen_range(ROOM_SIDE.0, ROOM_SIDE.1 + 1).min(grid.num_x);
+            let height = rng.gen_range(ROOM_SIDE.0, ROOM_SIDE.1 + 1).min(grid.num_y);
+            let x = rng.gen_range(0, grid.num_x - width + 1);
+            let y = rng.gen_range(0, grid.num_y - height + 1);
+
+            let room = (x..x + width).flat_map(|x| (y..y + height).map(move |y| Point { x, y }));
+            walls -= dig(grid, room);
+            let center = Point {
+                x: x + width / 2,
+                y: y + height / 2,
+            };
+            if let Some(previous) = previous {
+                walls -= dig(grid, line(previous, center).into_iter());
+            }
+            previous = Some(center);
+        }
+        Ok(())
+    }
+}
+
+// Remove walls from the hexes, returns the number of removed ones
+fn dig(grid: &mut Grid, points: impl Iterator<Item = Point>) -> usize {
+    let mut dug = 0;
+    for point in points {
+        if let Some(hex) = grid.get_hex_mut(point.x, point.y) {
+            if hex.content.is_some() {
+                hex.set_content(None);
+                dug += 1;
+            }
+        }
+    }
+    dug
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generator::is_connected;
+    use rand::SeedableRng;
+
+    #[test]
+    fn rooms_are_connected() {
+        for seed in 0..10 {
+            let mut grid = Grid::new(15, 15);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let corridors = Corridors { walls_percent: 50 };
+            assert!(corridors.generate(&mut grid, &mut rng).is_ok());
+
+            let walls = grid
+                .hexes
+                .iter()
+                .filter(|hex| hex.content.is_some())
+                .count();
+            assert!(walls <= 15 * 15 / 2, "seed {}", seed);
+            assert!(is_connected(&grid), "seed {}", seed);
+        }
+    }
+}
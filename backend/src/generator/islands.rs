@@ -0,0 +1,123 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::api::common::Point;
+use crate::game_objects::grid::Grid;
+use crate::game_objects::hex_objects::content::Content;
+
+use super::{max_obstacles, MapGenerator};
+
+use eyre::Result;
+
+// Hexes between random heights of the value noise
+const CELL: f64 = 4.0;
+
+// Islands of the value noise: the lowest hexes are under the water
+pub struct Islands {
+    pub walls_percent: u8,
+}
+
+impl MapGenerator for Islands {
+    fn generate(&self, grid: &mut Grid, rng: &mut StdRng) -> Result<()> {
+        let noise = Noise::new(grid, rng);
+        let mut heights: Vec<(f64, Point)> = grid
+            .hexes
+            .iter()
+            .map(|hex| (noise.height(hex.to_point()), hex.to_point()))
+            .collect();
+        heights.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        for (_, point) in heights
+            .into_iter()
+            .take(max_obstacles(grid, self.walls_percent))
+        {
+            if let Some(hex) = grid.get_hex_mut(point.x, point.y) {
+                hex.set_content(Some(Content::Water));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Random heights on the coarse lattice, smoothly interpolated between its nodes
+struct Noise {
+    width: usize,
+    nodes: Vec<f64>,
+}
+
+impl Noise {
+    fn new(grid: &Grid, rng: &mut StdRng) -> Noise {
+        let width = (grid.num_x as f64 / CELL) as usize + 2;
+        let height = (grid.num_y as f64 / CELL) as usize + 2;
+        let nodes = (0..width * height).map(|_| rng.gen::<f64>()).collect();
+        Noise { width, nodes }
+    }
+
+    fn node(&self, x: usize, y: usize) -> f64 {
+        self.nodes[y * self.width + x]
+    }
+
+    fn height(&self, point: Point) -> f64 {
+        // Even rows are shifted right by half of a hex
+        let x = (point.x as f64 + if point.y.is_multiple_of(2) { 0.5 } else { 0.0 }) / CELL;
+        let y = point.y as f64 / CELL;
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (tx, ty) = (smooth(x.fract()), smooth(y.fract()));
+
+        let top = lerp(self.node(x0, y0), self.node(x0 + 1, y0), tx);
+        let bottom = lerp(self.node(x0, y0 + 1), self.node(x0 + 1, y0 + 1), tx);
+        lerp(top, bottom, ty)
+    }
+}
+
+fn smooth(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn water_takes_walls_percent() {
+        let mut grid = Grid::new(12, 10);
+        let mut rng = StdRng::seed_from_u64(1);
+        let islands = Islands { walls_percent: 30 };
+        assert!(islands.generate(&mut grid, &mut rng).is_ok());
+
+        let water = grid
+            .hexes
+            .iter()
+            .filter(|hex| matches!(hex.content, Some(Content::Water)))
+            .count();
+        assert_eq!(water, 36);
+        assert!(grid
+            .hexes
+            .iter()
+            .all(|hex| hex.content.is_none() || matches!(hex.content, Some(Content::Water))));
+    }
+
+    #[test]
+    fn noise_is_smooth() {
+        let grid = Grid::new(20, 20);
+        let mut rng = StdRng::seed_from_u64(2);
+        let noise = Noise::new(&grid, &mut rng);
+        for hex in &grid.hexes {
+            let height = noise.height(hex.to_point());
+            assert!((0.0..=1.0).contains(&height));
+            if hex.x + 1 < grid.num_x {
+                let next = noise.height(Point {
+                    x: hex.x + 1,
+                    y: hex.y,
+                });
+                // Neighbours differ at most by the whole range over the cell
+                assert!((height - next).abs() <= 1.5 / CELL);
+            }
+        }
+    }
+}
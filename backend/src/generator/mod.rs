@@ -0,0 +1,504 @@
+use std::collections::{HashMap, VecDeque};
+use std::f64::consts::PI;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::api::common::Point;
+use crate::game::{Game, GameError};
+use crate::game_objects::grid::Grid;
+use crate::game_objects::hex::Hex;
+use crate::game_objects::hex_objects::content::Content;
+use crate::game_objects::unit::Unit;
+use crate::hex_coords::{distance, neighbours};
+use crate::settings::{MapKind, Symmetry};
+
+use eyre::{Result, WrapErr};
+
+pub mod caves;
+pub mod corridors;
+pub mod islands;
+pub mod uniform;
+
+// Generator of obstacles on the empty field, terrain and units are placed after it
+// Field is made symmetric and connected afterwards, so generators don't have to care
+pub trait MapGenerator {
+    fn generate(&self, grid: &mut Grid, rng: &mut StdRng) -> Result<()>;
+}
+
+impl MapKind {
+    // Obstacles take at most walls_percent of the field
+    pub fn generator(self, walls_percent: u8) -> Box<dyn MapGenerator> {
+        match self {
+            MapKind::Uniform => Box::new(uniform::Uniform { walls_percent }),
+            MapKind::Caves => Box::new(caves::Caves { walls_percent }),
+            MapKind::Islands => Box::new(islands::Islands { walls_percent }),
+            MapKind::Corridors => Box::new(corridors::Corridors { walls_percent }),
+        }
+    }
+}
+
+// Number of obstacles the generator may put on the field
+pub fn max_obstacles(grid: &Grid, walls_percent: u8) -> usize {
+    grid.hexes.len() * walls_percent as usize / 100
+}
+
+// Hexes of the field around the point
+pub fn neighbour_hexes(grid: &Grid, point: Point) -> Vec<Hex> {
+    neighbours(point)
+        .into_iter()
+        .filter_map(|point| grid.get_hex(point.x, point.y))
+        .collect()
+}
+
+// Hex the point turns into with the symmetry of the field, none without symmetry
+// Distances stay the same only when the field has rows of the right parity, see rows
+pub fn twin(grid: &Grid, symmetry: Symmetry, point: Point) -> Option<Point> {
+    let y = grid.num_y - 1 - point.y;
+    match symmetry {
+        Symmetry::None => None,
+        Symmetry::Mirror => Some(Point { x: point.x, y }),
+        Symmetry::Rotation => Some(Point {
+            x: grid.num_x - 1 - point.x,
+            y,
+        }),
+    }
+}
+
+// Number of rows closest to the wanted one which keeps the symmetry exact
+// Rows are shifted one by one, so mirror needs the odd number of rows and rotation the even one
+pub fn rows(symmetry: Symmetry, num_y: u32, range: (u32, u32)) -> u32 {
+    let odd = match symmetry {
+        Symmetry::None => return num_y,
+        Symmetry::Mirror => true,
+        Symmetry::Rotation => false,
+    };
+    if (num_y % 2 == 1) == odd {
+        num_y
+    } else if num_y < range.1 {
+        num_y + 1
+    } else if num_y > range.0 {
+        num_y - 1
+    } else {
+        // Range has the only number of rows, symmetry is approximate
+        num_y
+    }
+}
+
+// Centers of starting zones of players, evenly spread around the center of the field
+// The first player starts at the top, so with two players they face each other
+pub fn anchors(grid: &Grid, num_of_players: usize) -> Vec<Point> {
+    let half_x = (grid.num_x - 1) as f64 / 2.0;
+    let half_y = (grid.num_y - 1) as f64 / 2.0;
+    (0..num_of_players)
+        .map(|player| {
+            let angle = -PI / 2.0 + 2.0 * PI * player as f64 / num_of_players as f64;
+            Point {
+                x: (half_x + half_x * angle.cos()).round() as u32,
+                y: (half_y + half_y * angle.sin()).round() as u32,
+            }
+        })
+        .collect()
+}
+
+// Put content to the random empty hex and to its twin, return number of hexes taken
+pub fn place_content(
+    grid: &mut Grid,
+    content: Content,
+    symmetry: Symmetry,
+    rng: &mut impl Rng,
+) -> Result<u32> {
+    let free: Vec<(Point, Option<Point>)> = grid
+        .hexes
+        .iter()
+        .filter(|hex| hex.is_empty())
+        .map(|hex| hex.to_point())
+        .filter_map(|point| match twin(grid, symmetry, point) {
+            None => Some((point, None)),
+            Some(twin) if twin == point => Some((point, None)),
+            Some(twin) => match grid.get_hex(twin.x, twin.y) {
+                Some(hex) if hex.is_empty() => Some((point, Some(twin))),
+                _ => None,
+            },
+        })
+        .collect();
+
+    let (point, twin) = match free.choose(rng) {
+        Some(choice) => *choice,
+        None => Err(GameError::FieldIsFull).wrap_err("place content")?,
+    };
+    set_content(grid, point, Some(content))?;
+    if let Some(twin) = twin {
+        set_content(grid, twin, Some(content))?;
+        return Ok(2);
+    }
+    Ok(1)
+}
+
+// Put the unit to the free hex nearest to the anchor of its player
+// With symmetry the copy of the unit for the second player takes the twin hex
+pub fn place_unit(
+    game: &mut Game,
+    unit: Unit,
+    anchor: Point,
+    symmetry: Symmetry,
+    rng: &mut impl Rng,
+) -> Result<()> {
+    let grid = &game.field;
+    let mut free: Vec<(Point, Option<Point>)> = grid
+        .hexes
+        .iter()
+        .filter(|hex| hex.is_free())
+        .map(|hex| hex.to_point())
+        .filter_map(|point| match twin(grid, symmetry, point) {
+            None => Some((point, None)),
+            // First player takes the upper half of the field
+            Some(twin) if twin.y <= point.y => None,
+            Some(twin) => match grid.get_hex(twin.x, twin.y) {
+                Some(hex) if hex.is_free() => Some((point, Some(twin))),
+                _ => None,
+            },
+        })
+        .collect();
+    // Hexes at the same distance are taken in random order
+    free.shuffle(rng);
+
+    let (point, twin) = match free
+        .iter()
+        .min_by_key(|(point, _)| distance(*point, anchor))
+    {
+        Some(choice) => *choice,
+        None => Err(GameError::FieldIsFull).wrap_err("place unit")?,
+    };
+    game.set_unit(point.x, point.y, Some(unit))?;
+    if let Some(twin) = twin {
+        let copy = Unit {
+            player: unit.player + 1,
+            ..unit
+        };
+        game.set_unit(twin.x, twin.y, Some(copy))?;
+    }
+    Ok(())
+}
+
+fn set_content(grid: &mut Grid, point: Point, content: Option<Content>) -> Result<()> {
+    match grid.get_hex_mut(point.x, point.y) {
+        Some(hex) => hex.set_content(content),
+        None => {
+            Err(GameError::NoHex).wrap_err_with(|| format!("get hex {}:{}", point.x, point.y))?
+        }
+    }
+    Ok(())
+}
+
+// Copy content of the upper half of the field to the lower one
+pub fn symmetrize(grid: &mut Grid, symmetry: Symmetry) -> Result<()> {
+    let copies: Vec<(Point, Option<Content>)> = grid
+        .hexes
+        .iter()
+        .filter_map(|hex| {
+            let point = hex.to_point();
+            let twin = twin(grid, symmetry, point)?;
+            // Middle row of the rotated field is its own twin reversed
+            if (twin.y, twin.x) > (point.y, point.x) {
+                Some((twin, hex.content))
+            } else {
+                None
+            }
+        })
+        .collect();
+    for (twin, content) in copies {
+        set_content(grid, twin, content)?;
+    }
+    Ok(())
+}
+
+// All passable hexes can be reached from each other
+pub fn is_connected(grid: &Grid) -> bool {
+    let mut passable = grid.hexes.iter().filter(|hex| hex.move_cost().is_some());
+    match passable.next() {
+        Some(first) => {
+            let reached = search(grid, first.to_point(), |_| false, true);
+            passable.all(|hex| reached.contains_key(&hex.to_point()))
+        }
+        None => true,
+    }
+}
+
+// Clear the way between all parts of the field, so every unit can reach every enemy
+// Obstacles are removed along the shortest way from the cut off part to the first one
+pub fn connect(grid: &mut Grid, symmetry: Symmetry) -> Result<()> {
+    loop {
+        let first = match grid.hexes.iter().find(|hex| hex.move_cost().is_some()) {
+            Some(hex) => hex.to_point(),
+            None => return Ok(()),
+        };
+
+        let reached = search(grid, first, |_| false, true);
+        let cut_off = match grid
+            .hexes
+            .iter()
+            .find(|hex| hex.move_cost().is_some() && !reached.contains_key(&hex.to_point()))
+        {
+            Some(hex) => hex.to_point(),
+            None => return Ok(()),
+        };
+
+        // Shortest way through obstacles to any hex of the first part
+        let way = search(grid, cut_off, |point| reached.contains_key(point), false);
+        let mut point = match way.keys().find(|point| reached.contains_key(point)) {
+            Some(point) => *point,
+            None => Err(GameError::NoHex).wrap_err("connect field")?,
+        };
+        while point != cut_off {
+            for clear in std::iter::once(point).chain(twin(grid, symmetry, point)) {
+                let blocked = matches!(grid.get_hex(clear.x, clear.y),
+                    Some(hex) if hex.move_cost().is_none());
+                if blocked {
+                    set_content(grid, clear, None)?;
+                }
+            }
+            point = way[&point];
+        }
+    }
+}
+
+// Breadth first search from the point, returns found points with the previous point
+// Search stops at the first point satisfying the goal
+fn search<F>(grid: &Grid, from: Point, goal: F, passable_only: bool) -> HashMap<Point, Point>
+where
+    F: Fn(&Point) -> bool,
+{
+    let mut previous = HashMap::new();
+    let mut queue = VecDeque::new();
+    previous.insert(from, from);
+    queue.push_back(from);
+
+    while let Some(point) = queue.pop_front() {
+        for hex in neighbour_hexes(grid, point) {
+            let next = hex.to_point();
+            if previous.contains_key(&next) || (passable_only && hex.move_cost().is_none()) {
+                continue;
+            }
+            previous.insert(next, point);
+            if goal(&next) {
+                return previous;
+            }
+            queue.push_back(next);
+        }
+    }
+    previous
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_objects::hex_objects::wall::Wall;
+    use crate::settings::Settings;
+
+    const MAP_KINDS: [MapKind; 4] = [
+        MapKind::Uniform,
+        MapKind::Caves,
+        MapKind::Islands,
+        MapKind::Corridors,
+    ];
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn twins() {
+        let grid = Grid::new(5, 4);
+        let point = Point { x: 1, y: 0 };
+
+        assert_eq!(twin(&grid, Symmetry::None, point), None);
+        assert_eq!(
+            twin(&grid, Symmetry::Mirror, point),
+            Some(Point { x: 1, y: 3 })
+        );
+        assert_eq!(
+            twin(&grid, Symmetry::Rotation, point),
+            Some(Point { x: 3, y: 3 })
+        );
+    }
+
+    #[test]
+    fn twins_keep_distances() {
+        for (symmetry, num_y) in &[(Symmetry::Mirror, 7), (Symmetry::Rotation, 6)] {
+            let grid = Grid::new(5, *num_y);
+            let points: Vec<Point> = grid.hexes.iter().map(|hex| hex.to_point()).collect();
+            for a in &points {
+                for b in &points {
+                    let twin_a = twin(&grid, *symmetry, *a).unwrap();
+                    let twin_b = twin(&grid, *symmetry, *b).unwrap();
+                    assert_eq!(distance(*a, *b), distance(twin_a, twin_b));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rows_for_symmetry() {
+        assert_eq!(rows(Symmetry::None, 6, (5, 15)), 6);
+        assert_eq!(rows(Symmetry::Mirror, 6, (5, 15)), 7);
+        assert_eq!(rows(Symmetry::Mirror, 6, (5, 6)), 5);
+        assert_eq!(rows(Symmetry::Rotation, 6, (5, 15)), 6);
+        assert_eq!(rows(Symmetry::Rotation, 7, (7, 7)), 7);
+    }
+
+    #[test]
+    fn anchors_of_two_players_face_each_other() {
+        let grid = Grid::new(7, 9);
+        assert_eq!(
+            anchors(&grid, 2),
+            vec![Point { x: 3, y: 0 }, Point { x: 3, y: 8 }]
+        );
+        assert_eq!(anchors(&grid, 4).len(), 4);
+    }
+
+    #[test]
+    fn full_field() {
+        let mut game = Game::new(2, 1);
+        let mut rng = StdRng::seed_from_u64(0);
+        let wall = Content::Wall(Wall {});
+        assert_eq!(
+            place_content(&mut game.field, wall, Symmetry::None, &mut rng).unwrap(),
+            1
+        );
+        assert!(place_content(&mut game.field, wall, Symmetry::None, &mut rng).is_ok());
+
+        let result = place_content(&mut game.field, wall, Symmetry::None, &mut rng);
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<GameError>(),
+            Some(&GameError::FieldIsFull)
+        );
+        let unit = Unit::new(0, 1, [1, 1], 1);
+        let result = place_unit(
+            &mut game,
+            unit,
+            Point { x: 0, y: 0 },
+            Symmetry::None,
+            &mut rng,
+        );
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<GameError>(),
+            Some(&GameError::FieldIsFull)
+        );
+    }
+
+    #[test]
+    fn walled_off_part_gets_connected() {
+        // Wall across the field between two units
+        let mut game = Game::new(5, 5);
+        for x in 0..5 {
+            assert!(game.set_content(x, 2, Some(Content::Wall(Wall {}))).is_ok());
+        }
+        assert!(game
+            .set_unit(2, 0, Some(Unit::new(0, 1, [1, 1], 1)))
+            .is_ok());
+        assert!(game
+            .set_unit(2, 4, Some(Unit::new(1, 1, [1, 1], 1)))
+            .is_ok());
+
+        assert!(!is_connected(&game.field));
+        assert!(connect(&mut game.field, Symmetry::None).is_ok());
+        assert!(is_connected(&game.field));
+        let walls = game
+            .field
+            .hexes
+            .iter()
+            .filter(|hex| hex.content.is_some())
+            .count();
+        assert_eq!(walls, 4);
+        let reached = search(&game.field, Point { x: 2, y: 0 }, |_| false, true);
+        assert!(reached.contains_key(&Point { x: 2, y: 4 }));
+    }
+
+    fn units(game: &Game) -> Vec<(Point, Unit)> {
+        game.field
+            .hexes
+            .iter()
+            .filter_map(|hex| hex.unit.map(|unit| (hex.to_point(), unit)))
+            .collect()
+    }
+
+    #[test]
+    fn every_unit_reaches_every_enemy() {
+        for map in &MAP_KINDS {
+            let settings = Settings {
+                walls_percent: (40, 40),
+                terrain_percent: (0, 30),
+                map: *map,
+                ..Settings::default()
+            };
+            for seed in 0..30 {
+                let game = Game::random(2, seed, settings.clone()).unwrap();
+                assert!(is_connected(&game.field), "{:?} seed {}", map, seed);
+                let units = units(&game);
+                let reached = search(&game.field, units[0].0, |_| false, true);
+                assert!(
+                    units.iter().all(|(point, _)| reached.contains_key(point)),
+                    "{:?} seed {}",
+                    map,
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn units_start_in_own_zone() {
+        let settings = Settings {
+            num_x: (10, 10),
+            num_y: (12, 12),
+            num_units: (4, 4),
+            ..Settings::default()
+        };
+        for seed in 0..20 {
+            let game = Game::random(2, seed, settings.clone()).unwrap();
+            let anchors = anchors(&game.field, 2);
+            for (point, unit) in units(&game) {
+                let own = distance(point, anchors[unit.player as usize]);
+                let enemy = distance(point, anchors[1 - unit.player as usize]);
+                assert!(own < enemy, "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_fields() {
+        for (symmetry, map) in [Symmetry::Mirror, Symmetry::Rotation]
+            .iter()
+            .flat_map(|symmetry| MAP_KINDS.iter().map(move |map| (symmetry, map)))
+        {
+            let settings = Settings {
+                walls_percent: (30, 30),
+                symmetry: *symmetry,
+                map: *map,
+                ..Settings::default()
+            };
+            for seed in 0..10 {
+                let game = Game::random(2, seed, settings.clone()).unwrap();
+                let grid = &game.field;
+                for hex in &grid.hexes {
+                    let twin = twin(grid, *symmetry, hex.to_point()).unwrap();
+                    let twin = grid.get_hex(twin.x, twin.y).unwrap();
+                    assert_eq!(
+                        serde_json::to_string(&hex.content).unwrap(),
+                        serde_json::to_string(&twin.content).unwrap(),
+                        "seed {}",
+                        seed
+                    );
+                    match (hex.unit, twin.unit) {
+                        (None, None) => {}
+                        (Some(unit), Some(twin)) => {
+                            assert_ne!(unit.player, twin.player);
+                            assert_eq!(Unit { player: 0, ..unit }, Unit { player: 0, ..twin });
+                        }
+                        _ => panic!("unit has no twin, seed {}", seed),
+                    }
+                }
+            }
+        }
+    }
+}
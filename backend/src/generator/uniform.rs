@@ -0,0 +1,51 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::game_objects::grid::Grid;
+use crate::game_objects::hex_objects::content::Content;
+use crate::game_objects::hex_objects::wall::Wall;
+use crate::settings::Symmetry;
+
+use super::{max_obstacles, place_content, MapGenerator};
+
+use eyre::Result;
+
+// Walls and water scattered over the field one by one
+pub struct Uniform {
+    pub walls_percent: u8,
+}
+
+impl MapGenerator for Uniform {
+    fn generate(&self, grid: &mut Grid, rng: &mut StdRng) -> Result<()> {
+        // Every fourth obstacle is water
+        for _ in 0..max_obstacles(grid, self.walls_percent) {
+            let content = match rng.gen_range(0, 4) {
+                0 => Content::Water,
+                _ => Content::Wall(Wall {}),
+            };
+            place_content(grid, content, Symmetry::None, rng)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn exact_number_of_obstacles() {
+        let mut grid = Grid::new(10, 10);
+        let mut rng = StdRng::seed_from_u64(3);
+        let uniform = Uniform { walls_percent: 25 };
+        assert!(uniform.generate(&mut grid, &mut rng).is_ok());
+
+        let obstacles = grid
+            .hexes
+            .iter()
+            .filter(|hex| hex.move_cost().is_none())
+            .count();
+        assert_eq!(obstacles, 25);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::auth::{create_jwt, verify};
+use crate::database::Storage;
+use crate::errors::ApiError;
+use crate::helpers::respond_json;
+use crate::models::user::find_by_email;
+use actix_web::web::{block, Data, Json};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginResponse {
+    pub token: String,
+}
+
+/// Api
+/// Login with email and password, get JWT
+pub async fn login(
+    data: Data<dyn Storage>,
+    params: Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    info!("Login: {}", params.email);
+    let token = block(move || {
+        match find_by_email(&**data, &params.email)? {
+            Some(user) if verify(&params.password, &user.password) => create_jwt(&user.id),
+            // Don't tell if it is email or password that is wrong
+            _ => Err(ApiError::Unauthorized(
+                "wrong email or password".to_string(),
+            )),
+        }
+    })
+    .await?;
+    respond_json(LoginResponse { token })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::memory::MemoryStorage;
+    use crate::models::user::{create_random_user, User};
+    use std::sync::Arc;
+
+    fn test_storage(user: &User) -> Data<dyn Storage> {
+        let storage = MemoryStorage::default();
+        storage.insert_user(user).unwrap();
+        Data::from(Arc::new(storage) as Arc<dyn Storage>)
+    }
+
+    #[actix_rt::test]
+    async fn test_login() {
+        let user = create_random_user();
+        let response = login(
+            test_storage(&user),
+            Json(LoginRequest {
+                email: user.email.clone(),
+                password: "tbd".to_string(),
+            }),
+        )
+        .await
+        .unwrap();
+
+        let user_id = crate::auth::decode_jwt(&response.into_inner().token).unwrap();
+        assert_eq!(user_id.to_string(), user.id);
+    }
+
+    #[actix_rt::test]
+    async fn test_login_wrong_password() {
+        let user = create_random_user();
+        let response = login(
+            test_storage(&user),
+            Json(LoginRequest {
+                email: user.email,
+                password: "wrong_password".to_string(),
+            }),
+        )
+        .await;
+
+        assert!(matches!(response, Err(ApiError::Unauthorized(_))));
+    }
+}
@@ -1,8 +1,12 @@
-use crate::database::UserStorage;
+use crate::auth::authorize;
+use crate::database::Storage;
 use crate::errors::ApiError;
-use crate::helpers::respond_json;
-use crate::models::user::{find, get_all, User};
-use actix_web::web::{block, Data, Json, Path};
+use crate::helpers::{respond_json, respond_ok};
+use crate::models::match_result::{self, MatchResult};
+use crate::models::rating::{self, Rating};
+use crate::models::user::{create, delete, find, get_all, update, NewUser, UpdateUser, User};
+use actix_web::web::{block, Data, HttpResponse, Json, Path};
+use actix_web::HttpRequest;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -16,6 +20,9 @@ pub struct UserResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UsersResponse(pub Vec<UserResponse>);
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MatchesResponse(pub Vec<MatchResult>);
+
 impl From<User> for UserResponse {
     fn from(user: User) -> UserResponse {
         UserResponse {
@@ -35,12 +42,12 @@ impl From<Vec<User>> for UsersResponse {
 /// Api
 /// Get user
 pub async fn get_user(
-    data: Data<UserStorage>,
+    data: Data<dyn Storage>,
     user_id: Path<Uuid>,
 ) -> Result<Json<UserResponse>, ApiError> {
     info!("User id: {}", *user_id);
     let user = block(move || {
-        let user = find(&data, *user_id);
+        let user = find(&**data, *user_id)?;
         match user {
             Some(u) => Ok(u),
             None => Err(ApiError::NotFound("user".to_string())),
@@ -51,7 +58,66 @@ pub async fn get_user(
 }
 
 /// Get all users
-pub async fn get_users(data: Data<UserStorage>) -> Result<Json<UsersResponse>, ApiError> {
-    let users = block(move || Ok(get_all(&data))).await?;
+pub async fn get_users(data: Data<dyn Storage>) -> Result<Json<UsersResponse>, ApiError> {
+    let users = block(move || get_all(&**data)).await?;
     respond_json(users.into())
 }
+
+/// Sign up
+pub async fn create_user(
+    data: Data<dyn Storage>,
+    params: Json<NewUser>,
+) -> Result<HttpResponse, ApiError> {
+    info!("Create user: {}", params.handle);
+    let user = block(move || create(&**data, params.into_inner())).await?;
+    Ok(HttpResponse::Created().json(UserResponse::from(user)))
+}
+
+/// Change handle, email or password of the user
+pub async fn update_user(
+    req: HttpRequest,
+    data: Data<dyn Storage>,
+    user_id: Path<Uuid>,
+    params: Json<UpdateUser>,
+) -> Result<Json<UserResponse>, ApiError> {
+    info!("Update user: {}", *user_id);
+    authorize(&req, *user_id)?;
+    let user = block(move || update(&**data, *user_id, params.into_inner())).await?;
+    respond_json(user.into())
+}
+
+/// Delete user
+pub async fn delete_user(
+    req: HttpRequest,
+    data: Data<dyn Storage>,
+    user_id: Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    info!("Delete user: {}", *user_id);
+    authorize(&req, *user_id)?;
+    block(move || delete(&**data, *user_id)).await?;
+    respond_ok()
+}
+
+/// Get rating of the user
+pub async fn get_rating(
+    data: Data<dyn Storage>,
+    user_id: Path<Uuid>,
+) -> Result<Json<Rating>, ApiError> {
+    let rating = block(move || {
+        if find(&**data, *user_id)?.is_none() {
+            return Err(ApiError::NotFound("user".to_string()));
+        }
+        rating::get(&**data, *user_id)
+    })
+    .await?;
+    respond_json(rating)
+}
+
+/// Get matches the user played, the latest first
+pub async fn get_matches(
+    data: Data<dyn Storage>,
+    user_id: Path<Uuid>,
+) -> Result<Json<MatchesResponse>, ApiError> {
+    let matches = block(move || match_result::get_for_user(&**data, *user_id)).await?;
+    respond_json(MatchesResponse(matches))
+}
@@ -2,6 +2,7 @@ use crate::errors::ApiError;
 use actix_web::{
     body::Body,
     web::{HttpResponse, Json},
+    HttpRequest,
 };
 use serde::Serialize;
 
@@ -15,3 +16,11 @@ where
 pub fn respond_ok() -> Result<HttpResponse, ApiError> {
     Ok(HttpResponse::Ok().body(Body::Empty))
 }
+
+pub fn query_param(req: &HttpRequest, name: &str) -> Option<String> {
+    req.query_string()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
@@ -0,0 +1,338 @@
+use crate::api::common::Point;
+
+// Field uses offset coordinates where even rows are shifted right by half of a hex
+// Cube coordinates make the hex math simple: q + r + s = 0, r is the row
+
+// Steps to the six neighbours, starting from the right one counterclockwise
+const DIRECTIONS: [(i64, i64); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cube {
+    pub q: i64,
+    pub r: i64,
+    pub s: i64,
+}
+
+impl Cube {
+    pub fn new(q: i64, r: i64) -> Cube {
+        Cube { q, r, s: -q - r }
+    }
+
+    pub fn from_point(point: Point) -> Cube {
+        let x = point.x as i64;
+        let y = point.y as i64;
+        Cube::new(x - (y + (y & 1)) / 2, y)
+    }
+
+    // None for hexes with negative offset coordinates
+    pub fn to_point(self) -> Option<Point> {
+        let x = self.q + (self.r + (self.r & 1)) / 2;
+        if x < 0 || self.r < 0 {
+            return None;
+        }
+        Some(Point {
+            x: x as u32,
+            y: self.r as u32,
+        })
+    }
+
+    // Number of steps between two hexes
+    pub fn distance(self, other: Cube) -> u32 {
+        ((self.q - other.q).abs() + (self.r - other.r).abs() + (self.s - other.s).abs()) as u32 / 2
+    }
+
+    fn add(self, other: Cube) -> Cube {
+        Cube::new(self.q + other.q, self.r + other.r)
+    }
+
+    fn sub(self, other: Cube) -> Cube {
+        Cube::new(self.q - other.q, self.r - other.r)
+    }
+
+    fn scale(self, factor: i64) -> Cube {
+        Cube::new(self.q * factor, self.r * factor)
+    }
+
+    fn direction(index: usize) -> Cube {
+        let (q, r) = DIRECTIONS[index % 6];
+        Cube::new(q, r)
+    }
+
+    pub fn neighbours(self) -> Vec<Cube> {
+        (0..6)
+            .map(|index| self.add(Cube::direction(index)))
+            .collect()
+    }
+
+    // Hexes exactly at the radius, one hex for zero radius
+    pub fn ring(self, radius: u32) -> Vec<Cube> {
+        if radius == 0 {
+            return vec![self];
+        }
+        let mut hexes = Vec::with_capacity(6 * radius as usize);
+        let mut hex = self.add(Cube::direction(4).scale(radius as i64));
+        for side in 0..6 {
+            for _ in 0..radius {
+                hexes.push(hex);
+                hex = hex.add(Cube::direction(side));
+            }
+        }
+        hexes
+    }
+
+    // Hexes within the radius, ring by ring from the center
+    pub fn spiral(self, radius: u32) -> Vec<Cube> {
+        (0..=radius).flat_map(|ring| self.ring(ring)).collect()
+    }
+
+    // Hexes the straight line between centers of two hexes goes through, both ends included
+    pub fn line_to(self, other: Cube) -> Vec<Cube> {
+        let steps = self.distance(other);
+        // Shift the line a bit, so it doesn't go exactly between two hexes
+        let nudge = |cube: Cube| {
+            (
+                cube.q as f64 + 1e-6,
+                cube.r as f64 + 2e-6,
+                cube.s as f64 - 3e-6,
+            )
+        };
+        let start = nudge(self);
+        let end = nudge(other);
+
+        (0..=steps)
+            .map(|step| {
+                let t = if steps == 0 {
+                    0.0
+                } else {
+                    step as f64 / steps as f64
+                };
+                round(
+                    start.0 + (end.0 - start.0) * t,
+                    start.1 + (end.1 - start.1) * t,
+                    start.2 + (end.2 - start.2) * t,
+                )
+            })
+            .collect()
+    }
+
+    // Rotation by 60 degrees around the center
+    pub fn rotate_left(self, center: Cube) -> Cube {
+        let v = self.sub(center);
+        center.add(Cube::new(-v.s, -v.q))
+    }
+
+    pub fn rotate_right(self, center: Cube) -> Cube {
+        let v = self.sub(center);
+        center.add(Cube::new(-v.r, -v.s))
+    }
+
+    // Reflection across the axis going through the center where q doesn't change
+    pub fn reflect_q(self, center: Cube) -> Cube {
+        let v = self.sub(center);
+        center.add(Cube::new(v.q, v.s))
+    }
+
+    pub fn reflect_r(self, center: Cube) -> Cube {
+        let v = self.sub(center);
+        center.add(Cube::new(v.s, v.r))
+    }
+
+    pub fn reflect_s(self, center: Cube) -> Cube {
+        let v = self.sub(center);
+        center.add(Cube::new(v.r, v.q))
+    }
+}
+
+fn round(q: f64, r: f64, s: f64) -> Cube {
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    Cube::new(rq as i64, rr as i64)
+}
+
+// Helpers for offset coordinates, hexes with negative coordinates are dropped
+
+pub fn distance(from: Point, to: Point) -> u32 {
+    Cube::from_point(from).distance(Cube::from_point(to))
+}
+
+pub fn neighbours(point: Point) -> Vec<Point> {
+    Cube::from_point(point)
+        .neighbours()
+        .into_iter()
+        .filter_map(Cube::to_point)
+        .collect()
+}
+
+pub fn line(from: Point, to: Point) -> Vec<Point> {
+    Cube::from_point(from)
+        .line_to(Cube::from_point(to))
+        .into_iter()
+        .filter_map(Cube::to_point)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    fn point(x: u32, y: u32) -> Point {
+        Point { x, y }
+    }
+
+    // Neighbours as the game found them before: 3x3 square without two corners
+    fn square_neighbours(point: Point) -> HashSet<Point> {
+        let mut points = HashSet::new();
+        let min_x = point.x.saturating_sub(1);
+        let min_y = point.y.saturating_sub(1);
+        for x in min_x..point.x + 2 {
+            for y in min_y..point.y + 2 {
+                if x == point.x && y == point.y {
+                    continue;
+                }
+                if point.y.is_multiple_of(2) {
+                    if x + 1 == point.x && y != point.y {
+                        continue;
+                    }
+                } else if x == point.x + 1 && y != point.y {
+                    continue;
+                }
+                points.insert(Point { x, y });
+            }
+        }
+        points
+    }
+
+    fn cube() -> impl Strategy<Value = Cube> {
+        (-50i64..50, -50i64..50).prop_map(|(q, r)| Cube::new(q, r))
+    }
+
+    #[test]
+    fn neighbours_of_odd_and_even_rows() {
+        let odd: HashSet<Point> = neighbours(point(1, 9)).into_iter().collect();
+        let expected = [(0, 8), (1, 8), (0, 9), (2, 9), (0, 10), (1, 10)];
+        assert_eq!(odd, expected.iter().map(|(x, y)| point(*x, *y)).collect());
+
+        let even: HashSet<Point> = neighbours(point(1, 10)).into_iter().collect();
+        let expected = [(1, 9), (2, 9), (0, 10), (2, 10), (1, 11), (2, 11)];
+        assert_eq!(even, expected.iter().map(|(x, y)| point(*x, *y)).collect());
+
+        assert_eq!(neighbours(point(0, 0)).len(), 3);
+    }
+
+    #[test]
+    fn distance_to_neighbours() {
+        assert_eq!(distance(point(1, 9), point(1, 9)), 0);
+        for neighbour in &[(0, 8), (1, 8), (0, 9), (2, 9), (0, 10), (1, 10)] {
+            assert_eq!(distance(point(1, 9), point(neighbour.0, neighbour.1)), 1);
+        }
+        for neighbour in &[(1, 9), (2, 9), (0, 10), (2, 10), (1, 11), (2, 11)] {
+            assert_eq!(distance(point(1, 10), point(neighbour.0, neighbour.1)), 1);
+        }
+        assert_eq!(distance(point(0, 0), point(4, 0)), 4);
+        assert_eq!(distance(point(0, 0), point(0, 4)), 4);
+        assert_eq!(distance(point(0, 0), point(3, 4)), 5);
+        assert_eq!(distance(point(0, 0), point(3, 3)), 4);
+    }
+
+    #[test]
+    fn line_goes_through_neighbours() {
+        let points = line(point(0, 0), point(4, 3));
+        assert_eq!(points.len(), 6);
+        assert_eq!(points.first(), Some(&point(0, 0)));
+        assert_eq!(points.last(), Some(&point(4, 3)));
+        for pair in points.windows(2) {
+            assert_eq!(distance(pair[0], pair[1]), 1);
+        }
+    }
+
+    #[test]
+    fn ring_and_spiral_sizes() {
+        let center = Cube::new(0, 0);
+        assert_eq!(center.ring(0), vec![center]);
+        assert_eq!(center.ring(3).len(), 18);
+        assert_eq!(center.spiral(2).len(), 19);
+    }
+
+    proptest! {
+        #[test]
+        fn neighbours_agree_with_square_scan(x in 0u32..30, y in 0u32..30) {
+            let found: HashSet<Point> = neighbours(point(x, y)).into_iter().collect();
+            prop_assert_eq!(found, square_neighbours(point(x, y)));
+        }
+
+        #[test]
+        fn offset_round_trip(x in 0u32..100, y in 0u32..100) {
+            prop_assert_eq!(Cube::from_point(point(x, y)).to_point(), Some(point(x, y)));
+        }
+
+        #[test]
+        fn distance_is_metric(a in cube(), b in cube(), c in cube()) {
+            prop_assert_eq!(a.distance(b), b.distance(a));
+            prop_assert_eq!(a.distance(a), 0);
+            prop_assert!(a.distance(c) <= a.distance(b) + b.distance(c));
+        }
+
+        #[test]
+        fn neighbours_are_one_step_away(a in cube()) {
+            for neighbour in a.neighbours() {
+                prop_assert_eq!(a.distance(neighbour), 1);
+            }
+        }
+
+        #[test]
+        fn ring_is_at_radius(a in cube(), radius in 0u32..10) {
+            let ring = a.ring(radius);
+            prop_assert_eq!(ring.len(), std::cmp::max(1, 6 * radius as usize));
+            prop_assert!(ring.iter().all(|hex| a.distance(*hex) == radius));
+            let unique: HashSet<Cube> = ring.iter().copied().collect();
+            prop_assert_eq!(unique.len(), ring.len());
+        }
+
+        #[test]
+        fn spiral_covers_area(a in cube(), radius in 0u32..8) {
+            let spiral = a.spiral(radius);
+            let r = radius as usize;
+            prop_assert_eq!(spiral.len(), 1 + 3 * r * (r + 1));
+            prop_assert!(spiral.iter().all(|hex| a.distance(*hex) <= radius));
+        }
+
+        #[test]
+        fn line_is_continuous(a in cube(), b in cube()) {
+            let line = a.line_to(b);
+            prop_assert_eq!(line.len() as u32, a.distance(b) + 1);
+            prop_assert_eq!(line[0], a);
+            prop_assert_eq!(*line.last().unwrap(), b);
+            for pair in line.windows(2) {
+                prop_assert_eq!(pair[0].distance(pair[1]), 1);
+            }
+        }
+
+        #[test]
+        fn rotation_keeps_distance(a in cube(), center in cube()) {
+            let mut hex = a;
+            for _ in 0..6 {
+                hex = hex.rotate_left(center);
+                prop_assert_eq!(hex.distance(center), a.distance(center));
+            }
+            prop_assert_eq!(hex, a);
+            prop_assert_eq!(a.rotate_left(center).rotate_right(center), a);
+        }
+
+        #[test]
+        fn reflection_is_involution(a in cube(), center in cube()) {
+            for reflect in &[Cube::reflect_q, Cube::reflect_r, Cube::reflect_s] {
+                let reflected = reflect(a, center);
+                prop_assert_eq!(reflected.distance(center), a.distance(center));
+                prop_assert_eq!(reflect(reflected, center), a);
+            }
+            prop_assert_eq!(a.reflect_q(center).q, a.q);
+        }
+    }
+}
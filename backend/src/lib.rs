@@ -0,0 +1,31 @@
+// Game logic and server parts, the binary only starts the server
+pub mod api;
+pub mod auth;
+pub mod bot;
+pub mod clock;
+pub mod communicator;
+pub mod config;
+pub mod database;
+pub mod errors;
+pub mod game;
+pub mod game_objects;
+pub mod game_server;
+pub mod generator;
+pub mod handlers;
+pub mod helpers;
+pub mod hex_coords;
+pub mod lobby;
+pub mod models;
+pub mod preset;
+pub mod replay;
+pub mod routes;
+pub mod settings;
+pub mod sight;
+pub mod snapshot;
+pub mod websocket;
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+extern crate log;
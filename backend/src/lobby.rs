@@ -0,0 +1,540 @@
+use actix::{Actor, Addr, AsyncContext, Context, Handler};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::communicator;
+use crate::config::CONFIG;
+use crate::database::Storage;
+use crate::game_server::GameServer;
+use crate::snapshot::Snapshot;
+use crate::websocket::Websocket;
+
+use crate::api::inner;
+use crate::api::request::{CreateRoom, JoinRoom, LeaveRoom, ListRooms};
+use crate::api::response::{Error, ErrorCode, Room as RoomResponse, RoomInfo, Rooms, Session};
+
+// Room as lobby sees it
+#[derive(Debug)]
+struct Room {
+    address: Addr<GameServer>,
+    clients: Vec<inner::Client>,
+    // Users of sessions disconnected during the game, they are returned to the room on reconnect
+    held: HashMap<Uuid, Uuid>,
+    game_started: bool,
+    // Game saved before server restart waits for its own players
+    resumed: bool,
+}
+
+impl Room {
+    fn new(address: Addr<GameServer>) -> Room {
+        Room {
+            address,
+            clients: vec![],
+            held: HashMap::new(),
+            game_started: false,
+            resumed: false,
+        }
+    }
+
+    fn has_client(&self, address: &Addr<Websocket>) -> bool {
+        self.clients.iter().any(|client| client.address == *address)
+    }
+}
+
+// Registry of game rooms
+// Every client joins and leaves rooms through the lobby,
+// so lobby always knows who is in which room
+#[derive(Debug)]
+pub struct Lobby {
+    rooms: HashMap<Uuid, Room>,
+    // Passed to rooms to save results of matches
+    storage: Arc<dyn Storage>,
+}
+
+impl Actor for Lobby {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.resume_rooms(ctx);
+    }
+}
+
+impl Handler<inner::Request<CreateRoom>> for Lobby {
+    type Result = ();
+
+    fn handle(&mut self, message: inner::Request<CreateRoom>, ctx: &mut Self::Context) {
+        debug!("Handle create room");
+
+        let room_id = self.create_room(ctx);
+        self.join_room(room_id, message.client());
+    }
+}
+
+impl Handler<inner::Request<JoinRoom>> for Lobby {
+    type Result = ();
+
+    fn handle(&mut self, message: inner::Request<JoinRoom>, ctx: &mut Self::Context) {
+        debug!("Handle join room");
+
+        let room_id = match message.payload.room_id {
+            Some(room_id) => {
+                if !self.rooms.contains_key(&room_id) {
+                    debug!("Error: no room with id {}", room_id);
+                    message.reply_error(Error::with_code(
+                        ErrorCode::NoRoom,
+                        format!("no room with id {}", room_id),
+                    ));
+                    return;
+                }
+                room_id
+            }
+            None => match self.waiting_room() {
+                Some(room_id) => room_id,
+                None => self.create_room(ctx),
+            },
+        };
+
+        self.join_room(room_id, message.client());
+    }
+}
+
+impl Handler<inner::Request<LeaveRoom>> for Lobby {
+    type Result = ();
+
+    fn handle(&mut self, message: inner::Request<LeaveRoom>, _: &mut Self::Context) {
+        debug!("Handle leave room");
+
+        if self.leave_room(&message.sender).is_none() {
+            debug!("Error: client is not in a room");
+            message.reply_error(Error::with_code(
+                ErrorCode::NotInRoom,
+                "client is not in a room".to_string(),
+            ));
+            return;
+        }
+
+        communicator::broadcast(&RoomResponse::new(None), vec![message.sender.clone()]);
+        message.sender.do_send(inner::RoomChanged::new(None));
+    }
+}
+
+impl Handler<inner::Request<ListRooms>> for Lobby {
+    type Result = ();
+
+    fn handle(&mut self, message: inner::Request<ListRooms>, _: &mut Self::Context) {
+        debug!("Handle list rooms");
+
+        let rooms = self
+            .rooms
+            .iter()
+            .map(|(id, room)| RoomInfo::new(*id, room.clients.len() as u32, room.game_started))
+            .collect();
+        communicator::broadcast(&Rooms::new(rooms), vec![message.sender]);
+    }
+}
+
+impl Handler<inner::Connect> for Lobby {
+    type Result = ();
+
+    fn handle(&mut self, message: inner::Connect, _: &mut Self::Context) {
+        let client = message.client;
+        let held_room = self
+            .rooms
+            .iter()
+            .find(|(_, room)| room.held.get(&client.session) == Some(&client.user_id))
+            .map(|(id, _)| *id);
+
+        let reconnected = match held_room {
+            Some(room_id) => {
+                info!("Session {} reconnected to room {}", client.session, room_id);
+                if let Some(room) = self.rooms.get_mut(&room_id) {
+                    room.held.remove(&client.session);
+                }
+                self.join_room(room_id, client.clone());
+                true
+            }
+            None => false,
+        };
+        communicator::broadcast(
+            &Session::new(client.session, reconnected),
+            vec![client.address],
+        );
+    }
+}
+
+impl Handler<inner::Disconnect> for Lobby {
+    type Result = ();
+
+    fn handle(&mut self, message: inner::Disconnect, _: &mut Self::Context) {
+        let room_id = match self.room_of(&message.address) {
+            Some(room_id) => room_id,
+            None => return,
+        };
+        let room = match self.rooms.get_mut(&room_id) {
+            Some(room) => room,
+            None => return,
+        };
+
+        // Seat is held while the game goes on, room decides if the client was a player
+        if room.game_started {
+            if let Some(position) = room
+                .clients
+                .iter()
+                .position(|client| client.address == message.address)
+            {
+                let client = room.clients.remove(position);
+                room.held.insert(client.session, client.user_id);
+            }
+            room.address
+                .do_send(inner::LooseClient::new(message.address, true));
+        } else {
+            self.leave_room(&message.address);
+        }
+    }
+}
+
+impl Handler<inner::RoomState> for Lobby {
+    type Result = ();
+
+    fn handle(&mut self, message: inner::RoomState, _: &mut Self::Context) {
+        if let Some(room) = self.rooms.get_mut(&message.room_id) {
+            room.game_started = message.game_started;
+            room.resumed = false;
+            // Nobody can come back to the finished game
+            if !room.game_started {
+                room.held.clear();
+            }
+        }
+        self.close_room_if_empty(message.room_id);
+    }
+}
+
+impl Lobby {
+    pub fn new(storage: Arc<dyn Storage>) -> Lobby {
+        Lobby {
+            rooms: HashMap::new(),
+            storage,
+        }
+    }
+
+    fn create_room(&mut self, ctx: &mut Context<Self>) -> Uuid {
+        let room_id = Uuid::new_v4();
+        let address = GameServer::new(room_id, ctx.address(), self.storage.clone()).start();
+        info!("Room {} created", room_id);
+
+        self.rooms.insert(room_id, Room::new(address));
+
+        room_id
+    }
+
+    // Create rooms for games saved before server restart
+    fn resume_rooms(&mut self, ctx: &mut Context<Self>) {
+        let snapshots = match Snapshot::load_all(&CONFIG.snapshots_dir) {
+            Ok(snapshots) => snapshots,
+            Err(error) => {
+                error!("{:?}", error.wrap_err("load snapshots"));
+                return;
+            }
+        };
+
+        for snapshot in snapshots {
+            let room_id = snapshot.room_id;
+            let address = GameServer::resume(snapshot, ctx.address(), self.storage.clone()).start();
+            info!("Room {} resumed", room_id);
+
+            let mut room = Room::new(address);
+            room.resumed = true;
+            self.rooms.insert(room_id, room);
+        }
+    }
+
+    // Room where new game has not been started yet
+    fn waiting_room(&self) -> Option<Uuid> {
+        self.rooms
+            .iter()
+            .find(|(_, room)| !room.game_started && !room.resumed)
+            .map(|(id, _)| *id)
+    }
+
+    fn join_room(&mut self, room_id: Uuid, client: inner::Client) {
+        // Client can be only in one room at a time
+        self.leave_room(&client.address);
+
+        let room = match self.rooms.get_mut(&room_id) {
+            Some(room) => room,
+            None => return,
+        };
+
+        room.clients.push(client.clone());
+        room.address.do_send(inner::NewClient::new(client.clone()));
+
+        communicator::broadcast(
+            &RoomResponse::new(Some(room_id)),
+            vec![client.address.clone()],
+        );
+        client
+            .address
+            .do_send(inner::RoomChanged::new(Some(room.address.clone())));
+    }
+
+    fn room_of(&self, client: &Addr<Websocket>) -> Option<Uuid> {
+        self.rooms
+            .iter()
+            .find(|(_, room)| room.has_client(client))
+            .map(|(id, _)| *id)
+    }
+
+    // Remove client from its room and close the room if it becomes empty
+    // Return id of the room client has left
+    fn leave_room(&mut self, client: &Addr<Websocket>) -> Option<Uuid> {
+        let room_id = self.room_of(client)?;
+
+        let room = self.rooms.get_mut(&room_id)?;
+        room.clients.retain(|other| other.address != *client);
+        room.address
+            .do_send(inner::LooseClient::new(client.clone(), false));
+
+        self.close_room_if_empty(room_id);
+
+        Some(room_id)
+    }
+
+    // Room is kept while someone is in it or may come back
+    fn close_room_if_empty(&mut self, room_id: Uuid) {
+        let room = match self.rooms.get(&room_id) {
+            Some(room) => room,
+            None => return,
+        };
+        if room.clients.is_empty() && room.held.is_empty() {
+            room.address.do_send(inner::CloseRoom);
+            self.rooms.remove(&room_id);
+            info!("Room {} closed", room_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::memory::MemoryStorage;
+
+    fn lobby() -> Lobby {
+        Lobby::new(Arc::new(MemoryStorage::default()))
+    }
+
+    // Context of the lobby which is not running, handlers are called directly
+    fn context() -> Context<Lobby> {
+        let (_, receiver) = actix::dev::channel::channel(16);
+        Context::with_receiver(receiver)
+    }
+
+    fn request<T>(client: &inner::Client, payload: T) -> inner::Request<T> {
+        inner::Request::new(client.clone(), None, payload)
+    }
+
+    fn clients_in(lobby: &Lobby, room_id: Uuid) -> usize {
+        lobby.rooms[&room_id].clients.len()
+    }
+
+    #[actix_rt::test]
+    async fn create_and_join_room() {
+        let mut lobby = lobby();
+        let mut ctx = context();
+        let host = inner::Client::test();
+        let guest = inner::Client::test();
+
+        lobby.handle(request(&host, CreateRoom {}), &mut ctx);
+        let room_id = lobby.room_of(&host.address).unwrap();
+        assert_eq!(lobby.rooms.len(), 1);
+
+        let join = JoinRoom {
+            room_id: Some(room_id),
+        };
+        lobby.handle(request(&guest, join), &mut ctx);
+        assert_eq!(lobby.room_of(&guest.address), Some(room_id));
+        assert_eq!(clients_in(&lobby, room_id), 2);
+    }
+
+    #[actix_rt::test]
+    async fn join_not_existing_room() {
+        let mut lobby = lobby();
+        let mut ctx = context();
+        let client = inner::Client::test();
+
+        let join = JoinRoom {
+            room_id: Some(Uuid::new_v4()),
+        };
+        lobby.handle(request(&client, join), &mut ctx);
+        assert!(lobby.rooms.is_empty());
+        assert_eq!(lobby.room_of(&client.address), None);
+    }
+
+    #[actix_rt::test]
+    async fn join_waiting_room() {
+        let mut lobby = lobby();
+        let mut ctx = context();
+        let first = inner::Client::test();
+        let second = inner::Client::test();
+        let third = inner::Client::test();
+
+        // No room is waiting, so the new one is created
+        lobby.handle(request(&first, JoinRoom { room_id: None }), &mut ctx);
+        let room_id = lobby.room_of(&first.address).unwrap();
+
+        lobby.handle(request(&second, JoinRoom { room_id: None }), &mut ctx);
+        assert_eq!(lobby.room_of(&second.address), Some(room_id));
+
+        // Room with the running game doesn't wait for players
+        lobby.handle(inner::RoomState::new(room_id, true), &mut ctx);
+        lobby.handle(request(&third, JoinRoom { room_id: None }), &mut ctx);
+        let other_room = lobby.room_of(&third.address).unwrap();
+        assert_ne!(other_room, room_id);
+        assert_eq!(lobby.rooms.len(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn resumed_room_is_not_waiting() {
+        let mut lobby = lobby();
+        let mut ctx = context();
+        let (room, _) = actix::dev::channel::channel(16);
+        let mut resumed = Room::new(Addr::new(room));
+        resumed.resumed = true;
+        let resumed_id = Uuid::new_v4();
+        lobby.rooms.insert(resumed_id, resumed);
+
+        let client = inner::Client::test();
+        lobby.handle(request(&client, JoinRoom { room_id: None }), &mut ctx);
+        assert_ne!(lobby.room_of(&client.address), Some(resumed_id));
+        assert_eq!(lobby.rooms.len(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn client_is_in_one_room() {
+        let mut lobby = lobby();
+        let mut ctx = context();
+        let client = inner::Client::test();
+
+        lobby.handle(request(&client, CreateRoom {}), &mut ctx);
+        let first_room = lobby.room_of(&client.address).unwrap();
+        lobby.handle(request(&client, CreateRoom {}), &mut ctx);
+        let second_room = lobby.room_of(&client.address).unwrap();
+
+        // The first room became empty and was closed
+        assert_ne!(first_room, second_room);
+        assert_eq!(lobby.rooms.len(), 1);
+        assert!(lobby.rooms.contains_key(&second_room));
+    }
+
+    #[actix_rt::test]
+    async fn leave_room() {
+        let mut lobby = lobby();
+        let mut ctx = context();
+        let host = inner::Client::test();
+        let guest = inner::Client::test();
+
+        lobby.handle(request(&host, CreateRoom {}), &mut ctx);
+        let room_id = lobby.room_of(&host.address).unwrap();
+        let join = JoinRoom {
+            room_id: Some(room_id),
+        };
+        lobby.handle(request(&guest, join), &mut ctx);
+
+        lobby.handle(request(&host, LeaveRoom {}), &mut ctx);
+        assert_eq!(lobby.room_of(&host.address), None);
+        assert_eq!(clients_in(&lobby, room_id), 1);
+
+        // The last client closes the room
+        lobby.handle(request(&guest, LeaveRoom {}), &mut ctx);
+        assert!(lobby.rooms.is_empty());
+
+        // Nothing to leave now
+        lobby.handle(request(&guest, LeaveRoom {}), &mut ctx);
+        assert!(lobby.rooms.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn disconnect_before_game() {
+        let mut lobby = lobby();
+        let mut ctx = context();
+        let client = inner::Client::test();
+
+        lobby.handle(request(&client, CreateRoom {}), &mut ctx);
+        lobby.handle(inner::Disconnect::new(client.address.clone()), &mut ctx);
+        assert!(lobby.rooms.is_empty());
+    }
+
+    // Room where the game of two clients is running
+    fn started_room(lobby: &mut Lobby, ctx: &mut Context<Lobby>) -> (Uuid, inner::Client) {
+        let host = inner::Client::test();
+        let guest = inner::Client::test();
+        lobby.handle(request(&host, CreateRoom {}), ctx);
+        let room_id = lobby.room_of(&host.address).unwrap();
+        let join = JoinRoom {
+            room_id: Some(room_id),
+        };
+        lobby.handle(request(&guest, join), ctx);
+        lobby.handle(inner::RoomState::new(room_id, true), ctx);
+        (room_id, host)
+    }
+
+    #[actix_rt::test]
+    async fn reclaim_held_seat() {
+        let mut lobby = lobby();
+        let mut ctx = context();
+        let (room_id, host) = started_room(&mut lobby, &mut ctx);
+
+        lobby.handle(inner::Disconnect::new(host.address.clone()), &mut ctx);
+        assert_eq!(lobby.room_of(&host.address), None);
+        assert_eq!(
+            lobby.rooms[&room_id].held.get(&host.session),
+            Some(&host.user_id)
+        );
+
+        // The same session comes back with a new websocket
+        let reconnected =
+            inner::Client::new(inner::Client::test().address, host.user_id, host.session);
+        lobby.handle(inner::Connect::new(reconnected.clone()), &mut ctx);
+        assert_eq!(lobby.room_of(&reconnected.address), Some(room_id));
+        assert!(lobby.rooms[&room_id].held.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn held_seat_is_for_the_same_user() {
+        let mut lobby = lobby();
+        let mut ctx = context();
+        let (room_id, host) = started_room(&mut lobby, &mut ctx);
+        lobby.handle(inner::Disconnect::new(host.address.clone()), &mut ctx);
+
+        let stranger =
+            inner::Client::new(inner::Client::test().address, Uuid::new_v4(), host.session);
+        lobby.handle(inner::Connect::new(stranger.clone()), &mut ctx);
+        assert_eq!(lobby.room_of(&stranger.address), None);
+
+        let other_session =
+            inner::Client::new(inner::Client::test().address, host.user_id, Uuid::new_v4());
+        lobby.handle(inner::Connect::new(other_session.clone()), &mut ctx);
+        assert_eq!(lobby.room_of(&other_session.address), None);
+        assert_eq!(lobby.rooms[&room_id].held.len(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn held_seats_keep_room_until_game_ends() {
+        let mut lobby = lobby();
+        let mut ctx = context();
+        let (room_id, host) = started_room(&mut lobby, &mut ctx);
+        let guest = lobby.rooms[&room_id].clients[1].clone();
+
+        lobby.handle(inner::Disconnect::new(host.address.clone()), &mut ctx);
+        lobby.handle(inner::Disconnect::new(guest.address.clone()), &mut ctx);
+        assert!(lobby.rooms[&room_id].clients.is_empty());
+        assert_eq!(lobby.rooms[&room_id].held.len(), 2);
+
+        // Nobody can come back to the finished game
+        lobby.handle(inner::RoomState::new(room_id, false), &mut ctx);
+        assert!(lobby.rooms.is_empty());
+        lobby.handle(inner::Connect::new(host.clone()), &mut ctx);
+        assert_eq!(lobby.room_of(&host.address), None);
+    }
+}
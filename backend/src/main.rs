@@ -1,40 +1,44 @@
-use crate::config::CONFIG;
+use backend::config::CONFIG;
+use backend::{auth, database, errors, helpers, lobby, models, routes, websocket};
 
 use actix::{Actor, Addr};
 use actix_web::middleware::Logger;
 use actix_web::{web, App, Error, HttpRequest, HttpResponse, HttpServer};
 use actix_web_actors::ws;
 
-mod api;
-mod auth;
-mod communicator;
-mod config;
-mod database;
-mod errors;
-mod game;
-mod game_objects;
-mod game_server;
-mod handlers;
-mod helpers;
-mod models;
-mod routes;
-mod websocket;
-
-#[macro_use]
-extern crate lazy_static;
-
-#[macro_use]
-extern crate log;
-
 use tracing::instrument;
 
-#[instrument(skip(stream))]
+#[instrument(skip(stream, users))]
 async fn index(
     req: HttpRequest,
     stream: web::Payload,
-    data: web::Data<Addr<game_server::GameServer>>,
+    data: web::Data<Addr<lobby::Lobby>>,
+    users: web::Data<dyn database::Storage>,
 ) -> Result<HttpResponse, Error> {
-    ws::start(websocket::Websocket { server_addr: data }, &req, stream)
+    // Only logged in users can connect
+    let token = auth::get_token(&req)
+        .ok_or_else(|| errors::ApiError::Unauthorized("no token".to_string()))?;
+    let user_id = auth::decode_jwt(&token)?;
+    let user = web::block(move || models::user::find(&**users, user_id))
+        .await
+        .map_err(errors::ApiError::from)?;
+    if user.is_none() {
+        return Err(errors::ApiError::Unauthorized("no user".to_string()).into());
+    }
+
+    // Session is passed back by the client to take its seat again after reconnect
+    let session = helpers::query_param(&req, "session")
+        .and_then(|session| uuid::Uuid::parse_str(&session).ok())
+        .unwrap_or_else(uuid::Uuid::new_v4);
+
+    let websocket = websocket::Websocket {
+        user_id,
+        session,
+        lobby_addr: data,
+        room_addr: None,
+        replay: None,
+    };
+    ws::start(websocket, &req, stream)
 }
 
 #[actix_rt::main]
@@ -42,13 +46,18 @@ async fn index(
 async fn main() -> std::io::Result<()> {
     install_tracing();
     color_eyre::install().unwrap();
-    let data = web::Data::new(game_server::GameServer::new().start());
+    let storage = database::open_storage();
+    let data = web::Data::new(lobby::Lobby::new(storage.clone()).start());
     HttpServer::new(move || {
+        let storage = storage.clone();
         App::new()
             .app_data(data.clone())
             .route("/ws/", web::get().to(index))
-            .wrap(Logger::default())
-            .configure(database::add_user_storage)
+            // Default format without the query, it holds the token of websocket clients
+            .wrap(Logger::new(
+                r#"%a "%U" %s %b "%{Referer}i" "%{User-Agent}i" %T"#,
+            ))
+            .configure(|cfg| database::add_user_storage(cfg, storage))
             .configure(routes::routes)
     })
     .bind(CONFIG.address.clone())?
@@ -0,0 +1,67 @@
+use crate::database::Storage;
+use crate::errors::ApiError;
+use crate::models::rating;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Finished match
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchResult {
+    pub id: Uuid,
+    pub room_id: Uuid,
+    pub replay_id: Option<Uuid>,
+    // Users who played the match, bots are not included
+    pub players: Vec<Uuid>,
+    // None if bot won
+    pub winner: Option<Uuid>,
+    pub finished_at: NaiveDateTime,
+}
+
+// Save the match and update ratings of its players
+pub fn finish(storage: &dyn Storage, result: &MatchResult) -> Result<(), ApiError> {
+    storage.insert_match(result)?;
+
+    if let Some(winner) = result.winner {
+        let losers: Vec<Uuid> = result
+            .players
+            .iter()
+            .copied()
+            .filter(|player| *player != winner)
+            .collect();
+        rating::update(storage, winner, &losers)?;
+    }
+
+    Ok(())
+}
+
+pub fn get_for_user(storage: &dyn Storage, user_id: Uuid) -> Result<Vec<MatchResult>, ApiError> {
+    storage.get_matches(user_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::memory::MemoryStorage;
+    use chrono::Utc;
+
+    #[test]
+    fn finish_match() {
+        let storage = MemoryStorage::default();
+        let winner = Uuid::new_v4();
+        let loser = Uuid::new_v4();
+        let result = MatchResult {
+            id: Uuid::new_v4(),
+            room_id: Uuid::new_v4(),
+            replay_id: None,
+            players: vec![winner, loser],
+            winner: Some(winner),
+            finished_at: Utc::now().naive_utc(),
+        };
+
+        assert!(finish(&storage, &result).is_ok());
+        assert_eq!(get_for_user(&storage, loser).unwrap(), vec![result]);
+        assert!(rating::get(&storage, winner).unwrap().rating > rating::INITIAL_RATING);
+        assert!(rating::get(&storage, loser).unwrap().rating < rating::INITIAL_RATING);
+    }
+}
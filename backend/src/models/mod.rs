@@ -1 +1,3 @@
+pub mod match_result;
+pub mod rating;
 pub mod user;
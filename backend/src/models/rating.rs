@@ -0,0 +1,104 @@
+use crate::database::Storage;
+use crate::errors::ApiError;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const INITIAL_RATING: i32 = 1000;
+// Maximal change of the rating after one match
+const K_FACTOR: f64 = 32.0;
+
+// Elo rating of the user
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rating {
+    pub user_id: Uuid,
+    pub rating: i32,
+    pub games: u32,
+}
+
+impl Rating {
+    pub fn new(user_id: Uuid) -> Rating {
+        Rating {
+            user_id,
+            rating: INITIAL_RATING,
+            games: 0,
+        }
+    }
+}
+
+// Rating of the user who has not played yet is initial
+pub fn get(storage: &dyn Storage, user_id: Uuid) -> Result<Rating, ApiError> {
+    Ok(storage
+        .get_rating(user_id)?
+        .unwrap_or_else(|| Rating::new(user_id)))
+}
+
+// Winner plays against every loser, user in several seats plays once
+pub fn update(storage: &dyn Storage, winner: Uuid, losers: &[Uuid]) -> Result<(), ApiError> {
+    let mut users = vec![winner];
+    for loser in losers {
+        if !users.contains(loser) {
+            users.push(*loser);
+        }
+    }
+
+    storage.update_ratings(&users, &mut |ratings| {
+        let (winner_rating, loser_ratings) = ratings.split_first_mut().unwrap();
+        for loser_rating in loser_ratings {
+            let change = rating_change(winner_rating.rating, loser_rating.rating);
+
+            winner_rating.rating += change;
+            loser_rating.rating -= change;
+            loser_rating.games += 1;
+        }
+        winner_rating.games += 1;
+    })
+}
+
+// Points winner gets from loser
+fn rating_change(winner: i32, loser: i32) -> i32 {
+    let expected = 1.0 / (1.0 + 10f64.powf((loser - winner) as f64 / 400.0));
+    (K_FACTOR * (1.0 - expected)).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::memory::MemoryStorage;
+
+    #[test]
+    fn change_of_equal_ratings() {
+        assert_eq!(rating_change(1000, 1000), 16);
+    }
+
+    #[test]
+    fn change_of_different_ratings() {
+        // Win over stronger player gives more
+        assert!(rating_change(1000, 1200) > rating_change(1200, 1000));
+        assert!(rating_change(1200, 1000) > 0);
+    }
+
+    #[test]
+    fn update_ratings() {
+        let storage = MemoryStorage::default();
+        let winner = Uuid::new_v4();
+        let losers = [Uuid::new_v4(), Uuid::new_v4()];
+
+        assert!(update(&storage, winner, &losers).is_ok());
+
+        let winner_rating = get(&storage, winner).unwrap();
+        assert_eq!(winner_rating.rating, INITIAL_RATING + 16 + 15);
+        assert_eq!(winner_rating.games, 1);
+        for loser in losers.iter() {
+            let loser_rating = get(&storage, *loser).unwrap();
+            assert!(loser_rating.rating < INITIAL_RATING);
+            assert_eq!(loser_rating.games, 1);
+        }
+    }
+
+    #[test]
+    fn not_played_user() {
+        let storage = MemoryStorage::default();
+        let user_id = Uuid::new_v4();
+        assert_eq!(get(&storage, user_id).unwrap(), Rating::new(user_id));
+    }
+}
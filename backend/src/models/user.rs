@@ -1,9 +1,13 @@
 use crate::auth::hash;
-use crate::database::UserStorage;
+use crate::database::Storage;
+use crate::errors::ApiError;
 use chrono::{NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+const HANDLE_LENGTH: (usize, usize) = (3, 32);
+const MIN_PASSWORD_LENGTH: usize = 6;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: String,
@@ -13,6 +17,23 @@ pub struct User {
     pub created_at: NaiveDateTime,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewUser {
+    pub handle: String,
+    pub email: String,
+    pub password: String,
+}
+
+// Fields to change, not set ones stay the same
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateUser {
+    pub handle: Option<String>,
+    pub email: Option<String>,
+    pub password: Option<String>,
+}
+
+// Fixture user for tests
+#[cfg(test)]
 pub fn create_random_user() -> User {
     User {
         id: Uuid::new_v4().to_string(),
@@ -23,17 +44,239 @@ pub fn create_random_user() -> User {
     }
 }
 
-pub fn find(storage: &UserStorage, id: Uuid) -> Option<User> {
-    match storage
-        .0
-        .iter()
-        .position(|user| user.id.eq(&id.to_string()))
+pub fn find(storage: &dyn Storage, id: Uuid) -> Result<Option<User>, ApiError> {
+    storage.find_user(id)
+}
+
+pub fn find_by_email(storage: &dyn Storage, email: &str) -> Result<Option<User>, ApiError> {
+    storage.find_user_by_email(email)
+}
+
+pub fn get_all(storage: &dyn Storage) -> Result<Vec<User>, ApiError> {
+    storage.get_users()
+}
+
+pub fn create(storage: &dyn Storage, new_user: NewUser) -> Result<User, ApiError> {
+    validate_handle(&new_user.handle)?;
+    validate_email(&new_user.email)?;
+    validate_password(&new_user.password)?;
+
+    let user = User {
+        id: Uuid::new_v4().to_string(),
+        handle: new_user.handle,
+        email: new_user.email,
+        password: hash(&new_user.password),
+        created_at: Utc::now().naive_utc(),
+    };
+    storage.insert_user(&user)?;
+    Ok(user)
+}
+
+pub fn update(storage: &dyn Storage, id: Uuid, update_user: UpdateUser) -> Result<User, ApiError> {
+    if let Some(handle) = &update_user.handle {
+        validate_handle(handle)?;
+    }
+    if let Some(email) = &update_user.email {
+        validate_email(email)?;
+    }
+    if let Some(password) = &update_user.password {
+        validate_password(password)?;
+    }
+
+    let mut user = storage
+        .find_user(id)?
+        .ok_or_else(|| ApiError::NotFound("user".to_string()))?;
+    if let Some(handle) = update_user.handle {
+        user.handle = handle;
+    }
+    if let Some(email) = update_user.email {
+        user.email = email;
+    }
+    if let Some(password) = update_user.password {
+        user.password = hash(&password);
+    }
+    storage.update_user(&user)?;
+    Ok(user)
+}
+
+pub fn delete(storage: &dyn Storage, id: Uuid) -> Result<(), ApiError> {
+    storage.delete_user(id)
+}
+
+fn validate_handle(handle: &str) -> Result<(), ApiError> {
+    let length = handle.chars().count();
+    if length < HANDLE_LENGTH.0 || length > HANDLE_LENGTH.1 {
+        return Err(ApiError::BadRequest(format!(
+            "handle must be from {} to {} characters long",
+            HANDLE_LENGTH.0, HANDLE_LENGTH.1
+        )));
+    }
+    if !handle
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
     {
-        Some(pos) => Some(storage.0[pos].clone()),
-        None => None,
+        return Err(ApiError::BadRequest(
+            "handle may contain only letters, digits, '_' and '-'".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_email(email: &str) -> Result<(), ApiError> {
+    let valid = match email.split_once('@') {
+        Some((name, domain)) => {
+            !name.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !domain.contains('@')
+                && !email.contains(char::is_whitespace)
+        }
+        None => false,
+    };
+    if !valid {
+        return Err(ApiError::BadRequest("wrong email".to_string()));
+    }
+    Ok(())
+}
+
+fn validate_password(password: &str) -> Result<(), ApiError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(ApiError::BadRequest(format!(
+            "password must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        )));
     }
+    Ok(())
 }
 
-pub fn get_all(storage: &UserStorage) -> Vec<User> {
-    storage.0.clone()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::memory::MemoryStorage;
+
+    fn new_user(handle: &str, email: &str) -> NewUser {
+        NewUser {
+            handle: handle.to_string(),
+            email: email.to_string(),
+            password: "password".to_string(),
+        }
+    }
+
+    fn test_storage() -> (MemoryStorage, User) {
+        let storage = MemoryStorage::default();
+        let user = create(&storage, new_user("player", "player@test.com")).unwrap();
+        (storage, user)
+    }
+
+    #[test]
+    fn create_user() {
+        let (storage, user) = test_storage();
+
+        assert_eq!(user.handle, "player");
+        assert_eq!(user.email, "player@test.com");
+        assert_eq!(user.password, hash("password"));
+        let id = Uuid::parse_str(&user.id).unwrap();
+        assert_eq!(find(&storage, id).unwrap().unwrap().handle, "player");
+    }
+
+    #[test]
+    fn create_user_with_taken_handle() {
+        let (storage, _) = test_storage();
+        let result = create(&storage, new_user("player", "other@test.com"));
+        assert!(matches!(result, Err(ApiError::Conflict(_))));
+    }
+
+    #[test]
+    fn create_user_with_taken_email() {
+        let (storage, _) = test_storage();
+        let result = create(&storage, new_user("other", "player@test.com"));
+        assert!(matches!(result, Err(ApiError::Conflict(_))));
+    }
+
+    #[test]
+    fn create_wrong_user() {
+        let storage = MemoryStorage::default();
+        for user in [
+            new_user("pl", "player@test.com"),
+            new_user("play er", "player@test.com"),
+            new_user("player", "player.test.com"),
+            new_user("player", "@test.com"),
+            new_user("player", "player@test"),
+            NewUser {
+                password: "short".to_string(),
+                ..new_user("player", "player@test.com")
+            },
+        ]
+        .iter()
+        {
+            let result = create(&storage, user.clone());
+            assert!(matches!(result, Err(ApiError::BadRequest(_))), "{:?}", user);
+        }
+        assert!(get_all(&storage).unwrap().is_empty());
+    }
+
+    #[test]
+    fn update_user() {
+        let (storage, user) = test_storage();
+        let id = Uuid::parse_str(&user.id).unwrap();
+
+        let updated = update(
+            &storage,
+            id,
+            UpdateUser {
+                handle: Some("new_player".to_string()),
+                password: Some("new_password".to_string()),
+                ..UpdateUser::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(updated.handle, "new_player");
+        assert_eq!(updated.email, "player@test.com");
+        assert_eq!(updated.password, hash("new_password"));
+        assert_eq!(find(&storage, id).unwrap().unwrap().handle, "new_player");
+    }
+
+    #[test]
+    fn update_user_with_own_handle() {
+        let (storage, user) = test_storage();
+        let id = Uuid::parse_str(&user.id).unwrap();
+
+        let update_user = UpdateUser {
+            handle: Some("player".to_string()),
+            ..UpdateUser::default()
+        };
+        assert!(update(&storage, id, update_user).is_ok());
+    }
+
+    #[test]
+    fn update_user_with_taken_email() {
+        let (storage, _) = test_storage();
+        let other = create(&storage, new_user("other", "other@test.com")).unwrap();
+
+        let update_user = UpdateUser {
+            email: Some("player@test.com".to_string()),
+            ..UpdateUser::default()
+        };
+        let result = update(&storage, Uuid::parse_str(&other.id).unwrap(), update_user);
+        assert!(matches!(result, Err(ApiError::Conflict(_))));
+    }
+
+    #[test]
+    fn update_not_existing_user() {
+        let (storage, _) = test_storage();
+        let result = update(&storage, Uuid::new_v4(), UpdateUser::default());
+        assert!(matches!(result, Err(ApiError::NotFound(_))));
+    }
+
+    #[test]
+    fn delete_user() {
+        let (storage, user) = test_storage();
+        let id = Uuid::parse_str(&user.id).unwrap();
+
+        assert!(delete(&storage, id).is_ok());
+        assert!(find(&storage, id).unwrap().is_none());
+        assert!(matches!(delete(&storage, id), Err(ApiError::NotFound(_))));
+    }
 }
@@ -0,0 +1,447 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+use crate::api::common::Point;
+use crate::game::Game;
+use crate::game_objects::grid::Grid;
+use crate::game_objects::hex_objects::content::Content;
+use crate::game_objects::hex_objects::wall::Wall;
+use crate::game_objects::kind::Kind;
+use crate::game_objects::unit::Unit;
+
+use eyre::{Result, WrapErr};
+
+#[derive(Error, Serialize, Debug, PartialEq)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum PresetError {
+    #[error("no preset with name {name}")]
+    NotFound { name: String },
+
+    #[error("field is empty")]
+    EmptyField,
+
+    #[error("point {point:?} is out of grid")]
+    OutOfGrid { point: Point },
+
+    #[error("more than one object in point {point:?}")]
+    Overlap { point: Point },
+
+    #[error("minimal damage is greater than maximal in point {point:?}")]
+    WrongDamage { point: Point },
+
+    #[error("unit without hp in point {point:?}")]
+    ZeroHp { point: Point },
+
+    #[error("players must be numbered from 0 without gaps, but there is no player {player}")]
+    NoPlayer { player: u32 },
+
+    #[error("at least 2 players needed")]
+    NotEnoughPlayers,
+
+    #[error("{clients} clients needed to play the preset")]
+    NotEnoughClients { clients: usize },
+
+    #[error("player {player} has more than {max} units of kind {kind:?}")]
+    TooManyUnits { player: u32, kind: Kind, max: u8 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnitPlacement {
+    pub x: u32,
+    pub y: u32,
+    pub player: u32,
+    #[serde(default)]
+    pub kind: Kind,
+    pub hp: u32,
+    pub damage: [u32; 2],
+    pub speed: u32,
+    // Sight and range of the kind are used when they are not set
+    #[serde(default)]
+    pub sight: Option<u32>,
+    #[serde(default)]
+    pub range: Option<u32>,
+}
+
+// Map described in the <presets_dir>/<name>.json file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub num_x: u32,
+    pub num_y: u32,
+    #[serde(default)]
+    pub walls: Vec<Point>,
+    pub units: Vec<UnitPlacement>,
+}
+
+impl Preset {
+    pub fn load(dir: &str, name: &str) -> Result<Preset> {
+        // Name is a part of the path, so we don't let it go outside of dir
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            Err(PresetError::NotFound {
+                name: name.to_string(),
+            })
+            .wrap_err("load preset")?;
+        }
+
+        let path = Path::new(dir).join(format!("{}.json", name));
+        if !path.exists() {
+            Err(PresetError::NotFound {
+                name: name.to_string(),
+            })
+            .wrap_err("load preset")?;
+        }
+
+        let string = fs::read_to_string(&path).wrap_err_with(|| format!("read {:?}", path))?;
+        serde_json::from_str(&string).wrap_err_with(|| format!("deserialize preset {:?}", path))
+    }
+
+    pub fn num_of_players(&self) -> usize {
+        self.units
+            .iter()
+            .map(|unit| unit.player)
+            .collect::<HashSet<u32>>()
+            .len()
+    }
+
+    // Check that game can be created from this preset
+    pub fn validate(&self) -> Result<()> {
+        if self.num_x == 0 || self.num_y == 0 {
+            Err(PresetError::EmptyField).wrap_err("validate preset")?;
+        }
+
+        let mut occupied = HashSet::new();
+        let points = self
+            .walls
+            .iter()
+            .copied()
+            .chain(self.units.iter().map(|unit| Point {
+                x: unit.x,
+                y: unit.y,
+            }));
+        for point in points {
+            if point.x >= self.num_x || point.y >= self.num_y {
+                Err(PresetError::OutOfGrid { point }).wrap_err("validate preset")?;
+            }
+            if !occupied.insert(point) {
+                Err(PresetError::Overlap { point }).wrap_err("validate preset")?;
+            }
+        }
+
+        for unit in &self.units {
+            let point = Point {
+                x: unit.x,
+                y: unit.y,
+            };
+            if unit.hp == 0 {
+                Err(PresetError::ZeroHp { point }).wrap_err("validate preset")?;
+            }
+            if unit.damage[0] > unit.damage[1] {
+                Err(PresetError::WrongDamage { point }).wrap_err("validate preset")?;
+            }
+        }
+
+        for unit in &self.units {
+            let max = match unit.kind.stats().max_per_army {
+                Some(max) => max,
+                None => continue,
+            };
+            let count = self
+                .units
+                .iter()
+                .filter(|other| other.player == unit.player && other.kind == unit.kind)
+                .count();
+            if count > max as usize {
+                Err(PresetError::TooManyUnits {
+                    player: unit.player,
+                    kind: unit.kind,
+                    max,
+                })
+                .wrap_err("validate preset")?;
+            }
+        }
+
+        let num_of_players = self.num_of_players();
+        if num_of_players < 2 {
+            Err(PresetError::NotEnoughPlayers).wrap_err("validate preset")?;
+        }
+        for player in 0..num_of_players as u32 {
+            if !self.units.iter().any(|unit| unit.player == player) {
+                Err(PresetError::NoPlayer { player }).wrap_err("validate preset")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_game(&self, seed: u32) -> Result<Game> {
+        self.validate()?;
+
+        let mut game = Game::with_field(Grid::new(self.num_x, self.num_y), seed);
+        for wall in &self.walls {
+            game.set_content(wall.x, wall.y, Some(Content::Wall(Wall {})))
+                .wrap_err("set wall from preset")?;
+        }
+        for unit in &self.units {
+            let stats = unit.kind.stats();
+            let new_unit = Unit {
+                kind: unit.kind,
+                sight: unit.sight.unwrap_or(stats.sight),
+                range: unit.range.unwrap_or(stats.range),
+                ..Unit::new(unit.player, unit.hp, unit.damage, unit.speed)
+            };
+            game.set_unit(unit.x, unit.y, Some(new_unit))
+                .wrap_err("set unit from preset")?;
+        }
+
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unit(x: u32, y: u32, player: u32) -> UnitPlacement {
+        UnitPlacement {
+            x,
+            y,
+            player,
+            kind: Kind::Infantry,
+            hp: 5,
+            damage: [1, 3],
+            speed: 2,
+            sight: None,
+            range: None,
+        }
+    }
+
+    // 3x3 field with wall in the center and units in the corners
+    fn test_preset() -> Preset {
+        Preset {
+            num_x: 3,
+            num_y: 3,
+            walls: vec![Point { x: 1, y: 1 }],
+            units: vec![unit(0, 0, 0), unit(2, 2, 1)],
+        }
+    }
+
+    fn assert_error(preset: Preset, error: PresetError) {
+        let result = preset.to_game(0);
+        assert!(result.is_err());
+        assert_eq!(
+            error,
+            *result.unwrap_err().downcast_ref::<PresetError>().unwrap()
+        );
+    }
+
+    #[test]
+    fn deserialize() {
+        let preset: Preset = serde_json::from_str(
+            "{\"num_x\":3,\"num_y\":3,\"walls\":[{\"x\":1,\"y\":1}],\"units\":[\
+             {\"x\":0,\"y\":0,\"player\":0,\"hp\":5,\"damage\":[1,3],\"speed\":2},\
+             {\"x\":2,\"y\":2,\"player\":1,\"hp\":5,\"damage\":[1,3],\"speed\":2}]}",
+        )
+        .unwrap();
+
+        assert_eq!(preset, test_preset());
+    }
+
+    #[test]
+    fn to_game() {
+        let mut game = test_preset().to_game(3).unwrap();
+
+        assert_eq!(game.seed, 3);
+        assert_eq!(game.field.num_x, 3);
+        assert_eq!(game.field.num_y, 3);
+        assert_eq!(game.get_unit(0, 0).unwrap().unwrap().player, 0);
+        assert_eq!(game.get_unit(2, 2).unwrap().unwrap().player, 1);
+        assert_eq!(game.get_unit(2, 2).unwrap().unwrap().damage, [1, 3]);
+        assert!(game.get_unit(1, 1).unwrap().is_none());
+        assert!(game.field.get_hex(1, 1).unwrap().content.is_some());
+    }
+
+    #[test]
+    fn empty_field() {
+        let preset = Preset {
+            num_x: 0,
+            ..test_preset()
+        };
+        assert_error(preset, PresetError::EmptyField);
+    }
+
+    #[test]
+    fn unit_out_of_grid() {
+        let mut preset = test_preset();
+        preset.units.push(unit(3, 0, 1));
+        assert_error(
+            preset,
+            PresetError::OutOfGrid {
+                point: Point { x: 3, y: 0 },
+            },
+        );
+    }
+
+    #[test]
+    fn wall_out_of_grid() {
+        let mut preset = test_preset();
+        preset.walls.push(Point { x: 0, y: 5 });
+        assert_error(
+            preset,
+            PresetError::OutOfGrid {
+                point: Point { x: 0, y: 5 },
+            },
+        );
+    }
+
+    #[test]
+    fn unit_on_unit() {
+        let mut preset = test_preset();
+        preset.units.push(unit(0, 0, 1));
+        assert_error(
+            preset,
+            PresetError::Overlap {
+                point: Point { x: 0, y: 0 },
+            },
+        );
+    }
+
+    #[test]
+    fn unit_on_wall() {
+        let mut preset = test_preset();
+        preset.units.push(unit(1, 1, 1));
+        assert_error(
+            preset,
+            PresetError::Overlap {
+                point: Point { x: 1, y: 1 },
+            },
+        );
+    }
+
+    #[test]
+    fn wrong_damage() {
+        let mut preset = test_preset();
+        preset.units[0].damage = [3, 1];
+        assert_error(
+            preset,
+            PresetError::WrongDamage {
+                point: Point { x: 0, y: 0 },
+            },
+        );
+    }
+
+    #[test]
+    fn zero_hp() {
+        let mut preset = test_preset();
+        preset.units[1].hp = 0;
+        assert_error(
+            preset,
+            PresetError::ZeroHp {
+                point: Point { x: 2, y: 2 },
+            },
+        );
+    }
+
+    #[test]
+    fn kind_stats_by_default() {
+        let mut preset = test_preset();
+        preset.units[0].kind = Kind::Archer;
+        preset.units[1].range = Some(2);
+        let mut game = preset.to_game(0).unwrap();
+
+        let archer = game.get_unit(0, 0).unwrap().unwrap();
+        assert_eq!(archer.kind, Kind::Archer);
+        assert_eq!(archer.range, Kind::Archer.stats().range);
+        assert_eq!(archer.sight, Kind::Archer.stats().sight);
+        assert_eq!(game.get_unit(2, 2).unwrap().unwrap().range, 2);
+    }
+
+    #[test]
+    fn too_many_units_of_kind() {
+        let mut preset = test_preset();
+        preset.units[0].kind = Kind::Healer;
+        preset.units.push(UnitPlacement {
+            kind: Kind::Healer,
+            ..unit(1, 0, 0)
+        });
+        assert_error(
+            preset,
+            PresetError::TooManyUnits {
+                player: 0,
+                kind: Kind::Healer,
+                max: 1,
+            },
+        );
+    }
+
+    #[test]
+    fn one_player() {
+        let mut preset = test_preset();
+        preset.units[1].player = 0;
+        assert_error(preset, PresetError::NotEnoughPlayers);
+    }
+
+    #[test]
+    fn players_with_gap() {
+        let mut preset = test_preset();
+        preset.units[1].player = 2;
+        assert_error(preset, PresetError::NoPlayer { player: 1 });
+    }
+
+    #[test]
+    fn load_all_presets() {
+        for entry in fs::read_dir("presets").unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_stem().unwrap().to_str().unwrap();
+
+            let preset = Preset::load("presets", name).unwrap();
+            assert!(preset.to_game(0).is_ok(), "wrong preset {}", name);
+        }
+    }
+
+    #[test]
+    fn load_not_existing() {
+        let result = Preset::load("presets", "no_such_preset");
+        assert!(result.is_err());
+        assert_eq!(
+            PresetError::NotFound {
+                name: "no_such_preset".to_string(),
+            },
+            *result.unwrap_err().downcast_ref::<PresetError>().unwrap()
+        );
+    }
+
+    #[test]
+    fn load_outside_of_dir() {
+        let result = Preset::load("presets", "../config");
+        assert!(result.is_err());
+        assert_eq!(
+            PresetError::NotFound {
+                name: "../config".to_string(),
+            },
+            *result.unwrap_err().downcast_ref::<PresetError>().unwrap()
+        );
+    }
+
+    #[test]
+    fn error_details() {
+        let details = serde_json::to_value(PresetError::OutOfGrid {
+            point: Point { x: 3, y: 0 },
+        })
+        .unwrap();
+        assert_eq!(details["error"], "out_of_grid");
+        assert_eq!(details["point"]["x"], 3);
+
+        let details = serde_json::to_value(PresetError::NotFound {
+            name: "../config".to_string(),
+        })
+        .unwrap();
+        assert_eq!(details["error"], "not_found");
+        assert_eq!(details["name"], "../config");
+    }
+}
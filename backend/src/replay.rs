@@ -0,0 +1,339 @@
+use actix::Addr;
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::communicator;
+use crate::websocket::Websocket;
+
+use crate::api::common::Point;
+use crate::api::response::{Attacking, Die, End, EndState, Field, Hurt, Moving, State, Update};
+use crate::game::{Action, Game};
+use crate::game_objects::grid::Grid;
+
+use eyre::{Result, WrapErr};
+
+// Increase it on every incompatible change of the replay format
+pub const REPLAY_VERSION: u32 = 1;
+
+const STATE_WATCH: &str = "watch";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ReplayError {
+    #[error("no replay with id {0}")]
+    NotFound(Uuid),
+
+    #[error("unsupported replay version {0}")]
+    WrongVersion(u32),
+}
+
+// Player's command accepted by game server
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "cmd")]
+pub enum Event {
+    #[serde(rename = "click")]
+    Click { player: u32, target: Point },
+    #[serde(rename = "skip_turn")]
+    SkipTurn { player: u32 },
+    // Player ran out of time too many times and lost all units
+    #[serde(rename = "forfeit")]
+    Forfeit { player: u32 },
+    // Player gave up, it may happen in the turn of another player
+    #[serde(rename = "surrender")]
+    Surrender { player: u32 },
+}
+
+// Everything needed to play the match again
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    pub version: u32,
+    pub id: Uuid,
+    pub num_of_players: usize,
+    pub seed: u32,
+    // Initial field
+    pub field: Grid,
+    // Defenders strike back, see Game
+    #[serde(default)]
+    pub retaliation: bool,
+    pub events: Vec<Event>,
+}
+
+impl Replay {
+    pub fn new(num_of_players: usize, game: &Game) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            id: Uuid::new_v4(),
+            num_of_players,
+            seed: game.seed,
+            field: game.field.clone(),
+            retaliation: game.retaliation,
+            events: vec![],
+        }
+    }
+
+    pub fn record(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    // Save replay to the <dir>/<id>.json file and return path to it
+    pub fn save(&self, dir: &str) -> Result<PathBuf> {
+        fs::create_dir_all(dir).wrap_err_with(|| format!("create directory {}", dir))?;
+
+        let path = Path::new(dir).join(format!("{}.json", self.id));
+        let string = serde_json::to_string(self).wrap_err("serialize replay")?;
+        fs::write(&path, string).wrap_err_with(|| format!("write {:?}", path))?;
+
+        Ok(path)
+    }
+
+    pub fn load(dir: &str, id: Uuid) -> Result<Replay> {
+        let path = Path::new(dir).join(format!("{}.json", id));
+        if !path.exists() {
+            Err(ReplayError::NotFound(id)).wrap_err("load replay")?;
+        }
+        let string = fs::read_to_string(&path).wrap_err_with(|| format!("read {:?}", path))?;
+        let replay: Replay = serde_json::from_str(&string).wrap_err("deserialize replay")?;
+
+        if replay.version != REPLAY_VERSION {
+            Err(ReplayError::WrongVersion(replay.version)).wrap_err("load replay")?;
+        }
+
+        Ok(replay)
+    }
+
+    // Ids of all replays saved in dir
+    pub fn list(dir: &str) -> Result<Vec<Uuid>> {
+        if !Path::new(dir).exists() {
+            return Ok(vec![]);
+        }
+
+        let mut ids = vec![];
+        for entry in fs::read_dir(dir).wrap_err_with(|| format!("read directory {}", dir))? {
+            let path = entry.wrap_err("read directory entry")?.path();
+            if let Some(id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| Uuid::parse_str(stem).ok())
+            {
+                ids.push(id);
+            }
+        }
+
+        Ok(ids)
+    }
+}
+
+// Plays replay to the spectator turn by turn
+#[derive(Debug)]
+pub struct ReplayPlayer {
+    game: Game,
+    events: Vec<Event>,
+    // Index of the next event to play
+    position: usize,
+    spectator: Addr<Websocket>,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay, spectator: Addr<Websocket>) -> ReplayPlayer {
+        let mut game = Game::with_field(replay.field, replay.seed);
+        game.retaliation = replay.retaliation;
+        ReplayPlayer {
+            game,
+            events: replay.events,
+            position: 0,
+            spectator,
+        }
+    }
+
+    // Send initial field to the spectator
+    pub fn start(&self) {
+        self.send(State::new(STATE_WATCH.to_string()));
+        self.send(Field::new(&self.game));
+    }
+
+    // Play events until the end of the current turn
+    pub fn step(&mut self) {
+        while let Some(event) = self.events.get(self.position).copied() {
+            self.position += 1;
+
+            let turn_ended = match event {
+                Event::Click { player, target } => self.click(player, target),
+                Event::SkipTurn { player } => {
+                    self.end_turn(player);
+                    true
+                }
+                Event::Forfeit { player } => {
+                    let removed = self.game.remove_player(player);
+                    self.send(Die::new(removed));
+                    self.end_turn(player);
+                    true
+                }
+                Event::Surrender { player } => {
+                    let removed = self.game.remove_player(player);
+                    self.send(Die::new(removed));
+                    false
+                }
+            };
+
+            if turn_ended {
+                break;
+            }
+        }
+
+        if self.finished() {
+            self.send(End::new(EndState::Finished));
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.position >= self.events.len()
+    }
+
+    // Repeat what game server did on this click
+    // Return true if click ended the turn
+    fn click(&mut self, player: u32, target: Point) -> bool {
+        let action = match self.game.get_current_action(target, player) {
+            Ok(action) => action,
+            Err(error) => {
+                debug!("{:?}", error.wrap_err("replay click"));
+                return false;
+            }
+        };
+
+        if let Err(error) = match action {
+            Action::Deselect => {
+                self.game.deselect_unit();
+                Ok(false)
+            }
+            Action::Select => self.game.select_unit(target).map(|_| false),
+            Action::Move => self.move_unit(target).map(|_| false),
+            Action::Attack => self.attack_unit(player, target).map(|_| true),
+        } {
+            debug!("{:?}", error.wrap_err("replay click"));
+            return false;
+        }
+
+        matches!(action, Action::Attack)
+    }
+
+    fn move_unit(&mut self, to: Point) -> Result<()> {
+        let path = self.game.move_unit(to).wrap_err("move unit")?;
+        let hexes = self
+            .game
+            .hexes_from_points(path)
+            .wrap_err("hexes from point")?;
+        self.send(Moving::new(hexes));
+
+        self.game
+            .select_unit(to)
+            .wrap_err("select unit after move")?;
+
+        Ok(())
+    }
+
+    fn attack_unit(&mut self, player: u32, to: Point) -> Result<()> {
+        let combat = self.game.attack(to).wrap_err("attack")?;
+        let from = self.game.selected_hex.unwrap();
+
+        self.send(Attacking::new(from.to_point(), to, &combat));
+        self.send(Hurt::new(combat.hurt));
+        self.send(Die::new(combat.die));
+        self.end_turn(player);
+
+        Ok(())
+    }
+
+    fn end_turn(&mut self, player: u32) {
+        let hexes_to_change = self.game.restore_movements(player);
+        self.send(Update::new(hexes_to_change));
+        self.game.deselect_unit();
+    }
+
+    fn send<T: Serialize>(&self, msg: T) {
+        communicator::broadcast(&msg, vec![self.spectator.clone()]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn serialize_event() {
+        let click = Event::Click {
+            player: 1,
+            target: Point { x: 2, y: 3 },
+        };
+        let skip_turn = Event::SkipTurn { player: 0 };
+
+        assert_eq!(
+            serde_json::to_string(&click).unwrap(),
+            "{\"cmd\":\"click\",\"player\":1,\"target\":{\"x\":2,\"y\":3}}"
+        );
+        assert_eq!(
+            serde_json::to_string(&skip_turn).unwrap(),
+            "{\"cmd\":\"skip_turn\",\"player\":0}"
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("replays-{}", Uuid::new_v4()));
+        let dir = dir.to_str().unwrap();
+
+        let game = Game::random(2, 7, Settings::default()).unwrap();
+        let mut replay = Replay::new(2, &game);
+        replay.record(Event::Click {
+            player: 0,
+            target: Point { x: 0, y: 0 },
+        });
+        replay.record(Event::SkipTurn { player: 0 });
+
+        assert!(replay.save(dir).is_ok());
+        assert_eq!(Replay::list(dir).unwrap(), vec![replay.id]);
+
+        let loaded = Replay::load(dir, replay.id).unwrap();
+        assert_eq!(loaded.id, replay.id);
+        assert_eq!(loaded.seed, 7);
+        assert_eq!(
+            serde_json::to_string(&loaded.field).unwrap(),
+            serde_json::to_string(&replay.field).unwrap()
+        );
+        assert_eq!(loaded.events, replay.events);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn load_wrong_version() {
+        let dir = std::env::temp_dir().join(format!("replays-{}", Uuid::new_v4()));
+        let dir = dir.to_str().unwrap();
+
+        let mut replay = Replay::new(2, &Game::random(2, 7, Settings::default()).unwrap());
+        replay.version = REPLAY_VERSION + 1;
+        assert!(replay.save(dir).is_ok());
+
+        let result = Replay::load(dir, replay.id);
+        assert!(result.is_err());
+        assert_eq!(
+            ReplayError::WrongVersion(REPLAY_VERSION + 1),
+            *result.unwrap_err().downcast_ref::<ReplayError>().unwrap()
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn load_not_existing() {
+        let id = Uuid::new_v4();
+        let result = Replay::load(std::env::temp_dir().to_str().unwrap(), id);
+        assert_eq!(
+            ReplayError::NotFound(id),
+            *result.unwrap_err().downcast_ref::<ReplayError>().unwrap()
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::handlers::{
+    auth::login,
     health::get_health,
-    user::{get_user, get_users},
+    user::{create_user, delete_user, get_matches, get_rating, get_user, get_users, update_user},
 };
 
 use actix_web::web;
@@ -11,11 +12,18 @@ pub fn routes(cfg: &mut web::ServiceConfig) {
         .route("/health", web::get().to(get_health))
         .service(
             web::scope("/api/v1")
+                // AUTH routes
+                .service(web::scope("/auth").route("/login", web::post().to(login)))
                 // USER roles
                 .service(
                     web::scope("/user")
+                        .route("/{id}/rating", web::get().to(get_rating))
+                        .route("/{id}/matches", web::get().to(get_matches))
                         .route("/{id}", web::get().to(get_user))
-                        .route("", web::get().to(get_users)),
+                        .route("/{id}", web::patch().to(update_user))
+                        .route("/{id}", web::delete().to(delete_user))
+                        .route("", web::get().to(get_users))
+                        .route("", web::post().to(create_user)),
                 ),
         );
 }
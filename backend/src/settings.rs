@@ -0,0 +1,562 @@
+use rand::distributions::uniform::SampleUniform;
+use rand::distributions::Uniform;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use eyre::Result;
+
+use crate::game_objects::kind::Kind;
+
+// Ranges [min, max] for random game generation
+const NUM_X: (u32, u32) = (5, 15);
+const NUM_Y: (u32, u32) = (5, 15);
+const WALLS_PERCENT: (u8, u8) = (0, 40);
+const TERRAIN_PERCENT: (u8, u8) = (0, 30);
+const NUM_UNITS: (u8, u8) = (2, 6);
+const UNIT_HP: (u8, u8) = (1, 10);
+const UNIT_MIN_DAMAGE: (u8, u8) = (1, 5);
+const UNIT_DAMAGE_INTERVAL: (u8, u8) = (1, 5);
+const UNIT_SPEED: (u8, u8) = (1, 8);
+
+// Maximal width and height of the field
+const MAX_FIELD_SIDE: u32 = 100;
+// Maximal units of one player and their stats
+const MAX_NUM_UNITS: u8 = 50;
+const MAX_UNIT_STAT: u8 = 100;
+
+#[derive(Error, Serialize, Debug, PartialEq)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum SettingsError {
+    #[error("minimum of {field} is greater than maximum")]
+    WrongRange { field: String },
+
+    #[error("minimum of {field} must be positive")]
+    NotPositive { field: String },
+
+    #[error("field side can not be greater than {max}")]
+    FieldTooBig { max: u32 },
+
+    #[error("maximum of {field} can not be greater than {max}")]
+    TooBig { field: String, max: u8 },
+
+    #[error("walls percent can not be greater than 100")]
+    TooManyWalls,
+
+    #[error("walls and terrain can not take more than 100 percent")]
+    TooMuchTerrain,
+
+    #[error("{hexes} free hexes are not enough for {units} units")]
+    NotEnoughHexes { hexes: u32, units: u32 },
+
+    #[error("army can not have more than {max} units of kind {kind:?}")]
+    TooManyUnits { kind: Kind, max: u8 },
+
+    #[error("symmetric field is only for 2 players")]
+    SymmetryForTwoPlayers,
+}
+
+// Symmetry of the random field, so neither player gets the better half
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Symmetry {
+    #[default]
+    None,
+    // Lower half is the upper half flipped upside down
+    Mirror,
+    // Lower half is the upper half turned around the center
+    Rotation,
+}
+
+// Generator of obstacles of the random field, see generator module
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MapKind {
+    // Walls and water scattered over the field
+    #[default]
+    Uniform,
+    // Cellular automata caves
+    Caves,
+    // Noise based islands in the water
+    Islands,
+    // Rooms joined with corridors
+    Corridors,
+}
+
+// Range [min, max] of units of the kind in every army
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Squad {
+    pub kind: Kind,
+    pub count: (u8, u8),
+}
+
+// Parameters of the random game, every parameter is a range [min, max]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub num_x: (u32, u32),
+    pub num_y: (u32, u32),
+    // Impassable hexes, walls and water
+    pub walls_percent: (u8, u8),
+    // Hexes with forests, swamps, roads and hills
+    pub terrain_percent: (u8, u8),
+    pub num_units: (u8, u8),
+    pub unit_hp: (u8, u8),
+    pub unit_min_damage: (u8, u8),
+    pub unit_damage_interval: (u8, u8),
+    pub unit_speed: (u8, u8),
+    // Kinds of units in every army, when empty num_units infantry is rolled from unit_* ranges
+    pub army: Vec<Squad>,
+    pub symmetry: Symmetry,
+    pub map: MapKind,
+    // Seed of the field alone, the same map with other units, none for the seed of the game
+    pub map_seed: Option<u32>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            num_x: NUM_X,
+            num_y: NUM_Y,
+            walls_percent: WALLS_PERCENT,
+            terrain_percent: TERRAIN_PERCENT,
+            num_units: NUM_UNITS,
+            unit_hp: UNIT_HP,
+            unit_min_damage: UNIT_MIN_DAMAGE,
+            unit_damage_interval: UNIT_DAMAGE_INTERVAL,
+            unit_speed: UNIT_SPEED,
+            army: vec![],
+            symmetry: Symmetry::None,
+            map: MapKind::Uniform,
+            map_seed: None,
+        }
+    }
+}
+
+impl Settings {
+    // Check that any game generated with these settings is correct
+    pub fn validate(&self, num_of_players: usize) -> Result<()> {
+        let ranges = [
+            ("num_x", self.num_x.0, self.num_x.1),
+            ("num_y", self.num_y.0, self.num_y.1),
+            (
+                "walls_percent",
+                self.walls_percent.0 as u32,
+                self.walls_percent.1 as u32,
+            ),
+            (
+                "terrain_percent",
+                self.terrain_percent.0 as u32,
+                self.terrain_percent.1 as u32,
+            ),
+            (
+                "num_units",
+                self.num_units.0 as u32,
+                self.num_units.1 as u32,
+            ),
+            ("unit_hp", self.unit_hp.0 as u32, self.unit_hp.1 as u32),
+            (
+                "unit_min_damage",
+                self.unit_min_damage.0 as u32,
+                self.unit_min_damage.1 as u32,
+            ),
+            (
+                "unit_damage_interval",
+                self.unit_damage_interval.0 as u32,
+                self.unit_damage_interval.1 as u32,
+            ),
+            (
+                "unit_speed",
+                self.unit_speed.0 as u32,
+                self.unit_speed.1 as u32,
+            ),
+        ];
+        for (field, min, max) in ranges.iter() {
+            if min > max {
+                Err(SettingsError::WrongRange {
+                    field: field.to_string(),
+                })?;
+            }
+        }
+
+        let positive = [
+            ("num_x", self.num_x.0),
+            ("num_y", self.num_y.0),
+            ("num_units", self.num_units.0 as u32),
+            ("unit_hp", self.unit_hp.0 as u32),
+        ];
+        for (field, min) in positive.iter() {
+            if *min == 0 {
+                Err(SettingsError::NotPositive {
+                    field: field.to_string(),
+                })?;
+            }
+        }
+
+        if self.num_x.1 > MAX_FIELD_SIDE || self.num_y.1 > MAX_FIELD_SIDE {
+            Err(SettingsError::FieldTooBig {
+                max: MAX_FIELD_SIDE,
+            })?;
+        }
+
+        let bounded = [
+            ("num_units", self.num_units.1, MAX_NUM_UNITS),
+            ("unit_hp", self.unit_hp.1, MAX_UNIT_STAT),
+            ("unit_min_damage", self.unit_min_damage.1, MAX_UNIT_STAT),
+            (
+                "unit_damage_interval",
+                self.unit_damage_interval.1,
+                MAX_UNIT_STAT,
+            ),
+            ("unit_speed", self.unit_speed.1, MAX_UNIT_STAT),
+        ];
+        for (field, value, max) in bounded.iter() {
+            if value > max {
+                Err(SettingsError::TooBig {
+                    field: field.to_string(),
+                    max: *max,
+                })?;
+            }
+        }
+
+        if self.walls_percent.1 > 100 {
+            Err(SettingsError::TooManyWalls)?;
+        }
+
+        if self.walls_percent.1 as u32 + self.terrain_percent.1 as u32 > 100 {
+            Err(SettingsError::TooMuchTerrain)?;
+        }
+
+        self.validate_army()?;
+
+        if self.symmetry != Symmetry::None && num_of_players != 2 {
+            Err(SettingsError::SymmetryForTwoPlayers)?;
+        }
+
+        // The smallest field with the most walls must fit the most units
+        let hexes = self.num_x.0 * self.num_y.0;
+        let free_hexes = hexes - hexes * self.walls_percent.1 as u32 / 100;
+        let units = self.max_army_size() * num_of_players as u32;
+        if free_hexes < units {
+            Err(SettingsError::NotEnoughHexes {
+                hexes: free_hexes,
+                units,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_army(&self) -> Result<()> {
+        if self.army.is_empty() {
+            return Ok(());
+        }
+
+        let mut min_units = 0;
+        for squad in &self.army {
+            if squad.count.0 > squad.count.1 {
+                Err(SettingsError::WrongRange {
+                    field: "army".to_string(),
+                })?;
+            }
+            min_units += squad.count.0 as u32;
+
+            // Kind may be split into several squads
+            let max_of_kind: u32 = self
+                .army
+                .iter()
+                .filter(|other| other.kind == squad.kind)
+                .map(|other| other.count.1 as u32)
+                .sum();
+            if let Some(max) = squad.kind.stats().max_per_army {
+                if max_of_kind > max as u32 {
+                    Err(SettingsError::TooManyUnits {
+                        kind: squad.kind,
+                        max,
+                    })?;
+                }
+            }
+        }
+        if min_units == 0 {
+            Err(SettingsError::NotPositive {
+                field: "army".to_string(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // The most units one player can get
+    fn max_army_size(&self) -> u32 {
+        if self.army.is_empty() {
+            self.num_units.1 as u32
+        } else {
+            self.army.iter().map(|squad| squad.count.1 as u32).sum()
+        }
+    }
+}
+
+// Random value of the range [min, max], both ends included
+pub fn roll_range<T: SampleUniform>(rng: &mut impl Rng, range: (T, T)) -> T {
+    rng.sample(Uniform::new_inclusive(range.0, range.1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn assert_error(settings: Settings, error: SettingsError) {
+        let result = settings.validate(2);
+        assert!(result.is_err());
+        assert_eq!(
+            error,
+            *result.unwrap_err().downcast_ref::<SettingsError>().unwrap()
+        );
+    }
+
+    #[test]
+    fn default_is_valid() {
+        assert!(Settings::default().validate(2).is_ok());
+    }
+
+    #[test]
+    fn deserialize_partial() {
+        let settings: Settings =
+            serde_json::from_str("{\"num_x\":[7,7],\"unit_speed\":[2,3]}").unwrap();
+
+        assert_eq!(
+            settings,
+            Settings {
+                num_x: (7, 7),
+                unit_speed: (2, 3),
+                ..Settings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn wrong_range() {
+        let settings = Settings {
+            unit_hp: (5, 4),
+            ..Settings::default()
+        };
+        assert_error(
+            settings,
+            SettingsError::WrongRange {
+                field: "unit_hp".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn not_positive() {
+        let settings = Settings {
+            num_units: (0, 3),
+            ..Settings::default()
+        };
+        assert_error(
+            settings,
+            SettingsError::NotPositive {
+                field: "num_units".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn field_too_big() {
+        let settings = Settings {
+            num_y: (5, MAX_FIELD_SIDE + 1),
+            ..Settings::default()
+        };
+        assert_error(
+            settings,
+            SettingsError::FieldTooBig {
+                max: MAX_FIELD_SIDE,
+            },
+        );
+    }
+
+    #[test]
+    fn too_big_unit_stats() {
+        let settings = Settings {
+            unit_hp: (1, 255),
+            ..Settings::default()
+        };
+        assert_error(
+            settings,
+            SettingsError::TooBig {
+                field: "unit_hp".to_string(),
+                max: MAX_UNIT_STAT,
+            },
+        );
+
+        let settings = Settings {
+            num_units: (1, 255),
+            ..Settings::default()
+        };
+        assert_error(
+            settings,
+            SettingsError::TooBig {
+                field: "num_units".to_string(),
+                max: MAX_NUM_UNITS,
+            },
+        );
+    }
+
+    #[test]
+    fn roll_whole_range() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert_eq!(roll_range(&mut rng, (255u8, 255u8)), 255);
+            let value = roll_range(&mut rng, (250u8, 255u8));
+            assert!(value >= 250);
+        }
+    }
+
+    #[test]
+    fn too_many_walls() {
+        let settings = Settings {
+            walls_percent: (10, 101),
+            ..Settings::default()
+        };
+        assert_error(settings, SettingsError::TooManyWalls);
+    }
+
+    #[test]
+    fn too_much_terrain() {
+        let settings = Settings {
+            walls_percent: (10, 80),
+            terrain_percent: (0, 30),
+            ..Settings::default()
+        };
+        assert_error(settings, SettingsError::TooMuchTerrain);
+    }
+
+    #[test]
+    fn not_enough_hexes() {
+        // 3x3 field with 50% walls has 5 free hexes for 2 players with 3 units each
+        let settings = Settings {
+            num_x: (3, 10),
+            num_y: (3, 10),
+            walls_percent: (0, 50),
+            num_units: (1, 3),
+            ..Settings::default()
+        };
+        assert_error(
+            settings,
+            SettingsError::NotEnoughHexes { hexes: 5, units: 6 },
+        );
+    }
+
+    fn army(squads: &[(Kind, u8, u8)]) -> Vec<Squad> {
+        squads
+            .iter()
+            .map(|(kind, min, max)| Squad {
+                kind: *kind,
+                count: (*min, *max),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn army_is_valid() {
+        let settings = Settings {
+            army: army(&[(Kind::Infantry, 2, 4), (Kind::Archer, 1, 2)]),
+            ..Settings::default()
+        };
+        assert!(settings.validate(2).is_ok());
+    }
+
+    #[test]
+    fn empty_army() {
+        let settings = Settings {
+            army: army(&[(Kind::Infantry, 0, 2), (Kind::Archer, 0, 1)]),
+            ..Settings::default()
+        };
+        assert_error(
+            settings,
+            SettingsError::NotPositive {
+                field: "army".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn too_many_units_of_kind() {
+        // Healers are split into two squads, but still limited together
+        let settings = Settings {
+            army: army(&[
+                (Kind::Healer, 1, 1),
+                (Kind::Infantry, 1, 2),
+                (Kind::Healer, 0, 1),
+            ]),
+            ..Settings::default()
+        };
+        assert_error(
+            settings,
+            SettingsError::TooManyUnits {
+                kind: Kind::Healer,
+                max: 1,
+            },
+        );
+    }
+
+    #[test]
+    fn not_enough_hexes_for_army() {
+        let settings = Settings {
+            num_x: (3, 10),
+            num_y: (3, 10),
+            army: army(&[(Kind::Infantry, 1, 4), (Kind::Cavalry, 1, 1)]),
+            ..Settings::default()
+        };
+        assert_error(
+            settings,
+            SettingsError::NotEnoughHexes {
+                hexes: 6,
+                units: 10,
+            },
+        );
+    }
+
+    #[test]
+    fn symmetry_for_two_players() {
+        let settings = Settings {
+            symmetry: Symmetry::Rotation,
+            ..Settings::default()
+        };
+        assert!(settings.validate(2).is_ok());
+        assert_eq!(
+            *settings
+                .validate(3)
+                .unwrap_err()
+                .downcast_ref::<SettingsError>()
+                .unwrap(),
+            SettingsError::SymmetryForTwoPlayers
+        );
+
+        let settings: Settings = serde_json::from_str("{\"symmetry\":\"mirror\"}").unwrap();
+        assert_eq!(settings.symmetry, Symmetry::Mirror);
+    }
+
+    #[test]
+    fn deserialize_map() {
+        let settings: Settings =
+            serde_json::from_str("{\"map\":\"caves\",\"map_seed\":42}").unwrap();
+        assert_eq!(settings.map, MapKind::Caves);
+        assert_eq!(settings.map_seed, Some(42));
+    }
+
+    #[test]
+    fn serialize_error() {
+        let error = SettingsError::WrongRange {
+            field: "num_x".to_string(),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&error).unwrap(),
+            "{\"error\":\"wrong_range\",\"field\":\"num_x\"}"
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+
+use crate::api::common::Point;
+use crate::game_objects::grid::Grid;
+use crate::game_objects::hex::Hex;
+use crate::game_objects::hex_objects::content::Content;
+use crate::hex_coords::{line, Cube};
+
+pub fn blocks_sight(hex: &Hex) -> bool {
+    matches!(hex.content, Some(Content::Wall(_)))
+}
+
+// Nothing on the line between hexes blocks it, ends themselves are not checked
+pub fn line_is_clear<F>(grid: &Grid, from: Point, to: Point, blocks: F) -> bool
+where
+    F: Fn(&Hex) -> bool,
+{
+    let points = line(from, to);
+    let inner = points.len().saturating_sub(1);
+    points
+        .iter()
+        .take(inner)
+        .skip(1)
+        .all(|point| match grid.get_hex(point.x, point.y) {
+            Some(hex) => !blocks(&hex),
+            None => true,
+        })
+}
+
+// Walls block the sight, units can be seen through
+pub fn in_line_of_sight(grid: &Grid, from: Point, to: Point) -> bool {
+    line_is_clear(grid, from, to, blocks_sight)
+}
+
+// Hexes seen by at least one unit of the player
+pub fn visible_points(grid: &Grid, player: u32) -> HashSet<Point> {
+    let mut visible = HashSet::new();
+    for hex in &grid.hexes {
+        let unit = match hex.unit {
+            Some(unit) if unit.is_my(player) => unit,
+            _ => continue,
+        };
+        let from = hex.to_point();
+        for point in Cube::from_point(from)
+            .spiral(unit.sight)
+            .into_iter()
+            .filter_map(Cube::to_point)
+        {
+            if grid.get_hex(point.x, point.y).is_some() && in_line_of_sight(grid, from, point) {
+                visible.insert(point);
+            }
+        }
+    }
+    visible
+}
+
+// Field as the player sees it, units in the fog are hidden
+#[derive(Debug, Clone)]
+pub struct View {
+    pub player: u32,
+    pub visible: HashSet<Point>,
+    pub field: Grid,
+}
+
+impl View {
+    pub fn new(grid: &Grid, player: u32) -> View {
+        let visible = visible_points(grid, player);
+        let mut field = grid.clone();
+        for hex in field.hexes.iter_mut() {
+            if !visible.contains(&hex.to_point()) {
+                hex.unit = None;
+            }
+        }
+
+        View {
+            player,
+            visible,
+            field,
+        }
+    }
+
+    pub fn sees(&self, point: &Point) -> bool {
+        self.visible.contains(point)
+    }
+
+    // Visible hexes in the stable order
+    pub fn visible_points(&self) -> Vec<Point> {
+        let mut points: Vec<Point> = self.visible.iter().copied().collect();
+        points.sort_by_key(|point| (point.x, point.y));
+        points
+    }
+
+    // Hexes which look different in the new view
+    pub fn changes(&self, new: &View) -> Vec<Hex> {
+        new.field
+            .hexes
+            .iter()
+            .zip(self.field.hexes.iter())
+            .filter(|(new_hex, old_hex)| new_hex.unit != old_hex.unit)
+            .map(|(new_hex, _)| *new_hex)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game_objects::hex_objects::wall::Wall;
+    use crate::game_objects::unit::Unit;
+
+    fn point(x: u32, y: u32) -> Point {
+        Point { x, y }
+    }
+
+    fn set_unit(grid: &mut Grid, x: u32, y: u32, player: u32, sight: u32) {
+        grid.get_hex_mut(x, y).unwrap().unit = Some(Unit {
+            sight,
+            ..Unit::new(player, 5, [1, 2], 2)
+        });
+    }
+
+    #[test]
+    fn wall_blocks_sight() {
+        let mut grid = Grid::new(5, 1);
+        grid.get_hex_mut(2, 0).unwrap().content = Some(Content::Wall(Wall {}));
+
+        assert!(in_line_of_sight(&grid, point(0, 0), point(1, 0)));
+        // Wall itself is seen
+        assert!(in_line_of_sight(&grid, point(0, 0), point(2, 0)));
+        assert!(!in_line_of_sight(&grid, point(0, 0), point(3, 0)));
+    }
+
+    #[test]
+    fn line_blocked_by_units() {
+        let mut grid = Grid::new(5, 1);
+        set_unit(&mut grid, 2, 0, 1, 1);
+        let blocks = |hex: &Hex| hex.unit.is_some();
+
+        assert!(in_line_of_sight(&grid, point(0, 0), point(4, 0)));
+        assert!(line_is_clear(&grid, point(0, 0), point(2, 0), blocks));
+        assert!(!line_is_clear(&grid, point(0, 0), point(4, 0), blocks));
+    }
+
+    #[test]
+    fn view_hides_units_in_fog() {
+        let mut grid = Grid::new(6, 1);
+        set_unit(&mut grid, 0, 0, 0, 2);
+        set_unit(&mut grid, 2, 0, 1, 1);
+        set_unit(&mut grid, 5, 0, 1, 1);
+
+        let view = View::new(&grid, 0);
+        assert!(view.sees(&point(2, 0)));
+        assert!(!view.sees(&point(3, 0)));
+        assert!(view.field.get_hex(2, 0).unwrap().unit.is_some());
+        assert!(view.field.get_hex(5, 0).unwrap().unit.is_none());
+        assert_eq!(
+            view.visible_points(),
+            vec![point(0, 0), point(1, 0), point(2, 0)]
+        );
+
+        // Enemy steps into sight
+        let unit = grid.get_hex_mut(5, 0).unwrap().unit.take();
+        grid.get_hex_mut(1, 0).unwrap().unit = unit;
+        let new_view = View::new(&grid, 0);
+        let changes: Vec<Point> = view.changes(&new_view).iter().map(Hex::to_point).collect();
+        assert_eq!(changes, vec![point(1, 0)]);
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::bot::Bot;
+use crate::clock::TimerSettings;
+use crate::game::Game;
+use crate::preset::Preset;
+use crate::replay::Replay;
+
+use eyre::{Result, WrapErr};
+
+// State of the running match, enough to continue it after server restart
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    pub room_id: Uuid,
+    pub num_of_players: usize,
+    // Users in the seats of human players, only they can continue the game
+    pub players: Vec<Uuid>,
+    pub first_player: u32,
+    // Preset the game was made from, rematch plays it again
+    #[serde(default)]
+    pub preset: Option<Preset>,
+    // Bots taking the last player seats
+    #[serde(default)]
+    pub bots: Vec<Bot>,
+    // Time limits, banks of players are full again after restart
+    #[serde(default)]
+    pub timer: Option<TimerSettings>,
+    #[serde(default)]
+    pub fog: bool,
+    pub game: Game,
+    pub replay: Option<Replay>,
+}
+
+impl Snapshot {
+    fn path(dir: &str, room_id: Uuid) -> PathBuf {
+        Path::new(dir).join(format!("{}.json", room_id))
+    }
+
+    // Save snapshot to the <dir>/<room_id>.json file, rewriting the previous one
+    pub fn save(&self, dir: &str) -> Result<PathBuf> {
+        fs::create_dir_all(dir).wrap_err_with(|| format!("create directory {}", dir))?;
+
+        let path = Snapshot::path(dir, self.room_id);
+        let string = serde_json::to_string(self).wrap_err("serialize snapshot")?;
+        fs::write(&path, string).wrap_err_with(|| format!("write {:?}", path))?;
+
+        Ok(path)
+    }
+
+    pub fn remove(dir: &str, room_id: Uuid) -> Result<()> {
+        let path = Snapshot::path(dir, room_id);
+        if path.exists() {
+            fs::remove_file(&path).wrap_err_with(|| format!("remove {:?}", path))?;
+        }
+
+        Ok(())
+    }
+
+    fn load(path: &Path) -> Result<Snapshot> {
+        let string = fs::read_to_string(path).wrap_err_with(|| format!("read {:?}", path))?;
+        serde_json::from_str(&string).wrap_err_with(|| format!("deserialize snapshot {:?}", path))
+    }
+
+    // Load all snapshots saved in dir, broken ones are skipped
+    pub fn load_all(dir: &str) -> Result<Vec<Snapshot>> {
+        if !Path::new(dir).exists() {
+            return Ok(vec![]);
+        }
+
+        let mut snapshots = vec![];
+        for entry in fs::read_dir(dir).wrap_err_with(|| format!("read directory {}", dir))? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(error) => {
+                    error!("Failed to read entry of {}: {}", dir, error);
+                    continue;
+                }
+            };
+            match Snapshot::load(&path) {
+                Ok(snapshot) => snapshots.push(snapshot),
+                Err(error) => error!("{:?}", error.wrap_err("load snapshot")),
+            }
+        }
+
+        Ok(snapshots)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn save_load_remove() {
+        let dir = std::env::temp_dir().join(format!("snapshots-{}", Uuid::new_v4()));
+        let dir = dir.to_str().unwrap();
+
+        let mut game = Game::random(2, 5, Settings::default()).unwrap();
+        game.change_player(2);
+        let snapshot = Snapshot {
+            room_id: Uuid::new_v4(),
+            num_of_players: 2,
+            players: vec![Uuid::new_v4()],
+            first_player: 1,
+            preset: None,
+            bots: vec![Bot::Greedy],
+            timer: None,
+            fog: true,
+            replay: Some(Replay::new(2, &game)),
+            game,
+        };
+
+        assert!(snapshot.save(dir).is_ok());
+        let snapshots = Snapshot::load_all(dir).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].room_id, snapshot.room_id);
+        assert_eq!(snapshots[0].num_of_players, 2);
+        assert_eq!(snapshots[0].bots, vec![Bot::Greedy]);
+        assert_eq!(snapshots[0].players, snapshot.players);
+        assert_eq!(snapshots[0].first_player, 1);
+        assert_eq!(snapshots[0].game.turn, 1);
+        assert!(snapshots[0].fog);
+        assert_eq!(
+            serde_json::to_string(&snapshots[0].game.field).unwrap(),
+            serde_json::to_string(&snapshot.game.field).unwrap()
+        );
+
+        // Broken file doesn't hide the others
+        fs::write(Path::new(dir).join("broken.json"), "{").unwrap();
+        assert_eq!(Snapshot::load_all(dir).unwrap().len(), 1);
+        fs::remove_file(Path::new(dir).join("broken.json")).unwrap();
+
+        assert!(Snapshot::remove(dir, snapshot.room_id).is_ok());
+        assert!(Snapshot::load_all(dir).unwrap().is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
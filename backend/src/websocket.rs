@@ -2,15 +2,31 @@ use actix::{Actor, Addr, AsyncContext, Handler, StreamHandler};
 use actix_web::web;
 use actix_web_actors::ws;
 
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::api::response::ErrorCode;
+use crate::communicator;
 use crate::communicator::Msg;
+use crate::config::CONFIG;
 use crate::game_server::GameServer;
+use crate::lobby::Lobby;
+use crate::replay::{Replay, ReplayError, ReplayPlayer};
 
 use super::api;
 
 /// Define http actor
 #[derive(Debug)]
 pub struct Websocket {
-    pub server_addr: web::Data<Addr<GameServer>>,
+    // Authenticated user the connection belongs to
+    pub user_id: Uuid,
+    // Session to take the seat again after reconnect
+    pub session: Uuid,
+    pub lobby_addr: web::Data<Addr<Lobby>>,
+    // Room the client is in now
+    pub room_addr: Option<Addr<GameServer>>,
+    // Replay the client is watching now
+    pub replay: Option<ReplayPlayer>,
 }
 
 impl Actor for Websocket {
@@ -27,46 +43,162 @@ impl Handler<Msg> for Websocket {
     }
 }
 
+impl Handler<api::inner::RoomChanged> for Websocket {
+    type Result = ();
+
+    fn handle(&mut self, msg: api::inner::RoomChanged, _: &mut Self::Context) {
+        self.room_addr = msg.room;
+    }
+}
+
+impl Websocket {
+    fn client(&self, ctx: &mut <Self as Actor>::Context) -> api::inner::Client {
+        api::inner::Client::new(ctx.address(), self.user_id, self.session)
+    }
+
+    // Pass request to the room, lobby or handle it here
+    fn dispatch(&mut self, envelope: api::request::Envelope, ctx: &mut <Self as Actor>::Context) {
+        use api::inner::Request as Inner;
+        use api::request::Request;
+
+        let client = self.client(ctx);
+        let id = envelope.id;
+        match envelope.request {
+            Request::Click(message) => self.send_to_room(Inner::new(client, id, message)),
+            Request::SkipTurn(message) => self.send_to_room(Inner::new(client, id, message)),
+            Request::Surrender(message) => self.send_to_room(Inner::new(client, id, message)),
+            Request::OfferDraw(message) => self.send_to_room(Inner::new(client, id, message)),
+            Request::AcceptDraw(message) => self.send_to_room(Inner::new(client, id, message)),
+            Request::Rematch(message) => self.send_to_room(Inner::new(client, id, message)),
+            Request::View(message) => self.send_to_room(Inner::new(client, id, message)),
+            Request::StartGame(message) => self.send_to_room(Inner::new(client, id, message)),
+            Request::CreateRoom(message) => {
+                self.lobby_addr.do_send(Inner::new(client, id, message))
+            }
+            Request::JoinRoom(message) => self.lobby_addr.do_send(Inner::new(client, id, message)),
+            Request::LeaveRoom(message) => self.lobby_addr.do_send(Inner::new(client, id, message)),
+            Request::ListRooms(message) => self.lobby_addr.do_send(Inner::new(client, id, message)),
+            Request::ListReplays => self.list_replays(id, ctx),
+            Request::WatchReplay(message) => self.watch_replay(message.replay_id, id, ctx),
+            Request::ReplayStep => self.replay_step(id, ctx),
+            // Parser answers unknown commands itself
+            Request::Unknown => debug!("Unknown command"),
+        }
+    }
+
+    // Game commands are handled by the room client is in
+    fn send_to_room<T>(&self, message: api::inner::Request<T>)
+    where
+        T: Send + 'static,
+        GameServer: Handler<api::inner::Request<T>>,
+    {
+        match &self.room_addr {
+            Some(room) => room.do_send(message),
+            None => {
+                debug!("Error: client is not in a room");
+                message.reply_error(api::response::Error::with_code(
+                    api::response::ErrorCode::NotInRoom,
+                    "client is not in a room".to_string(),
+                ));
+            }
+        }
+    }
+
+    // Replays
+    fn list_replays(&self, id: Option<Value>, ctx: &mut <Self as Actor>::Context) {
+        match Replay::list(&CONFIG.replays_dir) {
+            Ok(replays) => {
+                communicator::broadcast(&api::response::Replays::new(replays), vec![ctx.address()])
+            }
+            Err(error) => {
+                let response = api::response::Error::from_report(&error);
+                error!("{:?}", error.wrap_err("list replays"));
+                send_error(response, id, ctx);
+            }
+        }
+    }
+
+    fn watch_replay(
+        &mut self,
+        replay_id: Uuid,
+        id: Option<Value>,
+        ctx: &mut <Self as Actor>::Context,
+    ) {
+        match Replay::load(&CONFIG.replays_dir, replay_id) {
+            Ok(replay) => {
+                let player = ReplayPlayer::new(replay, ctx.address());
+                player.start();
+                self.replay = Some(player);
+            }
+            Err(error) => {
+                let response = match error.downcast_ref::<ReplayError>() {
+                    Some(replay_error) => {
+                        let code = match replay_error {
+                            ReplayError::NotFound(_) => ErrorCode::ReplayNotFound,
+                            ReplayError::WrongVersion(_) => ErrorCode::UnsupportedReplay,
+                        };
+                        api::response::Error::with_code(code, replay_error.to_string())
+                    }
+                    None => api::response::Error::from_report(&error),
+                };
+                error!("{:?}", error.wrap_err("watch replay"));
+                send_error(response, id, ctx);
+            }
+        }
+    }
+
+    fn replay_step(&mut self, id: Option<Value>, ctx: &mut <Self as Actor>::Context) {
+        match &mut self.replay {
+            Some(player) if !player.finished() => player.step(),
+            _ => {
+                debug!("Error: no replay to play");
+                let error = api::response::Error::with_code(
+                    ErrorCode::NoReplay,
+                    "no replay to play".to_string(),
+                );
+                send_error(error, id, ctx);
+            }
+        }
+    }
+}
+
+fn send_error(
+    error: api::response::Error,
+    id: Option<Value>,
+    ctx: &mut <Websocket as Actor>::Context,
+) {
+    let error = error.for_request(id);
+    communicator::broadcast(&error, vec![ctx.address()]);
+}
+
 /// Handler for ws::Message message
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for Websocket {
     fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
         if let Ok(ws::Message::Text(text)) = msg {
             debug!("Client text: {}", text);
 
-            let message = api::common::Message::from_str(&text);
-            match message.cmd.as_str() {
-                api::request::CMD_CLICK => {
-                    let message = api::request::Click::from_str(&text);
-                    let inner_message = api::inner::Request::new(ctx.address(), message);
-                    self.server_addr.do_send(inner_message);
-                }
-                api::request::CMD_SKIP_TURN => {
-                    let message = api::request::SkipTurn {};
-                    let inner_message = api::inner::Request::new(ctx.address(), message);
-                    self.server_addr.do_send(inner_message);
-                }
-                api::request::CMD_START_GAME => {
-                    let message = api::request::StartGame {};
-                    let inner_message = api::inner::Request::new(ctx.address(), message);
-                    self.server_addr.do_send(inner_message);
-                }
-                _ => {
-                    debug!("Unknown command: {}", message.cmd);
+            match api::request::parse(&text) {
+                Ok(envelope) => self.dispatch(envelope, ctx),
+                Err(error) => {
+                    debug!("Error: wrong request {:?}", error);
+                    communicator::broadcast(&error, vec![ctx.address()]);
                 }
             }
         }
     }
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        debug!("Client connected");
-        self.server_addr
-            .do_send(api::inner::NewClient::new(ctx.address()));
+        debug!(
+            "Client of user {} connected with session {}",
+            self.user_id, self.session
+        );
+        let client = api::inner::Client::new(ctx.address(), self.user_id, self.session);
+        self.lobby_addr.do_send(api::inner::Connect::new(client));
     }
 
     fn finished(&mut self, ctx: &mut Self::Context) {
         debug!("Client disconnected");
-        // TODO: Send everyone - player disconnected (stop current game)
-        self.server_addr
-            .do_send(api::inner::LooseClient::new(ctx.address()));
+        self.lobby_addr
+            .do_send(api::inner::Disconnect::new(ctx.address()));
     }
 }
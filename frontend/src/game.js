@@ -7,6 +7,8 @@ export class Game {
     constructor() {
         // Websocket we use to contact with server
         this.socket = null;
+        // JWT to connect to the server
+        this.token = null;
         // app - PIXI aplication, used for this game
         this.app = null;
 
@@ -21,6 +23,14 @@ export class Game {
 
         // current game state
         this.state = this.STATES.OUTSIDE;
+        // id of the room on the server we are in
+        this.room_id = null;
+        // session to take our seat again after reconnect, it lives while the tab is open
+        this.session = sessionStorage.getItem('session');
+        // number of the player whose turn it is
+        this.current_player = null;
+        // time when the current turn runs out, null if there is no limit
+        this.turn_deadline = null;
         // if game has been started on the server?
         this.game_started = false;
         // to output client their number in queue and total number of clients
@@ -41,6 +51,28 @@ export class Game {
         this.cmd_map = {};
 
         this.cmd_map.queue = this.process_queue;
+        this.cmd_map.room = function (data) {
+            this.room_id = data.room_id;
+        };
+        this.cmd_map.session = function (data) {
+            this.session = data.session;
+            sessionStorage.setItem('session', data.session);
+            if (!data.reconnected) {
+                // join any room waiting for players (or create a new one)
+                this.socket.send(
+                    JSON.stringify({
+                        "cmd": 'join_room',
+                    })
+                );
+            }
+        };
+        this.cmd_map.turn = function (data) {
+            this.current_player = data.player;
+        };
+        this.cmd_map.timer = function (data) {
+            this.current_player = data.player;
+            this.turn_deadline = Date.now() + data.time_left * 1000;
+        };
         // reset game (or create a new one if doesn't exist)
         this.cmd_map.field = this.create_new_field;
 
@@ -53,6 +85,7 @@ export class Game {
             }
 
             this.grid.reset_in_path();
+            this.grid.reset_targets();
         };
 
         this.cmd_map.moving = this.process_moving;
@@ -61,6 +94,7 @@ export class Game {
         this.cmd_map.hurt = this.process_hurt;
         this.cmd_map.die = this.process_die;
         this.cmd_map.update = this.process_update;
+        this.cmd_map.vision = this.process_vision;
 
         this.cmd_map.state = function (data) {
             this.change_state(data.state);
@@ -78,14 +112,34 @@ export class Game {
                 case 'lose':
                     alert('YOU LOSE!');
                     break;
+                case 'surrendered':
+                    alert('You surrendered');
+                    break;
+                case 'draw':
+                    alert('DRAW!');
+                    break;
                 case 'disconnected':
                     alert('Game was aborted because one of players was disconnected');
-                    break;
+                    return;
                 default:
                     alert('Game ends, but something strange happened');
+                    return;
+            }
+            if (confirm('Play again with the same players?')) {
+                this.send_command('rematch');
             }
         };
 
+        this.cmd_map.draw_offer = function (data) {
+            if (confirm(`Player ${data.player + 1} offers a draw. Accept?`)) {
+                this.send_command('accept_draw');
+            }
+        };
+
+        this.cmd_map.rematch_offer = function (data) {
+            console.log(`Player ${data.player + 1} wants to play again`);
+        };
+
         this.cmd_map.error = function (data) {
             console.error(JSON.stringify(data));
         };
@@ -98,35 +152,60 @@ export class Game {
         console.log('Is mobile:', PIXI.utils.isMobile.any);
         this.create_stage();
         await this.load_images();
+        this.token = await this.login();
         this.create_socket();
         this.create_queue_status();
         this.create_start_button();
     }
 
+    // ask user for email and password and get token for them
+    // private
+    async login() {
+        for (;;) {
+            const email = prompt('Email');
+            const password = prompt('Password');
+            const response = await fetch('/api/v1/auth/login', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ email, password }),
+            });
+            if (response.ok) {
+                return (await response.json()).token;
+            }
+            alert('Wrong email or password');
+        }
+    }
+
     // create websocket to receive and send messages
     // private
     create_socket() {
         const wsProtocol = process.env.WS_PROTOCOL || 'ws';
         const wsAddress = process.env.WS_ADDRESS || '127.0.0.1';
         const wsPort = process.env.WS_PORT || '8088';
-        const wsUrl = `${wsProtocol}://${wsAddress}:${wsPort}/ws/`;
+        let wsUrl = `${wsProtocol}://${wsAddress}:${wsPort}/ws/?token=${this.token}`;
+        if (this.session) {
+            wsUrl += `&session=${this.session}`;
+        }
 
         console.log(`connecting to websocket: ${wsUrl}`);
         this.socket = new WebSocket(wsUrl);
 
+        // server answers with session and returns us to the game we have left
         this.socket.onopen = function (e) {
             console.log("[open] Connection established");
         };
 
         this.socket.onmessage = this.process_message.bind(this);
 
-        this.socket.onclose = function (event) {
+        this.socket.onclose = (event) => {
             if (event.wasClean) {
                 console.log(`[close] Connection closed cleanly, code=${event.code} reason=${event.reason}`);
             } else {
                 // e.g. server process killed or network down
                 // event.code is usually 1006 in this case
-                console.log('[close] Connection died');
+                // server holds our seat for a while, so try to come back
+                console.log('[close] Connection died, reconnecting');
+                setTimeout(() => this.create_socket(), 1000);
             }
         };
 
@@ -159,7 +238,7 @@ export class Game {
         let data = JSON.parse(event.data);
         console.log(data);
         // outside clients can't process almost all commands
-        const allowed_cmds = ['state', 'queue'];
+        const allowed_cmds = ['state', 'queue', 'room', 'session', 'rematch_offer'];
         if (allowed_cmds.includes(data.cmd) || this.state !== this.STATES.OUTSIDE) {
             this.cmd_map[data.cmd].call(this, data);
             // TODO: not necessarry to call it on each message
@@ -215,9 +294,13 @@ export class Game {
         this.clear_app();
         this.create_grid(field_data);
         this.set_units_start_pos(field_data);
+        if (field_data.visible) {
+            this.grid.set_visible(field_data.visible);
+        }
         this.hide_queue_status();
         this.create_info();
         this.create_skip_button();
+        this.create_end_buttons();
         if (PIXI.utils.isMobile.any) {
             this.create_info_button();
         }
@@ -230,6 +313,7 @@ export class Game {
         this.grid = null;
 
         this.cur_hex = null;
+        this.turn_deadline = null;
     }
 
     // remove everything from the PIXI stage
@@ -282,8 +366,8 @@ export class Game {
 
         let hexes = field_data.field.hexes;
         for (let i = 0; i < field_data.num_x * field_data.num_y; i++) {
-            if (hexes[i].content && hexes[i].content.type === 'wall') {
-                grid.hexes[hexes[i].x][hexes[i].y].set_content('wall');
+            if (hexes[i].content) {
+                grid.hexes[hexes[i].x][hexes[i].y].set_content(hexes[i].content.type);
             }
         }
 
@@ -295,29 +379,28 @@ export class Game {
         let hexes = field_data.field.hexes;
         for (let i = 0; i < field_data.num_x * field_data.num_y; i++) {
             if (hexes[i].unit !== undefined) {
-                let texture;
-                // TODO: refactor this
-                switch (hexes[i].unit.player) {
-                    case 0:
-                        texture = this.app.loader.resources["blue unit"].texture;
-                    break;
-                    case 1:
-                        texture = this.app.loader.resources["red unit"].texture;
-                    break;
-                    case 2:
-                        texture = this.app.loader.resources["green unit"].texture;
-                    break;
-                    case 3:
-                        texture = this.app.loader.resources["white unit"].texture;
-                    break;
-                    default:
-                        texture = this.app.loader.resources["black unit"].texture;
-                }
-                this.create_unit(texture, this.grid.hex_size, hexes[i].unit, hexes[i].x, hexes[i].y);
+                this.create_unit(this.unit_texture(hexes[i].unit.player), this.grid.hex_size, hexes[i].unit, hexes[i].x, hexes[i].y);
             }
         }
     }
 
+    // private
+    unit_texture(player) {
+        // TODO: refactor this
+        switch (player) {
+            case 0:
+                return this.app.loader.resources["blue unit"].texture;
+            case 1:
+                return this.app.loader.resources["red unit"].texture;
+            case 2:
+                return this.app.loader.resources["green unit"].texture;
+            case 3:
+                return this.app.loader.resources["white unit"].texture;
+            default:
+                return this.app.loader.resources["black unit"].texture;
+        }
+    }
+
     // private
     create_info() {
         this.info = new PIXI.Text();
@@ -368,6 +451,23 @@ export class Game {
         this.app.stage.addChild(this.skip_button);
     }
 
+    // buttons to surrender and to offer a draw
+    create_end_buttons() {
+        const buttons = [
+            ['Surrender', 'surrender'],
+            ['Offer draw', 'offer_draw'],
+        ];
+        buttons.forEach(([text, cmd], i) => {
+            let button = new PIXI.Text(text);
+            button.buttonMode = true;
+            button.interactive = true;
+            button.position.x = 10;
+            button.position.y = 270 + i * 40;
+            button.on('pointerdown', () => this.send_command(cmd));
+            this.app.stage.addChild(button);
+        });
+    }
+
     create_info_button() {
         this.info_button = new PIXI.Sprite(this.app.loader.resources["info button"].texture);
         this.info_button.buttonMode = true;
@@ -384,6 +484,10 @@ export class Game {
     // private
     clear_info() {
         this.info.text = this.turn_info();
+        if (this.turn_deadline) {
+            const seconds = Math.max(0, Math.round((this.turn_deadline - Date.now()) / 1000));
+            this.info.text += `Time left: ${seconds}s\n`;
+        }
         this.info.text += 'Info:\n';
     }
 
@@ -391,8 +495,10 @@ export class Game {
     set_info(params) {
         this.clear_info();
         for (let [key, value] of Object.entries(params)) {
-            if (key === 'movements') continue;
+            if (key === 'max_hp') continue;
             if (key === 'player') value += 1;
+            if (key === 'movements') value = `${value}/${params.speed}`;
+            if (key === 'hp' && params.max_hp) value = `${value}/${params.max_hp}`;
             let text_value = value;
             if (Array.isArray(value)) {
                 text_value = value.join('-');
@@ -478,6 +584,11 @@ export class Game {
         this.cur_hex = null;
     }
 
+    // send command which doesn't depend on the turn
+    send_command(cmd) {
+        this.socket.send(JSON.stringify({ "cmd": cmd }));
+    }
+
     send_to_backend(cmd, target) {
         if (this.players_action_enabled) {
             this.socket.send(
@@ -495,7 +606,19 @@ export class Game {
     // Change field functions
     // private
     process_selecting(data) {
-        this.grid.hexes[data.target.x][data.target.y].unit.start_pulse();
+        // selecting after move comes while the unit is still on its way
+        let target = this.grid.hexes[data.target.x][data.target.y];
+        if (target.unit) {
+            target.unit.start_pulse();
+        }
+
+        this.grid.reset_targets();
+        data.attack_hexes.forEach(hex_data => {
+            let hex = this.grid.hexes[hex_data.x][hex_data.y];
+
+            hex.state_target = true;
+            hex.fill();
+        });
 
         data.highlight_hexes.forEach(hex_data => {
             let hex = this.grid.hexes[hex_data.x][hex_data.y];
@@ -509,17 +632,28 @@ export class Game {
 
     async process_moving(data) {
         this.players_action_enabled = false;
+        // selecting with the hexes the unit can still reach comes during the animation
+        this.grid.reset_in_path();
         for (let submove of window_iterator(data.coords, 2)) {
             this.move_unit(submove[0].x, submove[0].y, submove[1].x, submove[1].y);
             await sleep(300);
         }
 
-        this.grid.reset_in_path();
+        // the unit has spent movement points
+        let last = data.coords[data.coords.length - 1];
+        let hex = this.grid.hexes[last.x][last.y];
+        if (last.unit && hex.unit) {
+            hex.change_unit(last.unit);
+        }
         this.players_action_enabled = true;
     }
 
     process_attacking(data) {
         console.log('attack!!! charge!!!');
+        console.log(`damage: ${data.damage}`);
+        if (data.retaliation !== null) {
+            console.log(`strikes back with damage: ${data.retaliation}`);
+        }
         // animate attack
     }
 
@@ -547,6 +681,24 @@ export class Game {
         });
     }
 
+    // units appear and disappear in the fog of war
+    process_vision(data) {
+        console.log('process_vision');
+        this.grid.set_visible(data.visible);
+        data.hexes.forEach(hex_data => {
+            let hex = this.grid.hexes[hex_data.x][hex_data.y];
+            if (hex_data.unit) {
+                if (hex.unit) {
+                    hex.change_unit(hex_data.unit);
+                } else {
+                    this.create_unit(this.unit_texture(hex_data.unit.player), this.grid.hex_size, hex_data.unit, hex_data.x, hex_data.y);
+                }
+            } else if (hex.unit) {
+                this.kill_unit(hex_data);
+            }
+        });
+    }
+
     process_queue(data) {
         console.log('process_queue');
         this.queue_status.text = `Players connected: ${data.players_number}; Your number ${data.your_number}\n`;
@@ -570,7 +722,7 @@ export class Game {
         console.log(hex_data);
         let hex = this.grid.hexes[hex_data.x][hex_data.y];
         if (hex_data.content) {
-            hex.set_content(hex_data.content);
+            hex.set_content(hex_data.content.type);
         }
 
         if (hex_data.unit) {
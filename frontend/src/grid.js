@@ -1,7 +1,16 @@
 import * as PIXI from 'pixi.js';
 
+// colors of hexes with terrain, walls have their own color
+const TERRAIN_COLORS = {
+    forest: 0x2e7d32, // green
+    swamp: 0x556b2f, // olive
+    road: 0xc2a878, // sand
+    water: 0x2471a3, // blue
+    hill: 0x9c6b30, // brown
+};
+
 class Hex extends PIXI.Graphics {
-    constructor(x, y, side, border_width, border_color, default_color, mouseover_color, path_color, wall_color) {
+    constructor(x, y, side, border_width, border_color, default_color, mouseover_color, path_color, wall_color, fog_color, target_color) {
         super();
         this.coords = { x: x, y: y };
         this.BORDER_WIDTH = border_width;
@@ -10,9 +19,15 @@ class Hex extends PIXI.Graphics {
         this.MOUSEOVER_COLOR = mouseover_color;
         this.PATH_COLOR = path_color;
         this.WALL_COLOR = wall_color;
+        this.FOG_COLOR = fog_color;
+        this.TARGET_COLOR = target_color;
 
         this.state_selected = false;
         this.state_in_path = false;
+        // enemy here can be attacked by selected unit
+        this.state_target = false;
+        // units of other players here are not seen
+        this.state_fogged = false;
 
         // array of points of hex to draw
         this.points = this.hex_points(side);
@@ -43,10 +58,16 @@ class Hex extends PIXI.Graphics {
 
         if (this.state_selected) {
             color = this.MOUSEOVER_COLOR;
+        } else if (this.state_target) {
+            color = this.TARGET_COLOR;
         } else if (this.state_in_path) {
             color = this.PATH_COLOR;
-        } else if (this.content) {
+        } else if (this.content === 'wall') {
             color = this.WALL_COLOR;
+        } else if (this.content) {
+            color = TERRAIN_COLORS[this.content];
+        } else if (this.state_fogged) {
+            color = this.FOG_COLOR;
         }
 
         this.clear();
@@ -110,6 +131,8 @@ export class Hex_grid {
         this.MOUSEOVER_COLOR = 0x04A348; // green
         this.PATH_COLOR = 0x7b8485; // lite gray
         this.WALL_COLOR = 0x073d44; // dark
+        this.FOG_COLOR = 0x8c7a1a; // dark yellow
+        this.TARGET_COLOR = 0xc0392b; // red
     }
 
     // draw grid on the stage
@@ -125,6 +148,7 @@ export class Hex_grid {
                 let cur_hex = new Hex(x, y, this.hex_size,
                     this.BORDER_WIDTH, this.BORDER_COLOR,
                     this.DEFAULT_COLOR, this.MOUSEOVER_COLOR, this.PATH_COLOR, this.WALL_COLOR,
+                    this.FOG_COLOR, this.TARGET_COLOR,
                 );
 
                 let y_offset = side;
@@ -154,6 +178,34 @@ export class Hex_grid {
         cur_hex.endFill();
     }
 
+    // cover with fog all hexes except visible ones
+    set_visible(points) {
+        this.hexes.forEach(hexes => {
+            hexes.forEach(hex => {
+                hex.state_fogged = true;
+            });
+        });
+        points.forEach(point => {
+            this.hexes[point.x][point.y].state_fogged = false;
+        });
+        this.hexes.forEach(hexes => {
+            hexes.forEach(hex => {
+                hex.fill();
+            });
+        });
+    }
+
+    reset_targets() {
+        this.hexes.forEach(hexes => {
+            hexes.forEach(hex => {
+                if (hex.state_target) {
+                    hex.state_target = false;
+                    hex.fill();
+                }
+            });
+        });
+    }
+
     reset_in_path() {
         this.hexes.forEach(hexes => {
             hexes.forEach(hex => {
@@ -23,5 +23,9 @@ module.exports = {
     port: 8080,
     historyApiFallback: true,
     disableHostCheck: true,  // Not recommended unless necessary
+    // Send api requests to the backend
+    proxy: {
+      '/api': process.env.API_URL || 'http://127.0.0.1:8088',
+    },
   },
 };